@@ -0,0 +1,30 @@
+// Write the C header for what the `ffi` feature exports, from the doc comments and signatures in src/ffi.rs, into the
+// build's OUT_DIR, as build scripts mustn't write into the source tree
+
+#[cfg(feature = "ffi")]
+extern crate cbindgen;
+
+#[cfg(feature = "ffi")]
+fn main() {
+    use std::env;
+    use std::path::Path;
+
+    let dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let source = Path::new(&dir).join("src").join("ffi.rs");
+    println!("cargo:rerun-if-changed={}", source.display());
+    let mut config = cbindgen::Config::default();
+    config.language = cbindgen::Language::C;
+    config.include_guard = Some("AUTOGUILLOTINE_H".to_string());
+    config.header = Some("/* Generated from src/ffi.rs by build.rs, so edit that instead */".to_string());
+    // C has no scoped enums, so `AgStatus::Ok` is written `AgStatus_Ok`
+    config.enumeration.prefix_with_name = true;
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(&source)
+        .generate()
+        .expect("couldn't generate the C header")
+        .write_to_file(Path::new(&env::var("OUT_DIR").unwrap()).join("autoguillotine.h"));
+}
+
+#[cfg(not(feature = "ffi"))]
+fn main() {}
@@ -0,0 +1,123 @@
+// Measure how closely the cuts match hand-labelled pieces, so settings can be tuned on a labelled sample
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::Path;
+
+use image;
+use serde_json;
+
+use {cut_image, GuillotineConfig};
+
+// A piece in pixels, either labelled by hand or found by cutting
+#[derive(Deserialize, Clone, Copy)]
+struct Rectangle {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32
+}
+
+// How much of their union a found piece and a labelled piece have to share to be the same piece
+const MIN_OVERLAP: f64 = 0.5;
+
+// Get the area two rectangles share as a fraction of the area they cover together
+fn overlap(a: Rectangle, b: Rectangle) -> f64 {
+    let width = (a.x + a.width).min(b.x + b.width).saturating_sub(a.x.max(b.x)) as f64;
+    let height = (a.y + a.height).min(b.y + b.height).saturating_sub(a.y.max(b.y)) as f64;
+    let shared = width * height;
+    let union = a.width as f64 * a.height as f64 + b.width as f64 * b.height as f64 - shared;
+    if union > 0.0 { shared / union } else { 0.0 }
+}
+
+// Get how far apart the edges of two rectangles are, on average over the four edges
+fn edge_error(a: Rectangle, b: Rectangle) -> f64 {
+    let distance = |a: u32, b: u32| (a as f64 - b as f64).abs();
+    (distance(a.x, b.x) + distance(a.y, b.y) + distance(a.x + a.width, b.x + b.width) + distance(a.y + a.height, b.y + b.height)) / 4.0
+}
+
+// Pair found pieces with labelled ones, the pairs that overlap most first, using each piece at most once, and get
+// the edge error of each pair
+fn match_pieces(found: &[Rectangle], truth: &[Rectangle]) -> Vec<f64> {
+    let mut pairs: Vec<(usize, usize, f64)> = Vec::new();
+    for (found_index, &a) in found.iter().enumerate() {
+        for (truth_index, &b) in truth.iter().enumerate() {
+            let shared = overlap(a, b);
+            if shared >= MIN_OVERLAP {
+                pairs.push((found_index, truth_index, shared));
+            }
+        }
+    }
+    pairs.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(::std::cmp::Ordering::Equal));
+
+    let (mut found_used, mut truth_used) = (vec![false; found.len()], vec![false; truth.len()]);
+    let mut errors = Vec::new();
+    for (found_index, truth_index, _) in pairs {
+        if found_used[found_index] || truth_used[truth_index] {
+            continue;
+        }
+        found_used[found_index] = true;
+        truth_used[truth_index] = true;
+        errors.push(edge_error(found[found_index], truth[truth_index]));
+    }
+    errors
+}
+
+// Read a ground truth file, which maps each image, by its path or its file name, to its labelled pieces
+fn read_ground_truth(path: &Path) -> Result<BTreeMap<String, Vec<Rectangle>>, String> {
+    let file = File::open(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    serde_json::from_reader(file).map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+// Cut every labelled input and report how many of its pieces were found, returning whether every input could be
+// compared
+pub fn analyze(paths: &[String], ground_truth: &Path, config: &GuillotineConfig) -> bool {
+    let truths = match read_ground_truth(ground_truth) {
+        Ok(truths) => truths,
+        Err(error) => {
+            println!("error: {}", error);
+            return false;
+        }
+    };
+
+    let mut fine = true;
+    let (mut found_total, mut truth_total) = (0, 0);
+    let mut errors = Vec::new();
+    for path in paths {
+        let file_name = Path::new(path).file_name().map(|name| name.to_string_lossy().into_owned());
+        let truth = match truths.get(path).or_else(|| file_name.and_then(|name| truths.get(&name))) {
+            Some(truth) => truth,
+            None => {
+                println!("{}: error: not in {}", path, ground_truth.display());
+                fine = false;
+                continue;
+            }
+        };
+        let image = match image::open(path) {
+            Ok(image) => image,
+            Err(error) => {
+                println!("{}: error: {}", path, error);
+                fine = false;
+                continue;
+            }
+        };
+
+        let found: Vec<Rectangle> = cut_image(path, image, config).0.pieces.iter()
+            .map(|piece| Rectangle { x: piece.x, y: piece.y, width: piece.width, height: piece.height })
+            .collect();
+        let mut matched = match_pieces(&found, truth);
+        println!("{}: {} pieces found, {} labelled, {} matched", path, found.len(), truth.len(), matched.len());
+
+        found_total += found.len();
+        truth_total += truth.len();
+        errors.append(&mut matched);
+    }
+
+    let ratio = |matched: usize, total: usize| if total > 0 { matched as f64 / total as f64 } else { 1.0 };
+    let average_error = if errors.is_empty() { 0.0 } else { errors.iter().sum::<f64>() / errors.len() as f64 };
+    println!(
+        "Precision: {:.3}, recall: {:.3}, average edge error: {:.1} px",
+        ratio(errors.len(), found_total), ratio(errors.len(), truth_total), average_error
+    );
+    fine
+}
@@ -0,0 +1,275 @@
+// Guillotine the images inside zip archives without extracting them to disk
+
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use zip::result::ZipResult;
+
+use image::{DynamicImage, ImageResult};
+
+use image::GenericImage;
+
+use atomic::AtomicFile;
+use cbz::{write_named_cbz, Page};
+use error::Error;
+use icc;
+use manifest::Manifest;
+use naming::Namer;
+use postprocess;
+use {bleed_from, check_expectations, cut_image, cut_too_small, cuttable, encode_piece, piece_dirs, piece_encoding, quarantine, reading_order, stamp, tag_profile, unsupported_feature, GuillotineConfig, OutputOptions, Piece, Processed};
+
+// Whether a path looks like a zip archive, which comic book archives are too
+pub fn is_zip(path: &Path) -> bool {
+    path.extension().map(|extension| extension.eq_ignore_ascii_case("zip") || extension.eq_ignore_ascii_case("cbz")).unwrap_or(false)
+}
+
+// Whether a zip's pieces are written as a comic book archive, which they are if it is one or CBZs were asked for
+pub fn is_comic(path: &Path, output: &OutputOptions) -> bool {
+    output.cbz || path.extension().map(|extension| extension.eq_ignore_ascii_case("cbz")).unwrap_or(false)
+}
+
+// Put the new archive next to the input, as `name-guillotined.zip`, or `.cbz` for a comic
+pub fn output_path(path: &Path, comic: bool) -> PathBuf {
+    let stem = path.file_stem().unwrap().to_string_lossy();
+    path.with_file_name(format!("{}-guillotined.{}", stem, if comic { "cbz" } else { "zip" }))
+}
+
+// Split a name into the runs of text before each run of digits, lowercased, and the digits as a number, so names
+// compare the way people read them
+fn natural_key(name: &str) -> Vec<(String, u64)> {
+    let mut key = Vec::new();
+    let mut text = String::new();
+    let mut number = None;
+    for character in name.chars() {
+        match character.to_digit(10) {
+            Some(digit) => number = Some(number.unwrap_or(0u64).saturating_mul(10).saturating_add(digit as u64)),
+            None => {
+                if let Some(number) = number.take() {
+                    key.push((std::mem::take(&mut text), number));
+                }
+                text.extend(character.to_lowercase());
+            }
+        }
+    }
+    key.push((text, number.unwrap_or(0)));
+    key
+}
+
+// Get the indices of an archive's entries in the order pages are read in, by their names with numbers in them counted
+// as numbers so `page2` comes before `page10`, rather than the order they happen to be stored in
+fn page_order<R: Read + Seek>(archive: &mut ZipArchive<R>) -> ZipResult<Vec<usize>> {
+    let mut keys = Vec::new();
+    for index in 0 .. archive.len() {
+        keys.push((natural_key(&archive.by_index(index)?.name()?), index));
+    }
+    keys.sort();
+    Ok(keys.into_iter().map(|(_, index)| index).collect())
+}
+
+// Decode every image in a zip in page order, passing each one, or why it couldn't be decoded, to the function along
+// with its name
+pub fn for_each_image<F: FnMut(&str, ImageResult<DynamicImage>)>(path: &Path, mut function: F) -> ZipResult<()> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+
+    for index in page_order(&mut archive)? {
+        let mut file = archive.by_index(index)?;
+        if !file.is_file() {
+            continue;
+        }
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        if image::guess_format(&data).is_ok() {
+            function(&file.name()?, image::load_from_memory(&data));
+        }
+    }
+
+    Ok(())
+}
+
+// An image from an archive cut into pieces, in reading order with the row and column of each, and encoded
+struct Cut {
+    source_size: (u32, u32),
+    pieces: Vec<Piece>,
+    grid: Vec<(usize, usize)>,
+    pages: Vec<Page>
+}
+
+// Autoguillotine an image from an archive, adding what came of it to what was processed, or nothing if it's corrupt
+// or failed for being too small or needing a feature this build doesn't have
+fn cut_entry(full_name: &str, data: &[u8], config: &GuillotineConfig, output: &OutputOptions, processed: &mut Processed) -> Option<Cut> {
+    if let Some(error) = unsupported_feature(Path::new(full_name), output) {
+        processed.fail(full_name, error);
+        return None;
+    }
+    let image = match image::load_from_memory(data) {
+        Ok(image) => image,
+        Err(error) => {
+            processed.fail(full_name, Error::Decode(error.to_string()));
+            quarantine(output, full_name, data, &mut processed.log);
+            return None;
+        }
+    };
+    let source_size = image.dimensions();
+    if !cuttable(source_size.0, source_size.1) {
+        processed.fail(full_name, Error::Dimensions(source_size.0, source_size.1));
+        return None;
+    }
+    let encoding = piece_encoding(Some(data), output);
+    let mut source = output.post_process.image_bleed().map(|_| image.to_rgba());
+    let config = match config.for_input(full_name) {
+        Ok(config) => config,
+        Err(error) => {
+            processed.fail(full_name, Error::Settings(error));
+            return None;
+        }
+    };
+    let (mut guillotined, has_alpha) = match cut_too_small(full_name, &image, &config, output) {
+        Some(Ok(cut)) => cut,
+        Some(Err(error)) => {
+            processed.fail(full_name, error);
+            return None;
+        },
+        None => cut_image(full_name, image, &config)
+    };
+    if let (Some(margin), Some(source)) = (output.post_process.image_bleed(), source.as_mut()) {
+        bleed_from(source, &mut guillotined, margin);
+    }
+    processed.log.append(&mut guillotined.log);
+    if let Some(mismatch) = check_expectations(full_name, &guillotined.pieces, output) {
+        processed.log.push(mismatch);
+        processed.mismatched += 1;
+    }
+    let pieces = postprocess::post_process(full_name, guillotined.pieces, &output.post_process, &mut processed.log);
+    let (pieces, grid) = reading_order(pieces);
+    processed.pieces += pieces.len();
+    let profile = icc::embedded_profile(data);
+    let pages = match pieces.iter().map(|piece| Ok(tag_profile(encode_piece(&piece.image, has_alpha, encoding, output)?, profile.as_deref()))).collect::<Result<Vec<_>, String>>() {
+        Ok(pages) => pages,
+        Err(error) => {
+            processed.fail(full_name, Error::Write(Path::new(full_name).with_extension(""), error));
+            return None;
+        }
+    };
+    Some(Cut { source_size, pieces, grid, pages })
+}
+
+// Guillotine every image in a zip into a new zip with the same directory structure, where each image is replaced
+// by a directory of its pieces and a manifest. Anything that isn't an image is copied across as it is.
+// Images that can't be decoded are warned about and copied across as they are too.
+// Returns the log of the decisions made cutting the images, how many couldn't be decoded and how many didn't make the
+// expected pieces.
+pub fn guillotine_zip(path: &Path, output_path: &Path, config: &GuillotineConfig, output: &OutputOptions) -> ZipResult<Processed> {
+    if is_comic(path, output) {
+        return guillotine_comic(path, output_path, config, output);
+    }
+
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut zip = ZipWriter::new(AtomicFile::create(output_path)?);
+    let mut processed = Processed::default();
+    let run = stamp(config, output);
+    // The pieces are already compressed, so there's no point deflating them again
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+    for index in page_order(&mut archive)? {
+        let mut file = archive.by_index(index)?;
+        let name = file.name()?.into_owned();
+
+        if file.is_dir() {
+            zip.add_directory(name, SimpleFileOptions::default())?;
+            continue;
+        }
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        // Copy anything that isn't an image, or is a corrupt one, as it is
+        let full_name = format!("{}/{}", path.display(), name);
+        let cut = if image::guess_format(&data).is_ok() { cut_entry(&full_name, &data, config, output, &mut processed) } else { None };
+        let cut = match cut {
+            Some(cut) => cut,
+            None => {
+                zip.start_file(name, SimpleFileOptions::default())?;
+                zip.write_all(&data)?;
+                continue;
+            }
+        };
+
+        // Replace the image with a directory named after it
+        let dir = Path::new(&name).with_extension("").to_string_lossy().into_owned();
+        let stem = Path::new(&name).file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let (source_width, source_height) = cut.source_size;
+        let mut manifest = Manifest::new(name, source_width, source_height, output.units, run.clone());
+
+        let mut namer = Namer::new(&output.name_template, &stem);
+        let dirs = piece_dirs(&cut.pieces, output);
+        for (i, (piece, page)) in cut.pieces.iter().zip(&cut.pages).enumerate() {
+            let file = format!("{}{}.{}", dirs[i], namer.name(i, cut.grid[i], &piece.image), page.encoding.extension());
+            zip.start_file(format!("{}/{}", dir, file), stored)?;
+            zip.write_all(&page.data)?;
+
+            manifest.push(file, piece, &page.data);
+        }
+
+        zip.start_file(format!("{}/manifest.json", dir), SimpleFileOptions::default())?;
+        zip.write_all(manifest.to_json().as_bytes())?;
+    }
+
+    zip.finish()?.commit()?;
+    Ok(processed)
+}
+
+// Guillotine every page of a comic into a new comic of all the pieces in reading order, named after the number of the
+// page they came from and their place on it so readers show them in order whatever the name template. Each page's
+// manifest is kept under `manifests`, and anything else but the old ComicInfo.xml is copied across as it is.
+fn guillotine_comic(path: &Path, output_path: &Path, config: &GuillotineConfig, output: &OutputOptions) -> ZipResult<Processed> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut processed = Processed::default();
+    let run = stamp(config, output);
+    let order = page_order(&mut archive)?;
+    let digits = order.len().to_string().len();
+
+    let (mut names, mut pages, mut others) = (Vec::new(), Vec::new(), Vec::new());
+    let mut number = 0;
+    for index in order {
+        let mut file = archive.by_index(index)?;
+        let name = file.name()?.into_owned();
+        if file.is_dir() || name.eq_ignore_ascii_case("ComicInfo.xml") {
+            continue;
+        }
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        let full_name = format!("{}/{}", path.display(), name);
+        let cut = if image::guess_format(&data).is_ok() { cut_entry(&full_name, &data, config, output, &mut processed) } else { None };
+        let cut = match cut {
+            Some(cut) => cut,
+            None => {
+                others.push((name, data));
+                continue;
+            }
+        };
+
+        let dir = Path::new(&name).with_extension("").to_string_lossy().into_owned();
+        let (source_width, source_height) = cut.source_size;
+        let mut manifest = Manifest::new(name, source_width, source_height, output.units, run.clone());
+        let piece_digits = cut.pages.len().to_string().len();
+        for (i, (piece, page)) in cut.pieces.iter().zip(&cut.pages).enumerate() {
+            let file = format!("{:0digits$}-{:0piece_digits$}.{}", number, i, page.encoding.extension(), digits = digits, piece_digits = piece_digits);
+            manifest.push(file.clone(), piece, &page.data);
+            names.push(file);
+        }
+        pages.extend(cut.pages);
+        others.push((format!("manifests/{}/manifest.json", dir), manifest.to_json().into_bytes()));
+        number += 1;
+    }
+
+    let title = path.file_stem().unwrap().to_string_lossy();
+    let source = path.file_name().unwrap().to_string_lossy();
+    write_named_cbz(output_path, &title, &source, &names, &pages, &others)?;
+    Ok(processed)
+}
@@ -0,0 +1,64 @@
+// Write outputs under a temporary name and rename them into place once they're complete, so a crash never leaves a
+// half-written file behind under its real name
+
+use std::fs::{remove_file, rename, File};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+// A file being written next to where it will end up
+pub struct AtomicFile {
+    file: File,
+    temporary: PathBuf,
+    path: PathBuf,
+    committed: bool
+}
+
+impl AtomicFile {
+    // Start writing a file that will be at the path once committed. The temporary name starts with a dot, so it's
+    // hidden and doesn't have the real file's extension
+    pub fn create(path: &Path) -> io::Result<AtomicFile> {
+        let name = path.file_name().unwrap().to_string_lossy();
+        let temporary = path.with_file_name(format!(".{}.partial", name));
+        Ok(AtomicFile { file: File::create(&temporary)?, temporary, path: path.to_path_buf(), committed: false })
+    }
+
+    // Flush the file to disk and move it into place
+    pub fn commit(mut self) -> io::Result<()> {
+        self.file.sync_all()?;
+        rename(&self.temporary, &self.path)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+// Clean up the temporary file if it was never committed
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = remove_file(&self.temporary);
+        }
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.file.write(buffer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Seek for AtomicFile {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.file.seek(position)
+    }
+}
+
+// Write a whole file at once
+pub fn write(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = AtomicFile::create(path)?;
+    file.write_all(data)?;
+    file.commit()
+}
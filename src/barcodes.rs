@@ -0,0 +1,98 @@
+// Find the barcodes and QR codes on a scanned sheet, to anchor each item's piece to the code printed on it
+
+use std::path::Path;
+
+#[cfg(feature = "barcodes")]
+use std::process::{Command, Stdio};
+
+use Piece;
+
+// A code found in an image, and the centre of where it was found
+pub struct Code {
+    pub value: String,
+    pub x: f64,
+    pub y: f64
+}
+
+// Whether codes can be read, which needs the `barcodes` feature and the `zbarimg` command
+pub fn barcodes_supported() -> bool {
+    cfg!(feature = "barcodes")
+}
+
+// Read the codes in an image file with zbarimg, which exits with a failure when it finds none
+#[cfg(feature = "barcodes")]
+pub fn read_codes(path: &Path) -> Vec<Code> {
+    let output = Command::new("zbarimg").arg("--xml").arg("-q").arg(path)
+        .stdin(Stdio::null()).stderr(Stdio::null())
+        .output();
+    match output {
+        Ok(output) => parse_codes(&String::from_utf8_lossy(&output.stdout)),
+        Err(_) => Vec::new()
+    }
+}
+
+#[cfg(not(feature = "barcodes"))]
+pub fn read_codes(_path: &Path) -> Vec<Code> {
+    Vec::new()
+}
+
+// Get the text between the first `start` and the `end` after it
+#[cfg(feature = "barcodes")]
+fn between<'a>(text: &'a str, start: &str, end: &str) -> Option<&'a str> {
+    let rest = &text[text.find(start)? + start.len() ..];
+    Some(&rest[.. rest.find(end)?])
+}
+
+// Get the codes out of zbarimg's XML, where each symbol has the corners of where it was found like `+10,+20` and its
+// value in CDATA sections, leaving out symbols without corners, which older versions don't give, and binary values
+#[cfg(feature = "barcodes")]
+fn parse_codes(xml: &str) -> Vec<Code> {
+    xml.split("<symbol ").skip(1)
+        .filter_map(|symbol| {
+            let symbol = &symbol[.. symbol.find("</symbol>")?];
+            let corners: Vec<(f64, f64)> = between(symbol, "points='", "'")?.split_whitespace()
+                .filter_map(|point| {
+                    let mut parts = point.split(',');
+                    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+                })
+                .collect();
+            if corners.is_empty() {
+                return None;
+            }
+            let value: String = between(symbol, "<data>", "</data>")?.split("<![CDATA[").skip(1)
+                .filter_map(|part| part.find("]]>").map(|end| &part[.. end]))
+                .collect();
+            let count = corners.len() as f64;
+            Some(Code {
+                value,
+                x: corners.iter().map(|&(x, _)| x).sum::<f64>() / count,
+                y: corners.iter().map(|&(_, y)| y).sum::<f64>() / count
+            })
+        })
+        .collect()
+}
+
+// How far a point is from a piece, which is zero inside it
+fn distance(piece: &Piece, x: f64, y: f64) -> f64 {
+    let dx = (piece.x as f64 - x).max(x - (piece.x + piece.width) as f64).max(0.0);
+    let dy = (piece.y as f64 - y).max(y - (piece.y + piece.height) as f64).max(0.0);
+    dx.hypot(dy)
+}
+
+// Anchor each code to the piece it's on, or the nearest piece to it if it fell in a gap, and get the code each piece
+// is anchored by. A piece with more than one code on it is anchored by the first, reading across then down.
+pub fn anchor(mut codes: Vec<Code>, pieces: &[Piece]) -> Vec<Option<String>> {
+    codes.sort_by(|a, b| (a.y, a.x).partial_cmp(&(b.y, b.x)).unwrap());
+    let mut anchored = vec![None; pieces.len()];
+    for code in codes {
+        let nearest = pieces.iter().enumerate()
+            .min_by(|&(_, a), &(_, b)| distance(a, code.x, code.y).partial_cmp(&distance(b, code.x, code.y)).unwrap())
+            .map(|(index, _)| index);
+        if let Some(index) = nearest {
+            if anchored[index].is_none() {
+                anchored[index] = Some(code.value);
+            }
+        }
+    }
+    anchored
+}
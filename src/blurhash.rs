@@ -0,0 +1,92 @@
+// Encode pieces as BlurHashes, short strings that decode to a blurred placeholder of the piece
+
+use std::f64::consts::PI;
+
+use image::{imageops, FilterType, RgbaImage};
+
+const ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+// How many cosine components to encode across and down
+const COMPONENTS_X: u32 = 4;
+const COMPONENTS_Y: u32 = 3;
+// The hash only keeps the lowest frequencies, so there's no point looking at more pixels than this along a side
+const MAX_SIDE: u32 = 64;
+
+fn base83(value: u32, length: u32, hash: &mut String) {
+    for i in 1 ..= length {
+        let digit = value / 83u32.pow(length - i) % 83;
+        hash.push(ALPHABET[digit as usize] as char);
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f64 {
+    let value = value as f64 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f64) -> u32 {
+    let value = value.clamp(0.0, 1.0);
+    if value <= 0.0031308 {
+        (value * 12.92 * 255.0 + 0.5) as u32
+    } else {
+        ((1.055 * value.powf(1.0 / 2.4) - 0.055) * 255.0 + 0.5) as u32
+    }
+}
+
+fn sign_pow(value: f64, exponent: f64) -> f64 {
+    value.abs().powf(exponent).copysign(value)
+}
+
+// Get the BlurHash of an image, ignoring its alpha channel
+pub fn blurhash(image: &RgbaImage) -> String {
+    let (width, height) = image.dimensions();
+    let scale = MAX_SIDE as f64 / width.max(height) as f64;
+    let small;
+    let image = if scale < 1.0 {
+        let (small_width, small_height) = (((width as f64 * scale) as u32).max(1), ((height as f64 * scale) as u32).max(1));
+        small = imageops::resize(image, small_width, small_height, FilterType::Triangle);
+        &small
+    } else {
+        image
+    };
+    let (width, height) = image.dimensions();
+
+    // The weight of each cosine in each channel, in linear light
+    let mut factors = Vec::with_capacity((COMPONENTS_X * COMPONENTS_Y) as usize);
+    for j in 0 .. COMPONENTS_Y {
+        for i in 0 .. COMPONENTS_X {
+            let normalisation = if i == 0 && j == 0 { 1.0 } else { 2.0 };
+            let mut factor = [0.0; 3];
+            for (x, y, pixel) in image.enumerate_pixels() {
+                let basis = normalisation
+                    * (PI * i as f64 * x as f64 / width as f64).cos()
+                    * (PI * j as f64 * y as f64 / height as f64).cos();
+                for (channel, value) in factor.iter_mut().enumerate() {
+                    *value += basis * srgb_to_linear(pixel.data[channel]);
+                }
+            }
+            let pixels = width as f64 * height as f64;
+            factors.push([factor[0] / pixels, factor[1] / pixels, factor[2] / pixels]);
+        }
+    }
+
+    let mut hash = String::new();
+    base83((COMPONENTS_X - 1) + (COMPONENTS_Y - 1) * 9, 1, &mut hash);
+
+    let (dc, ac) = factors.split_first().unwrap();
+    let actual_maximum = ac.iter().flat_map(|factor| factor.iter()).fold(0.0f64, |maximum, value| maximum.max(value.abs()));
+    let quantised_maximum = ((actual_maximum * 166.0 - 0.5).floor() as i64).clamp(0, 82) as u32;
+    let maximum = (quantised_maximum + 1) as f64 / 166.0;
+    base83(quantised_maximum, 1, &mut hash);
+
+    base83((linear_to_srgb(dc[0]) << 16) + (linear_to_srgb(dc[1]) << 8) + linear_to_srgb(dc[2]), 4, &mut hash);
+    for factor in ac {
+        let quantise = |value: f64| ((sign_pow(value / maximum, 0.5) * 9.0 + 9.5).floor() as i64).clamp(0, 18) as u32;
+        base83(quantise(factor[0]) * 19 * 19 + quantise(factor[1]) * 19 + quantise(factor[2]), 2, &mut hash);
+    }
+
+    hash
+}
@@ -0,0 +1,115 @@
+// Write guillotined pieces into a comic book archive with ComicInfo.xml metadata
+
+use std::io::Write;
+use std::path::Path;
+
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+use zip::result::ZipResult;
+
+use atomic::AtomicFile;
+
+// The format a page is encoded in
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Encoding {
+    Png,
+    Jpeg,
+    Bmp,
+    Tiff,
+    Webp
+}
+
+impl Encoding {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Encoding::Png => "png",
+            Encoding::Jpeg => "jpg",
+            Encoding::Bmp => "bmp",
+            Encoding::Tiff => "tif",
+            Encoding::Webp => "webp"
+        }
+    }
+
+    pub fn media_type(self) -> &'static str {
+        match self {
+            Encoding::Png => "image/png",
+            Encoding::Jpeg => "image/jpeg",
+            Encoding::Bmp => "image/bmp",
+            Encoding::Tiff => "image/tiff",
+            Encoding::Webp => "image/webp"
+        }
+    }
+}
+
+// An encoded piece, ready to be stored as a page
+pub struct Page {
+    pub width: u32,
+    pub height: u32,
+    pub encoding: Encoding,
+    pub data: Vec<u8>
+}
+
+// Escape the characters that aren't allowed in XML text and attributes
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// Name pages with enough leading zeros that readers sort them in order
+pub fn page_name(index: usize, pages: usize, encoding: Encoding) -> String {
+    let digits = pages.to_string().len();
+    format!("{:0width$}.{}", index, encoding.extension(), width = digits)
+}
+
+// Describe the pages and where they came from in the ComicInfo format
+fn comic_info(title: &str, source: &str, pages: &[Page]) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<ComicInfo xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n");
+    xml.push_str(&format!("  <Title>{}</Title>\n", escape_xml(title)));
+    xml.push_str(&format!("  <Notes>Guillotined from {}</Notes>\n", escape_xml(source)));
+    xml.push_str(&format!("  <PageCount>{}</PageCount>\n", pages.len()));
+    xml.push_str("  <Pages>\n");
+    for (index, page) in pages.iter().enumerate() {
+        xml.push_str(&format!(
+            "    <Page Image=\"{}\" ImageWidth=\"{}\" ImageHeight=\"{}\" ImageSize=\"{}\" />\n",
+            index, page.width, page.height, page.data.len()
+        ));
+    }
+    xml.push_str("  </Pages>\n");
+    xml.push_str("</ComicInfo>\n");
+    xml
+}
+
+// Write the pages, in order, to a CBZ file
+pub fn write_cbz(path: &Path, title: &str, source: &str, pages: &[Page]) -> ZipResult<()> {
+    let names: Vec<String> = pages.iter().enumerate().map(|(index, page)| page_name(index, pages.len(), page.encoding)).collect();
+    write_named_cbz(path, title, source, &names, pages, &[])
+}
+
+// Write the pages, in order, to a CBZ file under the given names, which have to sort in the same order, with other
+// files alongside them
+pub fn write_named_cbz(path: &Path, title: &str, source: &str, names: &[String], pages: &[Page], others: &[(String, Vec<u8>)]) -> ZipResult<()> {
+    let mut zip = ZipWriter::new(AtomicFile::create(path)?);
+    // The pages are already compressed, so there's no point deflating them again
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+    for (name, page) in names.iter().zip(pages) {
+        zip.start_file(name.as_str(), stored)?;
+        zip.write_all(&page.data)?;
+    }
+    for &(ref name, ref data) in others {
+        zip.start_file(name.as_str(), SimpleFileOptions::default())?;
+        zip.write_all(data)?;
+    }
+
+    zip.start_file("ComicInfo.xml", SimpleFileOptions::default())?;
+    zip.write_all(comic_info(title, source, pages).as_bytes())?;
+
+    zip.finish()?.commit()?;
+    Ok(())
+}
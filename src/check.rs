@@ -0,0 +1,177 @@
+// Check inputs before a run by decoding only their headers
+
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek};
+use std::path::Path;
+
+use image::{ColorType, ImageDecoder, ImageFormat, ImageResult};
+use zip::ZipArchive;
+
+use archive::is_zip;
+use GuillotineConfig;
+
+// A rough guess at how many pixels a second the detection gets through, to estimate run times
+const PIXELS_PER_SECOND: f64 = 5_000_000.0;
+// Images with a side longer than this many times the other are likely to be mistakes
+const MAX_ASPECT_RATIO: u32 = 50;
+// Images with more pixels than this will need a lot of memory
+const LARGE_PIXELS: u64 = 100_000_000;
+
+// What the header of an image says about it
+struct Header {
+    format: ImageFormat,
+    width: u32,
+    height: u32,
+    color: ColorType
+}
+
+// Read the dimensions and colour type of an image from its header
+fn read_header<R: Read + Seek>(mut reader: R) -> ImageResult<Header> {
+    let mut magic = [0; 16];
+    let read = reader.read(&mut magic)?;
+    reader.seek(::std::io::SeekFrom::Start(0))?;
+    let format = image::guess_format(&magic[.. read])?;
+
+    let ((width, height), color) = match format {
+        ImageFormat::PNG => {
+            let mut decoder = image::png::PNGDecoder::new(reader);
+            (decoder.dimensions()?, decoder.colortype()?)
+        },
+        ImageFormat::JPEG => {
+            let mut decoder = image::jpeg::JPEGDecoder::new(reader);
+            (decoder.dimensions()?, decoder.colortype()?)
+        },
+        ImageFormat::GIF => {
+            let mut decoder = image::gif::Decoder::new(reader);
+            (decoder.dimensions()?, decoder.colortype()?)
+        },
+        ImageFormat::WEBP => {
+            let mut decoder = image::webp::WebpDecoder::new(reader);
+            (decoder.dimensions()?, decoder.colortype()?)
+        },
+        ImageFormat::PPM => {
+            let mut decoder = image::ppm::PPMDecoder::new(reader)?;
+            (decoder.dimensions()?, decoder.colortype()?)
+        },
+        ImageFormat::TIFF => {
+            let mut decoder = image::tiff::TIFFDecoder::new(reader)?;
+            (decoder.dimensions()?, decoder.colortype()?)
+        },
+        ImageFormat::BMP => {
+            let mut decoder = image::bmp::BMPDecoder::new(reader);
+            (decoder.dimensions()?, decoder.colortype()?)
+        },
+        ImageFormat::ICO => {
+            let mut decoder = image::ico::ICODecoder::new(reader)?;
+            (decoder.dimensions()?, decoder.colortype()?)
+        },
+        _ => return Err(image::ImageError::UnsupportedError(format!("{:?} images aren't supported", format)))
+    };
+
+    Ok(Header { format, width, height, color })
+}
+
+// Describe an image's header and anything about it that would cause trouble, returning whether it is fine
+fn report(name: &str, header: ImageResult<Header>, config: &GuillotineConfig) -> bool {
+    let header = match header {
+        Ok(header) => header,
+        Err(error) => {
+            println!("{}: error: {}", name, error);
+            return false;
+        }
+    };
+
+    let (width, height) = (header.width, header.height);
+    let pixels = width as u64 * height as u64;
+    // The decoded image, its RGBA copy and the pieces cut from it are all in memory at once
+    let memory = pixels * 4 * 3;
+    let seconds = pixels as f64 * 2.0 / PIXELS_PER_SECOND;
+
+    println!(
+        "{}: {:?} {}x{} {:?}, ~{:.1} MB, ~{:.1} s",
+        name, header.format, width, height, header.color, memory as f64 / 1e6, seconds
+    );
+
+    let mut warnings = Vec::new();
+    if width == 0 || height == 0 {
+        warnings.push("the image is empty".to_string());
+    } else if config.too_small(width, height) {
+        warnings.push("the image is smaller than the minimum size and won't produce any pieces".to_string());
+    } else if width / height >= MAX_ASPECT_RATIO || height / width >= MAX_ASPECT_RATIO {
+        warnings.push(format!("the image has an extreme aspect ratio of {}:{}", width, height));
+    }
+    if pixels > LARGE_PIXELS {
+        warnings.push(format!("the image has {} megapixels", pixels / 1_000_000));
+    }
+    match header.color {
+        ColorType::RGB(8) | ColorType::RGBA(8) | ColorType::Gray(8) | ColorType::GrayA(8) => {},
+        ColorType::Palette(_) => warnings.push("the image is paletted and will be saved as full colour pieces".to_string()),
+        color => warnings.push(format!("the {:?} colour type will be converted to 8 bits per channel", color))
+    }
+
+    for warning in &warnings {
+        println!("  warning: {}", warning);
+    }
+
+    warnings.is_empty()
+}
+
+// Check an input, returning whether everything in it is fine
+fn check_path(path: &Path, config: &GuillotineConfig) -> bool {
+    let name = path.display().to_string();
+
+    if !is_zip(path) {
+        let header = File::open(path).map_err(From::from).and_then(|file| read_header(BufReader::new(file)));
+        return report(&name, header, config);
+    }
+
+    let mut archive = match File::open(path).map_err(From::from).and_then(ZipArchive::new) {
+        Ok(archive) => archive,
+        Err(error) => {
+            println!("{}: error: {}", name, error);
+            return false;
+        }
+    };
+
+    let mut fine = true;
+    for index in 0 .. archive.len() {
+        let mut data = Vec::new();
+        let entry = archive.by_index(index).and_then(|mut file| {
+            file.read_to_end(&mut data)?;
+            Ok((file.name()?.into_owned(), file.is_file()))
+        });
+
+        match entry {
+            // Only images are guillotined, so anything else is fine to pass through
+            Ok((entry_name, true)) if image::guess_format(&data).is_ok() => {
+                fine &= report(&format!("{}/{}", name, entry_name), read_header(Cursor::new(data)), config);
+            },
+            Ok(_) => {},
+            Err(error) => {
+                println!("{}: error: {}", name, error);
+                fine = false;
+            }
+        }
+    }
+    fine
+}
+
+// Read the dimensions of an image from its header, without decoding it
+pub fn image_dimensions(path: &Path) -> Option<(u32, u32)> {
+    let header = File::open(path).map_err(From::from).and_then(|file| read_header(BufReader::new(file)));
+    header.ok().map(|header| (header.width, header.height))
+}
+
+// Check every input, returning whether they are all fine
+pub fn check(paths: &[String], config: &GuillotineConfig) -> bool {
+    let mut problems = 0;
+
+    for path in paths {
+        if !check_path(Path::new(path), config) {
+            problems += 1;
+        }
+    }
+
+    println!("{} of {} inputs have problems", problems, paths.len());
+    problems == 0
+}
@@ -0,0 +1,70 @@
+// Build up a directory of pieces over several runs, numbering each run's pieces on from the ones already there and
+// listing the manifest of every input that's been added in one file
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde_json::{self, Value};
+
+use atomic;
+use manifest::Manifest;
+
+// The file in a collection's directory that lists the manifests of the inputs added to it
+pub const COLLECTION_FILE: &str = "collection.json";
+
+// A collection's inputs as they're stored, so the ones already added don't have to be understood to keep them
+#[derive(Serialize, Deserialize, Default)]
+struct Stored {
+    inputs: Vec<Value>
+}
+
+// The inputs added to a collection so far
+pub struct Collection {
+    path: PathBuf,
+    stored: Stored,
+    sources: HashSet<String>,
+    pieces: usize
+}
+
+impl Collection {
+    // Open the collection in a directory, or start a new one if there isn't one there yet
+    pub fn open(dir: &Path) -> Result<Collection, String> {
+        let path = dir.join(COLLECTION_FILE);
+        let stored: Stored = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(|error| format!("{}: {}", path.display(), error))?,
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => Stored::default(),
+            Err(error) => return Err(format!("{}: {}", path.display(), error))
+        };
+        let sources = stored.inputs.iter().filter_map(|input| input["source"].as_str()).map(String::from).collect();
+        let pieces = stored.inputs.iter().filter_map(|input| input["pieces"].as_array()).map(Vec::len).sum();
+        Ok(Collection { path, stored, sources, pieces })
+    }
+
+    // Whether an input has already been added
+    pub fn contains(&self, source: &str) -> bool {
+        self.sources.contains(source)
+    }
+
+    // How many pieces and inputs have been added, where the number of pieces is the index the next one is given
+    pub fn pieces(&self) -> usize {
+        self.pieces
+    }
+
+    pub fn inputs(&self) -> usize {
+        self.stored.inputs.len()
+    }
+
+    // Add an input, once its pieces have been saved in the collection's directory
+    pub fn add(&mut self, manifest: &Manifest) {
+        self.sources.insert(manifest.source.clone());
+        self.pieces += manifest.pieces.len();
+        self.stored.inputs.push(serde_json::to_value(manifest).unwrap());
+    }
+
+    // Save the list of inputs, with the ones added this run after those from before
+    pub fn save(&self) -> io::Result<()> {
+        atomic::write(&self.path, serde_json::to_string_pretty(&self.stored).unwrap().as_bytes())
+    }
+}
@@ -0,0 +1,31 @@
+// Compare colours perceptually, in the CIE L*a*b* space
+
+use image::Rgba;
+
+// Undo the sRGB transfer curve of a channel, giving its linear light from 0 to 1
+fn linear(channel: u8) -> f64 {
+    let value = channel as f64 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// Convert an sRGB pixel to L*a*b* under the D65 white point, ignoring its alpha
+pub fn to_lab(pixel: &Rgba<u8>) -> [f64; 3] {
+    let (red, green, blue) = (linear(pixel[0]), linear(pixel[1]), linear(pixel[2]));
+    let x = (0.4124 * red + 0.3576 * green + 0.1805 * blue) / 0.95047;
+    let y = 0.2126 * red + 0.7152 * green + 0.0722 * blue;
+    let z = (0.0193 * red + 0.1192 * green + 0.9505 * blue) / 1.08883;
+
+    let f = |t: f64| if t > 0.008856 { t.cbrt() } else { 7.787 * t + 16.0 / 116.0 };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+// Get the CIE76 colour difference between two pixels, where about 2.3 is just noticeable
+pub fn delta_e(a: &Rgba<u8>, b: &Rgba<u8>) -> f64 {
+    let (a, b) = (to_lab(a), to_lab(b));
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
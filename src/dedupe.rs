@@ -0,0 +1,75 @@
+// Find pieces that are the same as one already written from an input, as sprite sheets and UI atlases repeat tiles,
+// either exactly or, if a distance is given, by how alike they look
+
+use image::{self, RgbaImage};
+
+use luma;
+use sha256::sha256;
+
+// A piece that was written, and what it looks like
+struct Written {
+    exact: String,
+    perceptual: u64,
+    file: String,
+    sha256: String
+}
+
+// The file a duplicate's rectangle points at instead of being written, and what that file holds
+pub struct Original {
+    pub file: String,
+    pub sha256: String
+}
+
+// The pieces of an input written so far
+pub struct Dedupe {
+    // The most bits the perceptual hashes of two pieces can differ by for them to be the same, if they're compared
+    // by how they look as well as exactly
+    max_distance: Option<u32>,
+    written: Vec<Written>
+}
+
+// Hash an image's size and pixels, so only identical pieces have the same hash
+fn exact_hash(image: &RgbaImage) -> String {
+    let mut data = Vec::with_capacity(8 + image.len());
+    data.extend_from_slice(&image.width().to_be_bytes());
+    data.extend_from_slice(&image.height().to_be_bytes());
+    data.extend_from_slice(image);
+    sha256(&data)
+}
+
+// Hash how an image looks with a difference hash: shrink it to 9 by 8 greys and set a bit for each grey that's
+// brighter than the one to its right, so pieces that only differ by noise or compression have close hashes
+fn perceptual_hash(image: &RgbaImage) -> u64 {
+    let small = image::imageops::resize(image, 9, 8, image::imageops::FilterType::Triangle);
+    let mut hash = 0;
+    for y in 0 .. 8 {
+        for x in 0 .. 8 {
+            hash <<= 1;
+            if luma(small.get_pixel(x, y)) > luma(small.get_pixel(x + 1, y)) {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+impl Dedupe {
+    pub fn new(max_distance: Option<u32>) -> Dedupe {
+        Dedupe { max_distance, written: Vec::new() }
+    }
+
+    // Find the piece already written that a piece duplicates, or else remember it as written to a file with the data
+    pub fn original(&mut self, image: &RgbaImage, file: &str, data: &[u8]) -> Option<Original> {
+        let exact = exact_hash(image);
+        let perceptual = perceptual_hash(image);
+        let max_distance = self.max_distance;
+        let found = self.written.iter().find(|written| {
+            written.exact == exact || max_distance.map_or(false, |max_distance| (written.perceptual ^ perceptual).count_ones() <= max_distance)
+        });
+        if let Some(written) = found {
+            return Some(Original { file: written.file.clone(), sha256: written.sha256.clone() });
+        }
+        self.written.push(Written { exact, perceptual, file: file.to_string(), sha256: sha256(data) });
+        None
+    }
+}
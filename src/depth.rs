@@ -0,0 +1,122 @@
+// Keep the bit depth and colour type of 16-bit and greyscale PNGs and TIFFs in their pieces, which are otherwise taken
+// from the eight bit RGBA copy the cuts are found in
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use image::{ColorType, DecodingResult, ImageDecoder};
+use image::tiff::TIFFDecoder;
+use png::{self, HasParameters};
+
+use cbz;
+use formats;
+use Piece;
+
+// An image as a PNG stores it, with its rows of samples packed one after another, and 16-bit samples big-endian
+pub struct DeepImage {
+    width: u32,
+    height: u32,
+    color: png::ColorType,
+    depth: png::BitDepth,
+    data: Vec<u8>
+}
+
+impl DeepImage {
+    fn bytes_per_pixel(&self) -> usize {
+        let bytes_per_sample = if self.depth == png::BitDepth::Sixteen { 2 } else { 1 };
+        self.color.samples() * bytes_per_sample
+    }
+
+    // Encode the rectangle of the image a piece was cut from as a PNG or TIFF of the same colour type and depth, or
+    // give up if the rectangle isn't in the image or it's to be saved in another format
+    pub fn encode_piece(&self, piece: &Piece, encoding: cbz::Encoding) -> Option<cbz::Page> {
+        if piece.x + piece.width > self.width || piece.y + piece.height > self.height {
+            return None;
+        }
+        let bytes_per_pixel = self.bytes_per_pixel();
+        let stride = self.width as usize * bytes_per_pixel;
+        let row_length = piece.width as usize * bytes_per_pixel;
+        let mut rows = Vec::with_capacity(row_length * piece.height as usize);
+        for y in piece.y .. piece.y + piece.height {
+            let start = y as usize * stride + piece.x as usize * bytes_per_pixel;
+            rows.extend_from_slice(&self.data[start .. start + row_length]);
+        }
+
+        let data = match encoding {
+            cbz::Encoding::Png => {
+                let mut data = Vec::new();
+                {
+                    let mut encoder = png::Encoder::new(&mut data, piece.width, piece.height);
+                    encoder.set(self.color).set(self.depth);
+                    let mut writer = encoder.write_header().ok()?;
+                    writer.write_image_data(&rows).ok()?;
+                }
+                data
+            },
+            cbz::Encoding::Tiff => {
+                // TIFFs are written little-endian
+                let bits = if self.depth == png::BitDepth::Sixteen {
+                    for sample in rows.chunks_mut(2) {
+                        sample.swap(0, 1);
+                    }
+                    16
+                } else {
+                    8
+                };
+                let greyscale = matches!(self.color, png::ColorType::Grayscale | png::ColorType::GrayscaleAlpha);
+                let has_alpha = matches!(self.color, png::ColorType::GrayscaleAlpha | png::ColorType::RGBA);
+                formats::write_tiff((piece.width, piece.height), self.color.samples() as u16, bits, greyscale, has_alpha, &rows)
+            },
+            _ => return None
+        };
+        Some(cbz::Page { width: piece.width, height: piece.height, encoding, data })
+    }
+}
+
+// Read a PNG or TIFF at its own depth if it's 16-bit or greyscale
+pub fn read(path: &Path) -> Option<DeepImage> {
+    read_png(path).or_else(|| read_tiff(path))
+}
+
+// Read a PNG at its own depth if it's 16-bit or greyscale, as the pieces of any other PNG lose nothing by being taken
+// from the eight bit RGBA copy. Palettes are expanded, and so are greyscale images of under eight bits.
+fn read_png(path: &Path) -> Option<DeepImage> {
+    let file = File::open(path).ok()?;
+    let mut decoder = png::Decoder::new(BufReader::new(file));
+    decoder.set(png::TRANSFORM_EXPAND);
+    let (info, mut reader) = decoder.read_info().ok()?;
+    let (color, depth) = reader.output_color_type();
+    let greyscale = matches!(color, png::ColorType::Grayscale | png::ColorType::GrayscaleAlpha);
+    if !(greyscale || depth == png::BitDepth::Sixteen) {
+        return None;
+    }
+    let mut data = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut data).ok()?;
+    Some(DeepImage { width: info.width, height: info.height, color, depth, data })
+}
+
+// Read a TIFF at its own depth if it's 16-bit or greyscale, the same way as a PNG
+fn read_tiff(path: &Path) -> Option<DeepImage> {
+    let file = File::open(path).ok()?;
+    let mut decoder = TIFFDecoder::new(BufReader::new(file)).ok()?;
+    let (width, height) = decoder.dimensions().ok()?;
+    let (color, depth) = match decoder.colortype().ok()? {
+        ColorType::Gray(8) => (png::ColorType::Grayscale, png::BitDepth::Eight),
+        ColorType::GrayA(8) => (png::ColorType::GrayscaleAlpha, png::BitDepth::Eight),
+        ColorType::Gray(16) => (png::ColorType::Grayscale, png::BitDepth::Sixteen),
+        ColorType::GrayA(16) => (png::ColorType::GrayscaleAlpha, png::BitDepth::Sixteen),
+        ColorType::RGB(16) => (png::ColorType::RGB, png::BitDepth::Sixteen),
+        ColorType::RGBA(16) => (png::ColorType::RGBA, png::BitDepth::Sixteen),
+        _ => return None
+    };
+    let data = match decoder.read_image().ok()? {
+        DecodingResult::U8(data) => data,
+        DecodingResult::U16(samples) => samples.iter().flat_map(|sample| sample.to_be_bytes().to_vec()).collect()
+    };
+    let image = DeepImage { width, height, color, depth, data };
+    if image.data.len() < width as usize * height as usize * image.bytes_per_pixel() {
+        return None;
+    }
+    Some(image)
+}
@@ -0,0 +1,106 @@
+// Find how far a scan was rotated on the glass and straighten it, so that the gutters between its pieces run along
+// whole rows and columns again
+
+use image::{self, RgbaImage};
+
+use estimate_background;
+
+// The largest skew, in degrees, that deskewing looks for, and how finely
+const MAX_SKEW: f64 = 5.0;
+const SKEW_STEP: f64 = 0.25;
+// How far a pixel's channels may stray from the background before deskewing counts it as content
+const SKEW_TOLERANCE: f64 = 32.0;
+
+// Find the angle, in degrees, that makes the rows of the content line up best, by how peaky the count of content
+// pixels in each row is once they are rotated by it
+pub fn skew_angle(image: &RgbaImage) -> f64 {
+    let background = estimate_background(image);
+    let content: Vec<(f64, f64)> = image.enumerate_pixels()
+        .filter(|&(_, _, pixel)| (0 .. 3).any(|channel| (pixel[channel] as f64 - background[channel] as f64).abs() > SKEW_TOLERANCE))
+        .map(|(x, y, _)| (x as f64, y as f64))
+        .collect();
+    if content.is_empty() {
+        return 0.0;
+    }
+
+    let diagonal = (image.width() as f64).hypot(image.height() as f64).ceil() as usize;
+    let steps = (MAX_SKEW / SKEW_STEP) as i32;
+    let mut best = (0.0, 0.0);
+
+    for step in -steps ..= steps {
+        let angle = step as f64 * SKEW_STEP;
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let mut rows = vec![0u64; diagonal * 2 + 1];
+        for &(x, y) in &content {
+            let row = (y * cos - x * sin).round() as i64 + diagonal as i64;
+            rows[row as usize] += 1;
+        }
+        let score = rows.iter().map(|&count| (count * count) as f64).sum::<f64>();
+        if score > best.1 {
+            best = (angle, score);
+        }
+    }
+    best.0
+}
+
+// Rotate an image about its centre, filling the corners with its background
+pub fn rotate(image: &RgbaImage, degrees: f64) -> RgbaImage {
+    let background = estimate_background(image);
+    let (width, height) = image.dimensions();
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (centre_x, centre_y) = (width as f64 / 2.0, height as f64 / 2.0);
+
+    RgbaImage::from_fn(width, height, |x, y| {
+        // Find where the pixel came from and blend the four pixels around it
+        let (dx, dy) = (x as f64 + 0.5 - centre_x, y as f64 + 0.5 - centre_y);
+        let source_x = dx * cos - dy * sin + centre_x - 0.5;
+        let source_y = dx * sin + dy * cos + centre_y - 0.5;
+        let (left, top) = (source_x.floor(), source_y.floor());
+        let (fraction_x, fraction_y) = (source_x - left, source_y - top);
+
+        let sample = |x: f64, y: f64| {
+            if x < 0.0 || y < 0.0 || x >= width as f64 || y >= height as f64 {
+                background
+            } else {
+                *image.get_pixel(x as u32, y as u32)
+            }
+        };
+        let corners = [
+            (sample(left, top), (1.0 - fraction_x) * (1.0 - fraction_y)),
+            (sample(left + 1.0, top), fraction_x * (1.0 - fraction_y)),
+            (sample(left, top + 1.0), (1.0 - fraction_x) * fraction_y),
+            (sample(left + 1.0, top + 1.0), fraction_x * fraction_y)
+        ];
+
+        let mut pixel = image::Rgba {data: [0; 4]};
+        for channel in 0 .. 4 {
+            let value: f64 = corners.iter().map(|&(corner, weight)| corner[channel] as f64 * weight).sum();
+            pixel[channel] = value.round() as u8;
+        }
+        pixel
+    })
+}
+
+// Get the rectangle of the image as it was that holds a rectangle of the image rotated by `rotate`, as x, y, width and
+// height, within the width and height of the image
+pub fn source_rectangle(rectangle: [u32; 4], width: u32, height: u32, degrees: f64) -> [u32; 4] {
+    let [x, y, piece_width, piece_height] = rectangle;
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (centre_x, centre_y) = (width as f64 / 2.0, height as f64 / 2.0);
+
+    let corners = [(x, y), (x + piece_width, y), (x, y + piece_height), (x + piece_width, y + piece_height)];
+    let (mut left, mut top, mut right, mut bottom) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+    for &(corner_x, corner_y) in &corners {
+        let (dx, dy) = (corner_x as f64 - centre_x, corner_y as f64 - centre_y);
+        let (source_x, source_y) = (dx * cos - dy * sin + centre_x, dx * sin + dy * cos + centre_y);
+        left = left.min(source_x);
+        top = top.min(source_y);
+        right = right.max(source_x);
+        bottom = bottom.max(source_y);
+    }
+
+    let clamp = |value: f64, size: u32| value.max(0.0).min(size as f64) as u32;
+    let (left, top) = (clamp(left.floor(), width - 1), clamp(top.floor(), height - 1));
+    let (right, bottom) = (clamp(right.ceil(), width).max(left + 1), clamp(bottom.ceil(), height).max(top + 1));
+    [left, top, right - left, bottom - top]
+}
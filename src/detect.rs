@@ -0,0 +1,123 @@
+// Measure an image the way it would be cut without cutting out any pieces, so other programs can draw the
+// profiles, the places that could be cut and the cuts chosen over their own view of it
+
+use image::{self, GenericImage};
+
+use {cut_image, cuttable, difference_horizontal, difference_vertical, GuillotineConfig, Guillotined, Profile};
+
+// What was measured across the lines in one direction
+#[derive(Clone, Serialize)]
+pub struct ProfileReport {
+    // The difference between each pair of neighbouring lines, after any relative threshold and window, where the
+    // first is between lines 0 and 1
+    pub differences: Vec<f64>,
+    // How much each line varies within itself
+    pub deviations: Vec<f64>,
+    // How many of each line's pixels are content rather than background
+    pub contents: Vec<u32>,
+    // The places that could be cut, at the peaks of the differences over the threshold
+    pub candidates: Vec<Candidate>
+}
+
+// A place that could be cut, before the line it's at, and its difference
+#[derive(Clone, Copy, Serialize)]
+pub struct Candidate {
+    pub line: u32,
+    pub difference: f64
+}
+
+// A cut that was chosen, along a row if it's horizontal or a column if not, from one end of the region it cut to the
+// other, with its difference and how far its true place lies from the line, in lines
+#[derive(Clone, Copy, Serialize)]
+pub struct Cut {
+    pub horizontal: bool,
+    pub line: u32,
+    pub start: u32,
+    pub end: u32,
+    pub difference: f64,
+    pub subpixel: f64
+}
+
+// Everything measured to cut an image: the profiles across its rows and columns, the cuts chosen and the rectangles
+// they leave as x, y, width and height
+#[derive(Serialize)]
+pub struct DetectionReport {
+    pub width: u32,
+    pub height: u32,
+    pub horizontal: Option<ProfileReport>,
+    pub vertical: Option<ProfileReport>,
+    pub cuts: Vec<Cut>,
+    pub pieces: Vec<[u32; 4]>,
+    pub log: Vec<String>
+}
+
+// Report a profile, with the differences scored as cuts are chosen by and the peaks among them
+fn report_profile(mut profile: Profile, config: &GuillotineConfig, min_gap: u32, forbidden: &[(u32, u32)]) -> ProfileReport {
+    profile.score(config);
+    let threshold = config.relative_threshold.unwrap_or(config.threshold);
+    let values = &profile.values;
+    let is_peak = |index: usize| {
+        (index == 0 || values[index - 1] <= values[index]) && (index + 1 == values.len() || values[index + 1] <= values[index])
+    };
+    let candidates = profile.candidates(min_gap, forbidden, 0, config).into_iter()
+        .filter(|&(line, difference)| difference > threshold && is_peak(line as usize - 1))
+        .map(|(line, difference)| Candidate { line, difference })
+        .collect();
+    ProfileReport {
+        differences: profile.values.clone(),
+        deviations: profile.deviations,
+        contents: profile.contents,
+        candidates
+    }
+}
+
+// Get the cuts made from the edges of the pieces they left, joining the stretches of each cut that pieces on either
+// side of it share
+fn report_cuts(guillotined: &Guillotined) -> Vec<Cut> {
+    let mut cuts: Vec<Cut> = Vec::new();
+    for piece in &guillotined.pieces {
+        let edges = [piece.x, piece.y, piece.x + piece.width, piece.y + piece.height];
+        for side in 0 .. 4 {
+            if let Some(difference) = piece.scores[side] {
+                let horizontal = side % 2 == 1;
+                let (start, end) = if horizontal { (piece.x, piece.x + piece.width) } else { (piece.y, piece.y + piece.height) };
+                cuts.push(Cut { horizontal, line: edges[side], start, end, difference, subpixel: piece.subpixel[side] });
+            }
+        }
+    }
+    cuts.sort_by_key(|cut| (!cut.horizontal, cut.line, cut.start));
+
+    let mut joined: Vec<Cut> = Vec::new();
+    for cut in cuts {
+        match joined.last_mut() {
+            Some(last) if last.horizontal == cut.horizontal && last.line == cut.line && cut.start <= last.end && last.difference == cut.difference => {
+                last.end = last.end.max(cut.end);
+            },
+            _ => joined.push(cut)
+        }
+    }
+    joined
+}
+
+// Measure an image as it would be cut with the config, without cutting out the pieces. As with `guillotine`, none of
+// the config's overrides apply.
+pub fn detect(image: &image::DynamicImage, config: &GuillotineConfig) -> DetectionReport {
+    let config = GuillotineConfig { overrides: Vec::new(), ..config.clone() };
+    let (width, height) = image.dimensions();
+    let mut report = DetectionReport { width, height, horizontal: None, vertical: None, cuts: Vec::new(), pieces: Vec::new(), log: Vec::new() };
+    if !cuttable(width, height) {
+        report.log.push(format!("Warning: the image is {}x{}, which is empty or too big to cut", width, height));
+        return report;
+    }
+
+    let rgba = image.to_rgba();
+    let background = config.background(&rgba);
+    report.horizontal = Some(report_profile(difference_horizontal(&rgba, background, &config), &config, config.min_gap_horizontal, &config.forbid_horizontal));
+    report.vertical = Some(report_profile(difference_vertical(&rgba, background, &config), &config, config.min_gap_vertical, &config.forbid_vertical));
+
+    let (guillotined, _) = cut_image("", image::DynamicImage::ImageRgba8(rgba), &config);
+    report.cuts = report_cuts(&guillotined);
+    report.pieces = guillotined.pieces.iter().map(|piece| [piece.x, piece.y, piece.width, piece.height]).collect();
+    report.log = guillotined.log;
+    report
+}
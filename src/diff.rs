@@ -0,0 +1,121 @@
+// Compare the manifests of two runs to find the images whose cuts changed
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use serde_json;
+use zip::ZipArchive;
+
+use archive::is_zip;
+
+// The parts of a manifest that say where the cuts were
+#[derive(Deserialize)]
+struct Cuts {
+    source: String,
+    units: String,
+    pieces: Vec<Rectangle>
+}
+
+#[derive(Deserialize, PartialEq)]
+struct Rectangle {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64
+}
+
+impl Rectangle {
+    fn describe(&self) -> String {
+        format!("{}x{} at ({}, {})", self.width, self.height, self.x, self.y)
+    }
+}
+
+// Read the manifests of a run, either a single manifest or every manifest in a guillotined zip, keyed by the image
+// they describe
+fn read_manifests(path: &Path) -> Result<BTreeMap<String, Cuts>, String> {
+    let parse = |name: &str, data: &[u8]| -> Result<Cuts, String> {
+        serde_json::from_slice(data).map_err(|error| format!("{}: {}", name, error))
+    };
+    let mut manifests = BTreeMap::new();
+
+    if !is_zip(path) {
+        let mut data = Vec::new();
+        File::open(path).and_then(|mut file| file.read_to_end(&mut data)).map_err(|error| format!("{}: {}", path.display(), error))?;
+        let cuts = parse(&path.display().to_string(), &data)?;
+        manifests.insert(cuts.source.clone(), cuts);
+        return Ok(manifests);
+    }
+
+    let mut archive = File::open(path).map_err(From::from).and_then(ZipArchive::new)
+        .map_err(|error| format!("{}: {}", path.display(), error))?;
+    for index in 0 .. archive.len() {
+        let mut file = archive.by_index(index).map_err(|error| format!("{}: {}", path.display(), error))?;
+        let name = file.name().map_err(|error| format!("{}: {}", path.display(), error))?.into_owned();
+        if name != "manifest.json" && !name.ends_with("/manifest.json") {
+            continue;
+        }
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).map_err(|error| format!("{}/{}: {}", path.display(), name, error))?;
+        let cuts = parse(&format!("{}/{}", path.display(), name), &data)?;
+        manifests.insert(cuts.source.clone(), cuts);
+    }
+    Ok(manifests)
+}
+
+// Describe how an image's cuts changed, if they did
+fn compare(old: &Cuts, new: &Cuts) -> Vec<String> {
+    if old.units != new.units {
+        return vec![format!("units changed from {} to {}, so the pieces can't be compared", old.units, new.units)];
+    }
+
+    let mut changes = Vec::new();
+    for piece in old.pieces.iter().filter(|piece| !new.pieces.contains(piece)) {
+        changes.push(format!("- {}", piece.describe()));
+    }
+    for piece in new.pieces.iter().filter(|piece| !old.pieces.contains(piece)) {
+        changes.push(format!("+ {}", piece.describe()));
+    }
+    changes
+}
+
+// Report the images whose cuts differ between two runs, returning whether they're all the same
+pub fn diff(old_path: &Path, new_path: &Path) -> bool {
+    let (old, new) = match (read_manifests(old_path), read_manifests(new_path)) {
+        (Ok(old), Ok(new)) => (old, new),
+        (Err(error), _) | (_, Err(error)) => {
+            println!("error: {}", error);
+            return false;
+        }
+    };
+
+    let mut changed = 0;
+    for (source, old_cuts) in &old {
+        let changes = match new.get(source) {
+            Some(new_cuts) => compare(old_cuts, new_cuts),
+            None => vec!["only in the old run".to_string()]
+        };
+        if changes.is_empty() {
+            continue;
+        }
+
+        changed += 1;
+        match new.get(source) {
+            Some(new_cuts) => println!("{}: {} pieces -> {} pieces", source, old_cuts.pieces.len(), new_cuts.pieces.len()),
+            None => println!("{}:", source)
+        }
+        for change in changes {
+            println!("  {}", change);
+        }
+    }
+    for source in new.keys().filter(|source| !old.contains_key(*source)) {
+        changed += 1;
+        println!("{}:\n  only in the new run", source);
+    }
+
+    let images = old.len() + new.keys().filter(|source| !old.contains_key(*source)).count();
+    println!("{} of {} images changed", changed, images);
+    changed == 0
+}
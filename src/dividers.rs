@@ -0,0 +1,73 @@
+// Find the solid dark rules some layouts separate their panels with, rather than gaps
+
+use image::RgbaImage;
+
+use luma;
+
+// The share of a line that has to be dark for it to be part of a rule, allowing for rules that stop short of the edges
+const MIN_DARK_SHARE: f64 = 0.9;
+// The most of the image a rule can cover, so dark artwork running across a panel isn't taken for one
+const MAX_RULE_SHARE: f64 = 0.05;
+
+// A rule, as the first line it covers and the line after its last
+pub type Rule = (u32, u32);
+
+// Find the runs of lines across the image that are almost all darker than the luma, horizontal ones going across
+// and vertical ones going down
+pub fn find_rules(image: &RgbaImage, horizontal: bool, max_luma: u8) -> Vec<Rule> {
+    let (width, height) = image.dimensions();
+    let (lines, length) = if horizontal { (height, width) } else { (width, height) };
+
+    let dark: Vec<bool> = (0 .. lines)
+        .map(|line| {
+            let pixels = (0 .. length)
+                .filter(|&along| {
+                    let pixel = if horizontal { image.get_pixel(along, line) } else { image.get_pixel(line, along) };
+                    luma(pixel) <= max_luma
+                })
+                .count();
+            pixels as f64 >= length as f64 * MIN_DARK_SHARE
+        })
+        .collect();
+
+    let mut rules = Vec::new();
+    let mut start = None;
+    for (line, &is_dark) in dark.iter().enumerate() {
+        match (is_dark, start) {
+            (true, None) => start = Some(line as u32),
+            (false, Some(rule_start)) => {
+                rules.push((rule_start, line as u32));
+                start = None;
+            },
+            _ => {}
+        }
+    }
+    if let Some(rule_start) = start {
+        rules.push((rule_start, lines));
+    }
+    rules.retain(|&(start, end)| (end - start) as f64 <= lines as f64 * MAX_RULE_SHARE);
+    rules
+}
+
+// Get the spans of lines between rules, either split through the middle of each rule or, when removing them, with the
+// rules left out, including any along the image's edges
+pub fn spans_between(rules: &[Rule], lines: u32, remove: bool) -> Vec<(u32, u32)> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    for &(rule_start, rule_end) in rules {
+        if remove {
+            if rule_start > start {
+                spans.push((start, rule_start));
+            }
+            start = rule_end;
+        } else if rule_start > 0 && rule_end < lines {
+            let middle = (rule_start + rule_end) / 2;
+            spans.push((start, middle));
+            start = middle;
+        }
+    }
+    if lines > start {
+        spans.push((start, lines));
+    }
+    spans
+}
@@ -0,0 +1,146 @@
+// Write guillotined pieces into a fixed-layout EPUB, one piece per page
+
+use std::env;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+use zip::result::ZipResult;
+
+use atomic::AtomicFile;
+use cbz::{escape_xml, Page};
+
+const CONTAINER: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml" />
+  </rootfiles>
+</container>
+"#;
+
+// Format the current time, or SOURCE_DATE_EPOCH if it's set so reruns make identical books, as an ISO 8601 UTC
+// timestamp, as required for dcterms:modified
+pub fn timestamp() -> String {
+    let seconds = env::var("SOURCE_DATE_EPOCH").ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0));
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+
+    // Convert days since the epoch into a civil date
+    let days = days as i64 + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, seconds / 3600, seconds / 60 % 60, seconds % 60
+    )
+}
+
+// A page that shows a single piece at its full size
+fn page_xhtml(index: usize, page: &Page) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+  <head>
+    <title>{index}</title>
+    <meta name="viewport" content="width={width}, height={height}" />
+    <style>body {{ margin: 0; }} img {{ display: block; width: {width}px; height: {height}px; }}</style>
+  </head>
+  <body>
+    <img src="../images/{index}.{extension}" alt="" />
+  </body>
+</html>
+"#,
+        index = index, extension = page.encoding.extension(), width = page.width, height = page.height
+    )
+}
+
+// The table of contents, which points at the first page
+fn nav_xhtml(title: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+  <head><title>{title}</title></head>
+  <body>
+    <nav epub:type="toc">
+      <ol><li><a href="pages/0.xhtml">{title}</a></li></ol>
+    </nav>
+  </body>
+</html>
+"#,
+        title = escape_xml(title)
+    )
+}
+
+// The package document listing every page in reading order
+fn content_opf(title: &str, pages: &[Page]) -> String {
+    let mut opf = String::new();
+    opf.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    opf.push_str("<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"id\" prefix=\"rendition: http://www.idpf.org/vocab/rendition/#\">\n");
+    opf.push_str("  <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n");
+    opf.push_str(&format!("    <dc:identifier id=\"id\">urn:autoguillotine:{}</dc:identifier>\n", escape_xml(title)));
+    opf.push_str(&format!("    <dc:title>{}</dc:title>\n", escape_xml(title)));
+    opf.push_str("    <dc:language>en</dc:language>\n");
+    opf.push_str(&format!("    <meta property=\"dcterms:modified\">{}</meta>\n", timestamp()));
+    opf.push_str("    <meta property=\"rendition:layout\">pre-paginated</meta>\n");
+    opf.push_str("    <meta property=\"rendition:spread\">none</meta>\n");
+    opf.push_str("  </metadata>\n");
+
+    opf.push_str("  <manifest>\n");
+    opf.push_str("    <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\" />\n");
+    for (index, page) in pages.iter().enumerate() {
+        opf.push_str(&format!("    <item id=\"page{0}\" href=\"pages/{0}.xhtml\" media-type=\"application/xhtml+xml\" />\n", index));
+        opf.push_str(&format!(
+            "    <item id=\"image{0}\" href=\"images/{0}.{1}\" media-type=\"{2}\" />\n",
+            index, page.encoding.extension(), page.encoding.media_type()
+        ));
+    }
+    opf.push_str("  </manifest>\n");
+
+    opf.push_str("  <spine>\n");
+    for index in 0 .. pages.len() {
+        opf.push_str(&format!("    <itemref idref=\"page{}\" />\n", index));
+    }
+    opf.push_str("  </spine>\n");
+    opf.push_str("</package>\n");
+    opf
+}
+
+// Write the pages, in order, to an EPUB file
+pub fn write_epub(path: &Path, title: &str, pages: &[Page]) -> ZipResult<()> {
+    let mut zip = ZipWriter::new(AtomicFile::create(path)?);
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default();
+
+    // The mimetype has to come first and be uncompressed
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/epub+zip")?;
+
+    zip.start_file("META-INF/container.xml", deflated)?;
+    zip.write_all(CONTAINER.as_bytes())?;
+    zip.start_file("OEBPS/content.opf", deflated)?;
+    zip.write_all(content_opf(title, pages).as_bytes())?;
+    zip.start_file("OEBPS/nav.xhtml", deflated)?;
+    zip.write_all(nav_xhtml(title).as_bytes())?;
+
+    for (index, page) in pages.iter().enumerate() {
+        zip.start_file(format!("OEBPS/pages/{}.xhtml", index), deflated)?;
+        zip.write_all(page_xhtml(index, page).as_bytes())?;
+        zip.start_file(format!("OEBPS/images/{}.{}", index, page.encoding.extension()), stored)?;
+        zip.write_all(&page.data)?;
+    }
+
+    zip.finish()?.commit()?;
+    Ok(())
+}
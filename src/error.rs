@@ -0,0 +1,51 @@
+// What can go wrong with an input, so it can be reported at the end of the run while the other inputs carry on
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum Error {
+    // The input, or the archive it's in, couldn't be read
+    Read(String),
+    // The input was read but isn't an image that can be decoded
+    Decode(String),
+    // The image is too small to make any pieces, at this width and height
+    TooSmall(u32, u32),
+    // The image has no pixels, or is too big to cut, at this width and height
+    Dimensions(u32, u32),
+    // The settings its directories or an override give the input are invalid
+    Settings(String),
+    // The input needs something this build can't do, which needs it built with the cargo feature
+    UnsupportedFeature(&'static str, &'static str),
+    // One of the input's outputs couldn't be written
+    Write(PathBuf, String),
+    // Processing the input panicked, which is caught so the other inputs still get processed
+    Panic(String)
+}
+
+impl Error {
+    // Make an error writing to a path out of the error that stopped it
+    pub fn writing<E: fmt::Display>(path: &Path) -> impl FnOnce(E) -> Error {
+        let path = path.to_path_buf();
+        move |error| Error::Write(path, error.to_string())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Read(ref error) => write!(formatter, "couldn't read it: {}", error),
+            Error::Decode(ref error) => write!(formatter, "couldn't decode it: {}", error),
+            Error::TooSmall(width, height) => write!(formatter, "it's {}x{}, too small to make any pieces", width, height),
+            Error::Dimensions(width, height) => write!(formatter, "it's {}x{}, which is empty or too big to cut", width, height),
+            Error::Settings(ref error) => write!(formatter, "its settings are invalid: {}", error),
+            Error::UnsupportedFeature(what, feature) => {
+                write!(formatter, "it needs {}, which needs autoguillotine built with the {} feature (--features {})", what, feature, feature)
+            },
+            Error::Write(ref path, ref error) => write!(formatter, "couldn't write {}: {}", path.display(), error),
+            Error::Panic(ref message) => write!(formatter, "crashed: {}", message)
+        }
+    }
+}
+
+impl std::error::Error for Error {}
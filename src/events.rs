@@ -0,0 +1,132 @@
+// Tell other services about each piece as soon as it's written, by posting a JSON message about it to a webhook, a
+// Kafka topic or an AMQP exchange, so they don't have to poll the output directories
+
+use std::path::Path;
+
+#[cfg(feature = "events")]
+use std::io::Write;
+#[cfg(feature = "events")]
+use std::process::{Command, Stdio};
+
+#[cfg(feature = "events")]
+use serde_json;
+
+use Piece;
+
+// Where the messages go, which is only read when they can be sent
+#[derive(Clone)]
+#[cfg_attr(not(feature = "events"), allow(dead_code))]
+pub enum Sink {
+    // Post each message to a URL, with curl
+    Webhook(String),
+    // Produce each message to a topic on a comma-separated list of brokers, with kcat
+    Kafka { brokers: String, topic: String },
+    // Publish each message to an exchange on a server with a routing key, with amqp-publish
+    Amqp { url: String, exchange: String, routing_key: String }
+}
+
+// Parse where to send messages: `http://` or `https://` URLs for webhooks, `kafka://BROKERS/TOPIC` and
+// `amqp://SERVER#EXCHANGE/ROUTING_KEY`, where the server can be `amqps://`
+impl ::std::str::FromStr for Sink {
+    type Err = ();
+
+    fn from_str(string: &str) -> Result<Sink, ()> {
+        if string.starts_with("http://") || string.starts_with("https://") {
+            Ok(Sink::Webhook(string.to_string()))
+        } else if let Some(rest) = string.strip_prefix("kafka://") {
+            let (brokers, topic) = rest.split_once('/').ok_or(())?;
+            if brokers.is_empty() || topic.is_empty() {
+                return Err(());
+            }
+            Ok(Sink::Kafka { brokers: brokers.to_string(), topic: topic.to_string() })
+        } else if string.starts_with("amqp://") || string.starts_with("amqps://") {
+            let (url, route) = string.split_once('#').ok_or(())?;
+            let (exchange, routing_key) = route.split_once('/').unwrap_or((route, ""));
+            Ok(Sink::Amqp { url: url.to_string(), exchange: exchange.to_string(), routing_key: routing_key.to_string() })
+        } else {
+            Err(())
+        }
+    }
+}
+
+// The message about a piece: the input it was cut from, where it was written, its rectangle in the input and the
+// difference that made the cut along each of its left, top, right and bottom edges
+#[derive(Serialize)]
+pub struct PieceEvent {
+    pub source: String,
+    pub path: String,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub scores: [Option<f64>; 4]
+}
+
+impl PieceEvent {
+    pub fn new(source: &str, path: &Path, piece: &Piece) -> PieceEvent {
+        PieceEvent {
+            source: source.to_string(),
+            path: path.to_string_lossy().into_owned(),
+            x: piece.x,
+            y: piece.y,
+            width: piece.width,
+            height: piece.height,
+            scores: piece.scores
+        }
+    }
+}
+
+// Whether messages can be sent, which needs the `events` feature and the command for the sink
+pub fn events_supported() -> bool {
+    cfg!(feature = "events")
+}
+
+// Run a command with the messages on its standard input, returning why it failed if it did
+#[cfg(feature = "events")]
+fn run(command: &mut Command, input: &str) -> Result<(), String> {
+    let name = format!("{:?}", command.get_program());
+    let mut child = command.stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::piped()).spawn()
+        .map_err(|error| format!("failed to run {}: {}", name, error))?;
+    child.stdin.take().unwrap().write_all(input.as_bytes()).map_err(|error| error.to_string())?;
+    let output = child.wait_with_output().map_err(|error| error.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} exited with {}: {}", name, output.status, String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+// Send a message for each piece, logging a warning for each that couldn't be sent rather than failing the input, as
+// its pieces were still written
+#[cfg(feature = "events")]
+pub fn publish(sink: &Sink, events: &[PieceEvent], log: &mut Vec<String>) {
+    let messages: Vec<String> = events.iter().map(|event| serde_json::to_string(event).unwrap()).collect();
+    let mut warn = |result: Result<(), String>| {
+        if let Err(error) = result {
+            log.push(format!("Warning: couldn't send a piece's message: {}", error));
+        }
+    };
+    match *sink {
+        Sink::Webhook(ref url) => {
+            for message in &messages {
+                warn(run(Command::new("curl").args(["-sS", "-f", "-X", "POST", "-H", "Content-Type: application/json", "--data-binary", "@-"]).arg(url), message));
+            }
+        },
+        // kcat produces a message for each line
+        Sink::Kafka { ref brokers, ref topic } => {
+            if !messages.is_empty() {
+                let mut lines = messages.join("\n");
+                lines.push('\n');
+                warn(run(Command::new("kcat").arg("-P").arg("-b").arg(brokers).arg("-t").arg(topic), &lines));
+            }
+        },
+        Sink::Amqp { ref url, ref exchange, ref routing_key } => {
+            for message in &messages {
+                warn(run(Command::new("amqp-publish").arg("-u").arg(url).arg("-e").arg(exchange).arg("-r").arg(routing_key), message));
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "events"))]
+pub fn publish(_sink: &Sink, _events: &[PieceEvent], _log: &mut Vec<String>) {}
@@ -0,0 +1,124 @@
+// Guillotine images from C and C++, with the library built as a cdylib. The caller owns all the memory: it passes in
+// the pixels and a buffer for the rectangles, and nothing is allocated that it has to free.
+// The exported items have doc comments, as they're copied into the generated header.
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::slice;
+
+use image::{self, ImageBuffer};
+
+use {apply_settings, guillotine, GuillotineConfig};
+
+/// A piece's rectangle in the image, in pixels
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct AgRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32
+}
+
+/// What came of a call
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum AgStatus {
+    Ok = 0,
+    /// A pointer was null, the size or number of channels was invalid, the stride was too short for a row or the pixels
+    /// were too many to address
+    InvalidArgument = 1,
+    /// The settings weren't UTF-8 or had a setting that isn't one or an invalid value
+    InvalidSettings = 2,
+    /// There were more pieces than the buffer holds, so none were written, and the count is how many it needs to hold
+    BufferTooSmall = 3,
+    /// Cutting the image failed unexpectedly
+    Failed = 4
+}
+
+/// Guillotine an image and write its pieces' rectangles into a buffer, in the order they were cut.
+///
+/// `pixels` holds `height` rows, `stride` bytes apart, of `width` pixels of `channels` bytes each: 1 for grey, 2 for
+/// grey and alpha, 3 for RGB and 4 for RGBA. `settings` is null for the defaults, or settings in the form
+/// `threshold=0.5,min-size=50`, where each name is a command line option without its dashes.
+///
+/// `rects` is a buffer of `capacity` rectangles, which can be null if `capacity` is 0. `count` is set to the number
+/// of pieces whenever the image could be cut, and if the buffer is too small for them, nothing is written into it and
+/// `BufferTooSmall` is returned, so the call can be made again with a buffer of that size.
+///
+/// # Safety
+///
+/// `pixels` must point to at least `stride * (height - 1) + width * channels` readable bytes, `settings` must be null
+/// or a NUL-terminated string, `rects` must point to `capacity` writable rectangles and `count` to a writable size.
+/// None of the pointers are kept after the call returns.
+#[no_mangle]
+pub unsafe extern "C" fn ag_guillotine(
+    pixels: *const u8, width: u32, height: u32, stride: usize, channels: u32, settings: *const c_char, rects: *mut AgRect,
+    capacity: usize, count: *mut usize
+) -> AgStatus {
+    if pixels.is_null() || count.is_null() || (rects.is_null() && capacity > 0) || width == 0 || height == 0 ||
+        !(1 ..= 4).contains(&channels) {
+        return AgStatus::InvalidArgument;
+    }
+    // A size that overflows would wrap round to a slice shorter than the pixels are read from
+    let length = (width as usize).checked_mul(channels as usize)
+        .filter(|&row_length| stride >= row_length)
+        .and_then(|row_length| (height as usize - 1).checked_mul(stride)?.checked_add(row_length));
+    let length = match length {
+        Some(length) => length,
+        None => return AgStatus::InvalidArgument
+    };
+
+    let mut config = GuillotineConfig::default();
+    if !settings.is_null() {
+        let applied = CStr::from_ptr(settings).to_str().map_err(|error| error.to_string())
+            .and_then(|settings| apply_settings(&mut config, settings));
+        if applied.is_err() {
+            return AgStatus::InvalidSettings;
+        }
+    }
+
+    let data = slice::from_raw_parts(pixels, length);
+    // Unwinding into C is undefined, so a panic, whether building the image or cutting it, is reported as a failure
+    // instead
+    let cut = catch_unwind(AssertUnwindSafe(|| guillotine(to_image(data, width, height, stride, channels), &config).pieces));
+    let pieces = match cut {
+        Ok(pieces) => pieces,
+        Err(_) => return AgStatus::Failed
+    };
+    *count = pieces.len();
+    if pieces.len() > capacity {
+        return AgStatus::BufferTooSmall;
+    }
+    if !pieces.is_empty() {
+        let rects = slice::from_raw_parts_mut(rects, pieces.len());
+        for (rect, piece) in rects.iter_mut().zip(&pieces) {
+            *rect = AgRect { x: piece.x, y: piece.y, width: piece.width, height: piece.height };
+        }
+    }
+    AgStatus::Ok
+}
+
+// Copy the caller's pixels into an image, where only images with an alpha channel have it compared
+fn to_image(data: &[u8], width: u32, height: u32, stride: usize, channels: u32) -> image::DynamicImage {
+    let pixel = |x: u32, y: u32| &data[y as usize * stride + x as usize * channels as usize ..];
+    match channels {
+        1 => image::DynamicImage::ImageRgb8(ImageBuffer::from_fn(width, height, |x, y| {
+            let pixel = pixel(x, y);
+            image::Rgb { data: [pixel[0], pixel[0], pixel[0]] }
+        })),
+        2 => image::DynamicImage::ImageRgba8(ImageBuffer::from_fn(width, height, |x, y| {
+            let pixel = pixel(x, y);
+            image::Rgba { data: [pixel[0], pixel[0], pixel[0], pixel[1]] }
+        })),
+        3 => image::DynamicImage::ImageRgb8(ImageBuffer::from_fn(width, height, |x, y| {
+            let pixel = pixel(x, y);
+            image::Rgb { data: [pixel[0], pixel[1], pixel[2]] }
+        })),
+        _ => image::DynamicImage::ImageRgba8(ImageBuffer::from_fn(width, height, |x, y| {
+            let pixel = pixel(x, y);
+            image::Rgba { data: [pixel[0], pixel[1], pixel[2], pixel[3]] }
+        }))
+    }
+}
@@ -0,0 +1,219 @@
+// Encode pieces in the formats the image library can't write, and work out what format and quality an input was saved
+// in so its pieces can be saved the same way
+
+#[cfg(feature = "webp")]
+use std::fs;
+#[cfg(feature = "webp")]
+use std::process::{self, Command, Stdio};
+#[cfg(feature = "webp")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use image::{self, RgbaImage};
+
+use cbz::Encoding;
+
+// The standard JPEG luminance quantization table at quality 50, in the zigzag order tables are stored in
+const STANDARD_LUMINANCE: [u16; 64] = [
+    16, 12, 11, 10, 12, 14, 14, 13, 14, 16, 24, 19, 16, 17, 18, 24, 22, 22, 24, 26, 40, 51, 58, 40, 29, 37, 35, 49, 72, 64,
+    55, 56, 51, 57, 60, 61, 55, 69, 87, 68, 64, 78, 92, 95, 87, 81, 109, 80, 56, 62, 103, 104, 103, 98, 112, 121, 113, 77,
+    92, 120, 100, 103, 101, 99
+];
+
+// Encode an image as an uncompressed BMP, with 32 bits a pixel and a header describing the alpha if it has any and
+// 24 bits otherwise
+pub fn encode_bmp(image: &RgbaImage, has_alpha: bool) -> Vec<u8> {
+    let (width, height) = image.dimensions();
+    let bytes_per_pixel = if has_alpha { 4 } else { 3 };
+    let row_length = (width * bytes_per_pixel + 3) / 4 * 4;
+    let header_length: u32 = if has_alpha { 108 } else { 40 };
+    let offset = 14 + header_length;
+    let file_length = offset + row_length * height;
+
+    let mut data = Vec::with_capacity(file_length as usize);
+    let u16_le = |data: &mut Vec<u8>, value: u16| data.extend_from_slice(&value.to_le_bytes());
+    let u32_le = |data: &mut Vec<u8>, value: u32| data.extend_from_slice(&value.to_le_bytes());
+
+    // The file header
+    data.extend_from_slice(b"BM");
+    u32_le(&mut data, file_length);
+    u32_le(&mut data, 0);
+    u32_le(&mut data, offset);
+
+    // The info header, or the version 4 header that adds the channel masks and colour space
+    u32_le(&mut data, header_length);
+    u32_le(&mut data, width);
+    u32_le(&mut data, height);
+    u16_le(&mut data, 1);
+    u16_le(&mut data, bytes_per_pixel as u16 * 8);
+    u32_le(&mut data, if has_alpha { 3 } else { 0 });
+    u32_le(&mut data, row_length * height);
+    // 72 DPI, in pixels per metre
+    u32_le(&mut data, 2835);
+    u32_le(&mut data, 2835);
+    u32_le(&mut data, 0);
+    u32_le(&mut data, 0);
+    if has_alpha {
+        for &mask in &[0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0xff00_0000] {
+            u32_le(&mut data, mask);
+        }
+        // sRGB, with no endpoints or gamma
+        data.extend_from_slice(b"BGRs");
+        data.extend_from_slice(&[0; 48]);
+    }
+
+    // The rows, from the bottom up, with each padded to a multiple of 4 bytes
+    for y in (0 .. height).rev() {
+        let start = data.len();
+        for x in 0 .. width {
+            let pixel = image.get_pixel(x, y);
+            data.extend_from_slice(&[pixel[2], pixel[1], pixel[0]]);
+            if has_alpha {
+                data.push(pixel[3]);
+            }
+        }
+        data.resize(start + row_length as usize, 0);
+    }
+
+    data
+}
+
+// Encode an image as an uncompressed baseline TIFF in a single strip, keeping the alpha if it has any
+pub fn encode_tiff(image: &RgbaImage, has_alpha: bool) -> Vec<u8> {
+    let samples = if has_alpha { 4 } else { 3 };
+    let mut pixels = Vec::with_capacity(image.width() as usize * image.height() as usize * samples);
+    for pixel in image.pixels() {
+        pixels.extend_from_slice(&pixel.data[.. samples]);
+    }
+    write_tiff(image.dimensions(), samples as u16, 8, false, has_alpha, &pixels)
+}
+
+// Lay out an uncompressed baseline TIFF in a single strip from its pixels, with the samples of each pixel together and
+// 16-bit samples little-endian, greyscale or RGB and with the alpha last if it has any
+pub fn write_tiff((width, height): (u32, u32), samples: u16, bits: u16, greyscale: bool, has_alpha: bool, pixels: &[u8]) -> Vec<u8> {
+    // The header, then the bits of each sample if there are more than the two that fit in their tag, then the pixels,
+    // then the directory
+    let mut data = b"II*\0".to_vec();
+    let bits_in_tag = samples <= 2;
+    let bits_offset = 8u32;
+    let pixels_offset = if bits_in_tag { bits_offset } else { bits_offset + samples as u32 * 2 };
+    let pixels_length = pixels.len() as u32;
+    let directory_offset = pixels_offset + pixels_length;
+    data.extend_from_slice(&directory_offset.to_le_bytes());
+    if !bits_in_tag {
+        for _ in 0 .. samples {
+            data.extend_from_slice(&bits.to_le_bytes());
+        }
+    }
+    data.extend_from_slice(pixels);
+    let bits_value = if bits_in_tag { (0 .. samples as u32).fold(0, |value, i| value | (bits as u32) << (16 * i)) } else { bits_offset };
+
+    // Each entry is a tag, a type of 3 for shorts or 4 for longs, a count and the value or where the values are,
+    // in order of tag
+    let mut entries: Vec<(u16, u16, u32, u32)> = vec![
+        (256, 4, 1, width),
+        (257, 4, 1, height),
+        (258, 3, samples as u32, bits_value),
+        // No compression
+        (259, 3, 1, 1),
+        // Black is zero, or RGB
+        (262, 3, 1, if greyscale { 1 } else { 2 }),
+        (273, 4, 1, pixels_offset),
+        (277, 3, 1, samples as u32),
+        (278, 4, 1, height),
+        (279, 4, 1, pixels_length),
+        // Chunky, with the samples of each pixel together
+        (284, 3, 1, 1)
+    ];
+    if has_alpha {
+        // The extra sample is unassociated alpha
+        entries.push((338, 3, 1, 2));
+    }
+
+    data.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    for (tag, kind, count, value) in entries {
+        data.extend_from_slice(&tag.to_le_bytes());
+        data.extend_from_slice(&kind.to_le_bytes());
+        data.extend_from_slice(&count.to_le_bytes());
+        // Shorts sit in the value in order, the first in its first two bytes
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    // There's no next directory
+    data.extend_from_slice(&0u32.to_le_bytes());
+
+    data
+}
+
+// Estimate the quality a JPEG was saved at from its luminance quantization table, assuming it was scaled from the
+// standard table the way most encoders do
+pub fn jpeg_quality(data: &[u8]) -> Option<u8> {
+    let mut position = 2;
+    while position + 4 <= data.len() && data[position] == 0xff {
+        let marker = data[position + 1];
+        let length = u16::from_be_bytes([data[position + 2], data[position + 3]]) as usize;
+        let segment = data.get(position + 4 .. position + 2 + length)?;
+
+        // A segment can hold several tables, each with its precision and number in its first byte
+        if marker == 0xdb {
+            let mut table = segment;
+            while let Some((&info, rest)) = table.split_first() {
+                let wide = info >> 4 == 1;
+                let table_length = if wide { 128 } else { 64 };
+                let values = rest.get(.. table_length)?;
+                if info & 0x0f == 0 {
+                    let value = |index: usize| if wide { u16::from_be_bytes([values[index * 2], values[index * 2 + 1]]) } else { values[index] as u16 };
+                    let scale = (0 .. 64).map(|index| value(index) as f64 / STANDARD_LUMINANCE[index] as f64).sum::<f64>() / 64.0 * 100.0;
+                    let quality = if scale <= 100.0 { (200.0 - scale) / 2.0 } else { 5000.0 / scale };
+                    return Some(quality.round().max(1.0).min(100.0) as u8);
+                }
+                table = &rest[table_length ..];
+            }
+        }
+
+        // The tables all come before the compressed data
+        if marker == 0xda {
+            return None;
+        }
+        position += 2 + length;
+    }
+    None
+}
+
+// Get the encoding an input was saved in, if its pieces can be saved in it too
+pub fn source_encoding(data: &[u8]) -> Option<Encoding> {
+    match image::guess_format(data) {
+        Ok(image::ImageFormat::PNG) => Some(Encoding::Png),
+        Ok(image::ImageFormat::JPEG) => Some(Encoding::Jpeg),
+        Ok(image::ImageFormat::BMP) => Some(Encoding::Bmp),
+        Ok(image::ImageFormat::TIFF) => Some(Encoding::Tiff),
+        Ok(image::ImageFormat::WEBP) if webp_supported() => Some(Encoding::Webp),
+        _ => None
+    }
+}
+
+// Whether pieces can be saved as WebPs, which needs the `webp` feature and libwebp's cwebp command
+pub fn webp_supported() -> bool {
+    cfg!(feature = "webp")
+}
+
+// Encode a PNG as a WebP of a quality from 1 to 100 with cwebp, which is given it in a file of its own, removed after
+#[cfg(feature = "webp")]
+pub fn encode_webp(png: &[u8], quality: u8) -> Result<Vec<u8>, String> {
+    static ENCODED: AtomicUsize = AtomicUsize::new(0);
+    let path = ::std::env::temp_dir().join(format!("autoguillotine-webp-{}-{}.png", process::id(), ENCODED.fetch_add(1, Ordering::SeqCst)));
+    fs::write(&path, png).map_err(|error| error.to_string())?;
+    let output = Command::new("cwebp").arg("-quiet").arg("-q").arg(quality.to_string()).arg(&path).arg("-o").arg("-")
+        .stdin(Stdio::null())
+        .output();
+    let _ = fs::remove_file(&path);
+    let output = output.map_err(|error| format!("failed to run cwebp: {}", error))?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(format!("cwebp exited with {}: {}", output.status, String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+#[cfg(not(feature = "webp"))]
+pub fn encode_webp(_png: &[u8], _quality: u8) -> Result<Vec<u8>, String> {
+    Err("saving WebPs needs the webp feature".to_string())
+}
@@ -0,0 +1,54 @@
+// Read every frame of an animated GIF, which decoding it as an image flattens to the first
+
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use image::{self, ImageBuffer, ImageDecoder, ImageFormat, Rgba, RgbaImage};
+
+// Read the frames of an animated GIF as they'd be shown, each drawn over the ones before it, or nothing if it isn't
+// an animated GIF
+pub fn read_frames(path: &Path) -> Option<Vec<RgbaImage>> {
+    if !path.extension().map(|extension| extension.eq_ignore_ascii_case("gif")).unwrap_or(false) {
+        return None;
+    }
+    let data = fs::read(path).ok()?;
+    if image::guess_format(&data).ok()? != ImageFormat::GIF {
+        return None;
+    }
+    let mut decoder = image::gif::Decoder::new(Cursor::new(&data[..]));
+    let (width, height) = decoder.dimensions().ok()?;
+    let frames: Vec<image::Frame> = decoder.into_frames().ok()?.into_iter().collect();
+    if frames.len() < 2 {
+        return None;
+    }
+
+    // A frame can cover only part of the canvas, and leaves what's under its transparent pixels showing
+    let mut canvas = RgbaImage::new(width, height);
+    Some(frames.into_iter()
+        .map(|frame| {
+            let (left, top) = (frame.left(), frame.top());
+            for (x, y, pixel) in frame.buffer().enumerate_pixels() {
+                if pixel[3] > 0 && left + x < width && top + y < height {
+                    canvas.put_pixel(left + x, top + y, *pixel);
+                }
+            }
+            canvas.clone()
+        })
+        .collect())
+}
+
+// Average frames, to find the cuts that suit all of them
+pub fn average(frames: &[RgbaImage]) -> RgbaImage {
+    let (width, height) = frames[0].dimensions();
+    let count = frames.len() as u32;
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let mut sums = [0u32; 4];
+        for frame in frames {
+            for (sum, &channel) in sums.iter_mut().zip(&frame.get_pixel(x, y).data) {
+                *sum += channel as u32;
+            }
+        }
+        Rgba { data: [(sums[0] / count) as u8, (sums[1] / count) as u8, (sums[2] / count) as u8, (sums[3] / count) as u8] }
+    })
+}
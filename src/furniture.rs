@@ -0,0 +1,98 @@
+// Find the headers and footers repeated across a batch of pages, so they can be cropped off before cutting
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use image::{GrayImage, Pixel};
+
+use check::image_dimensions;
+
+// The most the average luma of a row can differ from the same row of another page for them to match
+const MATCH_TOLERANCE: f64 = 2.0;
+// The range of luma a row needs to have something printed on it, rather than being blank margin
+const DETAIL_RANGE: u8 = 32;
+// The share of the pages a row has to match on to be furniture
+const MIN_SHARE: f64 = 0.75;
+// The least pages of a size there need to be to tell furniture from chance
+const MIN_PAGES: usize = 3;
+
+// The rows to crop off the top and bottom of a page
+#[derive(Clone, Copy, Default)]
+pub struct Furniture {
+    pub top: u32,
+    pub bottom: u32
+}
+
+fn row_difference(a: &GrayImage, b: &GrayImage, y: u32) -> f64 {
+    let total: u32 = (0 .. a.width())
+        .map(|x| (a.get_pixel(x, y).channels()[0] as i32 - b.get_pixel(x, y).channels()[0] as i32).unsigned_abs())
+        .sum();
+    total as f64 / a.width() as f64
+}
+
+fn row_has_detail(image: &GrayImage, y: u32) -> bool {
+    let lumas = (0 .. image.width()).map(|x| image.get_pixel(x, y).channels()[0]);
+    let (min, max) = lumas.fold((255, 0), |(min, max), luma| (luma.min(min), luma.max(max)));
+    max.saturating_sub(min) >= DETAIL_RANGE
+}
+
+// Find how far a band of matching rows runs from one edge, up to a quarter of the page, and where the last row with
+// something printed on it is, which is where the furniture ends
+fn band(rows: &mut dyn Iterator<Item = u32>, matching: &[bool], reference: &GrayImage) -> u32 {
+    let mut end = 0;
+    for (count, y) in rows.enumerate() {
+        if count as u32 >= reference.height() / 4 || !matching[y as usize] {
+            break;
+        }
+        if row_has_detail(reference, y) {
+            end = count as u32 + 1;
+        }
+    }
+    end
+}
+
+// Load a page's luma, if it can be decoded
+fn load(name: &str) -> Option<GrayImage> {
+    image::open(name).ok().map(|image| image.to_luma())
+}
+
+// Find the furniture on each page. Pages are grouped by size, and in each group the rows at the top and bottom that
+// are printed the same on most pages as on the page in the middle of the group are taken as furniture.
+// Pages are loaded one at a time, so only two are held at once.
+pub fn find_furniture(names: &[String]) -> BTreeMap<String, Furniture> {
+    let mut sizes: BTreeMap<(u32, u32), Vec<&String>> = BTreeMap::new();
+    for name in names {
+        if let Some(size) = image_dimensions(Path::new(name)) {
+            sizes.entry(size).or_default().push(name);
+        }
+    }
+
+    let mut furniture = BTreeMap::new();
+    for (_, pages) in sizes.into_iter().filter(|(_, pages)| pages.len() >= MIN_PAGES) {
+        let reference = match load(pages[pages.len() / 2]) {
+            Some(reference) => reference,
+            None => continue
+        };
+        let height = reference.height();
+
+        // Count the pages each row of the reference matches on
+        let mut matches = vec![0; height as usize];
+        for name in &pages {
+            if let Some(page) = load(name) {
+                for (y, count) in matches.iter_mut().enumerate() {
+                    if row_difference(&reference, &page, y as u32) <= MATCH_TOLERANCE {
+                        *count += 1;
+                    }
+                }
+            }
+        }
+        let matching: Vec<bool> = matches.iter().map(|&count| count as f64 >= pages.len() as f64 * MIN_SHARE).collect();
+
+        let top = band(&mut (0 .. height), &matching, &reference);
+        let bottom = band(&mut (0 .. height).rev(), &matching, &reference);
+        for name in pages {
+            furniture.insert(name.clone(), Furniture { top, bottom });
+        }
+    }
+    furniture
+}
@@ -0,0 +1,29 @@
+// Run a user's command on every written piece
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use rayon::prelude::*;
+use rayon::{Configuration, ThreadPool};
+
+// Quote a string so the shell passes it through as a single argument
+fn shell_quote(string: &str) -> String {
+    format!("'{}'", string.replace('\'', "'\\''"))
+}
+
+// Run the command for each piece, with `{piece}` replaced by the piece's path, at most `jobs` at a time
+pub fn run_post_command(command: &str, pieces: &[PathBuf], jobs: usize) {
+    let pool = ThreadPool::new(Configuration::new().num_threads(jobs)).unwrap();
+
+    pool.install(|| {
+        pieces.par_iter().for_each(|piece| {
+            let command = command.replace("{piece}", &shell_quote(&piece.to_string_lossy()));
+
+            match Command::new("sh").arg("-c").arg(&command).status() {
+                Ok(status) if status.success() => {},
+                Ok(status) => eprintln!("Warning: `{}` exited with {}", command, status),
+                Err(error) => eprintln!("Warning: failed to run `{}`: {}", command, error)
+            }
+        });
+    });
+}
@@ -0,0 +1,381 @@
+// Read the colour profiles embedded in images, and convert images in a wide gamut like Display P3 to sRGB, so the
+// same difference means the same change in colour whatever space an image was saved in.
+// Only matrix and curve profiles, which most RGB profiles are, are understood.
+
+use std::convert::TryInto;
+use std::io::{Read, Write};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use image::DynamicImage;
+
+use slices::{chunks, with_chunk};
+
+// The sRGB primaries adapted to the D50 white that profiles work in, as columns of X, Y and Z
+const SRGB_PRIMARIES: [[f64; 3]; 3] = [
+    [0.4360747, 0.3850649, 0.1430804],
+    [0.2225045, 0.7168786, 0.0606169],
+    [0.0139322, 0.0971045, 0.7141733]
+];
+
+// How close a profile's primaries have to be to sRGB's for it to be left alone
+const SRGB_TOLERANCE: f64 = 0.002;
+
+// The number of steps linear light is looked up in when encoding it as sRGB
+const ENCODE_STEPS: usize = 4096;
+
+// The most of a profile that fits in a JPEG segment, after the segment's length, signature and numbering
+const JPEG_PROFILE_PART: usize = 65535 - 2 - 14;
+
+// Get the colour profile embedded in a JPEG's APP2 segments or a PNG's iCCP chunk
+pub fn embedded_profile(data: &[u8]) -> Option<Vec<u8>> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        // A name, then the compression method, which can only be zlib
+        let (_, chunk) = chunks(data).into_iter().find(|&(kind, _)| kind == b"iCCP")?;
+        let separator = chunk.iter().position(|&byte| byte == 0)?;
+        let mut profile = Vec::new();
+        ZlibDecoder::new(chunk.get(separator + 2 ..)?).read_to_end(&mut profile).ok()?;
+        Some(profile)
+    } else if data.starts_with(&[0xff, 0xd8]) {
+        // A profile can be split over several segments, each numbered
+        let mut parts: Vec<(u8, &[u8])> = Vec::new();
+        let mut position = 2;
+        while position + 4 <= data.len() && data[position] == 0xff {
+            let marker = data[position + 1];
+            let length = u16::from_be_bytes([data[position + 2], data[position + 3]]) as usize;
+            // The image data starts after this
+            if marker == 0xda {
+                break;
+            }
+            let segment = data.get(position + 4 .. position + 2 + length)?;
+            if marker == 0xe2 && segment.starts_with(b"ICC_PROFILE\0") && segment.len() > 14 {
+                parts.push((segment[12], &segment[14 ..]));
+            }
+            position += 2 + length;
+        }
+        if parts.is_empty() {
+            return None;
+        }
+        parts.sort_by_key(|&(number, _)| number);
+        Some(parts.into_iter().flat_map(|(_, part)| part.iter().cloned()).collect())
+    } else {
+        None
+    }
+}
+
+// Get a copy of an encoded PNG or JPEG with a colour profile embedded in it, or nothing if it isn't one of those
+pub fn with_profile(data: &[u8], profile: &[u8]) -> Option<Vec<u8>> {
+    if data.starts_with(&[0xff, 0xd8]) {
+        // A profile too big for a JPEG's numbering can't be embedded
+        let parts: Vec<&[u8]> = profile.chunks(JPEG_PROFILE_PART).collect();
+        if parts.is_empty() || parts.len() > 255 {
+            return None;
+        }
+        // Keep a JFIF segment first, where readers look for it
+        let mut position = 2;
+        if data.get(2 .. 4) == Some(&[0xff, 0xe0]) {
+            position += 2 + u16::from_be_bytes([*data.get(4)?, *data.get(5)?]) as usize;
+        }
+        let mut jpeg = data.get(.. position)?.to_vec();
+        for (index, part) in parts.iter().enumerate() {
+            jpeg.extend_from_slice(&[0xff, 0xe2]);
+            jpeg.extend_from_slice(&((2 + 14 + part.len()) as u16).to_be_bytes());
+            jpeg.extend_from_slice(b"ICC_PROFILE\0");
+            jpeg.push(index as u8 + 1);
+            jpeg.push(parts.len() as u8);
+            jpeg.extend_from_slice(part);
+        }
+        jpeg.extend_from_slice(&data[position ..]);
+        Some(jpeg)
+    } else {
+        // A name, then the compression method, which can only be zlib
+        let mut encoder = ZlibEncoder::new(b"ICC profile\0\0".to_vec(), Compression::default());
+        encoder.write_all(profile).ok()?;
+        with_chunk(data, b"iCCP", &encoder.finish().ok()?)
+    }
+}
+
+// Get the name a profile describes itself with, from an old ASCII description or the first of a newer one's
+// translations
+pub fn profile_name(profile: &[u8]) -> Option<String> {
+    let data = tag(profile, b"desc")?;
+    let int = |offset: usize| data.get(offset .. offset + 4).map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize);
+    let name = match data.get(.. 4)? {
+        b"desc" => {
+            let text = data.get(12 .. 12 + int(8)?)?;
+            text.iter().take_while(|&&byte| byte != 0).map(|&byte| byte as char).collect()
+        },
+        b"mluc" => {
+            let (length, offset) = (int(20)?, int(24)?);
+            let text: Vec<u16> = data.get(offset .. offset + length)?.chunks(2)
+                .filter(|pair| pair.len() == 2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16_lossy(&text)
+        },
+        _ => return None
+    };
+    let name = name.trim().to_string();
+    if name.is_empty() { None } else { Some(name) }
+}
+
+// A tone curve, taking a channel from 0 to 1 to its linear light
+enum Curve {
+    Gamma(f64),
+    Table(Vec<f64>),
+    // The parameters of a parametric curve, filled out to g, a, b, c, d, e and f
+    Parametric(u16, [f64; 7])
+}
+
+impl Curve {
+    fn apply(&self, value: f64) -> f64 {
+        match *self {
+            Curve::Gamma(gamma) => value.powf(gamma),
+            Curve::Table(ref table) => {
+                let position = value * (table.len() - 1) as f64;
+                let index = (position as usize).min(table.len() - 2);
+                let fraction = position - index as f64;
+                table[index] * (1.0 - fraction) + table[index + 1] * fraction
+            },
+            Curve::Parametric(kind, [g, a, b, c, d, e, f]) => match kind {
+                0 => value.powf(g),
+                1 => if value >= -b / a { (a * value + b).powf(g) } else { 0.0 },
+                2 => if value >= -b / a { (a * value + b).powf(g) + c } else { c },
+                3 => if value >= d { (a * value + b).powf(g) } else { c * value },
+                _ => if value >= d { (a * value + b).powf(g) + e } else { c * value + f }
+            }
+        }
+    }
+}
+
+// Read a big-endian signed 15.16 fixed point number
+fn fixed(data: &[u8], offset: usize) -> Option<f64> {
+    let bytes = data.get(offset .. offset + 4)?;
+    Some(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64 / 65536.0)
+}
+
+// Find a tag's data in a profile
+fn tag<'a>(profile: &'a [u8], signature: &[u8; 4]) -> Option<&'a [u8]> {
+    let count = u32::from_be_bytes(profile.get(128 .. 132)?.try_into().ok()?) as usize;
+    (0 .. count)
+        .filter_map(|index| profile.get(132 + index * 12 .. 144 + index * 12))
+        .find(|entry| &entry[.. 4] == signature)
+        .and_then(|entry| {
+            let offset = u32::from_be_bytes([entry[4], entry[5], entry[6], entry[7]]) as usize;
+            let size = u32::from_be_bytes([entry[8], entry[9], entry[10], entry[11]]) as usize;
+            profile.get(offset .. offset + size)
+        })
+}
+
+// Read a curve tag
+fn curve(data: &[u8]) -> Option<Curve> {
+    match data.get(.. 4)? {
+        b"curv" => {
+            let count = u32::from_be_bytes(data.get(8 .. 12)?.try_into().ok()?) as usize;
+            let entry = |index: usize| data.get(12 + index * 2 .. 14 + index * 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+            match count {
+                0 => Some(Curve::Gamma(1.0)),
+                1 => Some(Curve::Gamma(entry(0)? as f64 / 256.0)),
+                _ => (0 .. count).map(|index| entry(index).map(|value| value as f64 / 65535.0)).collect::<Option<_>>().map(Curve::Table)
+            }
+        },
+        b"para" => {
+            let kind = u16::from_be_bytes(data.get(8 .. 10)?.try_into().ok()?);
+            let count = [1, 3, 4, 5, 7].get(kind as usize)?;
+            let mut parameters = [0.0; 7];
+            for (index, parameter) in parameters.iter_mut().take(*count).enumerate() {
+                *parameter = fixed(data, 12 + index * 4)?;
+            }
+            Some(Curve::Parametric(kind, parameters))
+        },
+        _ => None
+    }
+}
+
+// Invert a 3 by 3 matrix, if it can be
+fn invert(m: &[[f64; 3]; 3]) -> Option<[[f64; 3]; 3]> {
+    let cofactor = |row: usize, column: usize| {
+        let (r1, r2) = ((row + 1) % 3, (row + 2) % 3);
+        let (c1, c2) = ((column + 1) % 3, (column + 2) % 3);
+        m[r1][c1] * m[r2][c2] - m[r1][c2] * m[r2][c1]
+    };
+    let determinant: f64 = (0 .. 3).map(|column| m[0][column] * cofactor(0, column)).sum();
+    if determinant.abs() < 1e-9 {
+        return None;
+    }
+    let mut inverse = [[0.0; 3]; 3];
+    for (row, inverse_row) in inverse.iter_mut().enumerate() {
+        for (column, value) in inverse_row.iter_mut().enumerate() {
+            *value = cofactor(column, row) / determinant;
+        }
+    }
+    Some(inverse)
+}
+
+// The conversion from a profile's space to sRGB
+pub struct Transform {
+    // The linear light of each channel's values
+    decode: [[f64; 256]; 3],
+    // From the profile's linear light to sRGB's
+    matrix: [[f64; 3]; 3],
+    // The sRGB value of each step of linear light
+    encode: Vec<u8>
+}
+
+impl Transform {
+    // Make the conversion to sRGB from an RGB matrix and curve profile, or nothing if it can't be read or is already
+    // close enough to sRGB
+    pub fn to_srgb(profile: &[u8]) -> Option<Transform> {
+        if profile.get(16 .. 20)? != b"RGB " {
+            return None;
+        }
+
+        let mut primaries = [[0.0; 3]; 3];
+        for (column, signature) in [b"rXYZ", b"gXYZ", b"bXYZ"].iter().enumerate() {
+            let data = tag(profile, signature)?;
+            for (row, primary) in primaries.iter_mut().enumerate() {
+                primary[column] = fixed(data, 8 + row * 4)?;
+            }
+        }
+        let curves = [curve(tag(profile, b"rTRC")?)?, curve(tag(profile, b"gTRC")?)?, curve(tag(profile, b"bTRC")?)?];
+
+        // Leave a profile with sRGB's primaries and near enough its curve as it is
+        let srgb_curve = |value: f64| if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) };
+        let same_primaries = (0 .. 3).all(|row| (0 .. 3).all(|column| (primaries[row][column] - SRGB_PRIMARIES[row][column]).abs() < SRGB_TOLERANCE));
+        let same_curves = curves.iter().all(|curve| (0 ..= 10).all(|step| (curve.apply(step as f64 / 10.0) - srgb_curve(step as f64 / 10.0)).abs() < 0.01));
+        if same_primaries && same_curves {
+            return None;
+        }
+
+        let to_srgb = invert(&SRGB_PRIMARIES)?;
+        let mut matrix = [[0.0; 3]; 3];
+        for (row, matrix_row) in matrix.iter_mut().enumerate() {
+            for (column, value) in matrix_row.iter_mut().enumerate() {
+                *value = (0 .. 3).map(|index| to_srgb[row][index] * primaries[index][column]).sum();
+            }
+        }
+
+        let mut decode = [[0.0; 256]; 3];
+        for (table, curve) in decode.iter_mut().zip(&curves) {
+            for (value, linear) in table.iter_mut().enumerate() {
+                *linear = curve.apply(value as f64 / 255.0).max(0.0).min(1.0);
+            }
+        }
+        let encode = (0 .. ENCODE_STEPS)
+            .map(|step| {
+                let linear = step as f64 / (ENCODE_STEPS - 1) as f64;
+                let value = if linear <= 0.0031308 { linear * 12.92 } else { 1.055 * linear.powf(1.0 / 2.4) - 0.055 };
+                (value * 255.0).round() as u8
+            })
+            .collect();
+        Some(Transform { decode, matrix, encode })
+    }
+
+    // Convert the red, green and blue of a pixel
+    fn convert(&self, pixel: &mut [u8]) {
+        let linear = [self.decode[0][pixel[0] as usize], self.decode[1][pixel[1] as usize], self.decode[2][pixel[2] as usize]];
+        for (channel, row) in self.matrix.iter().enumerate() {
+            let value = (row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2]).max(0.0).min(1.0);
+            pixel[channel] = self.encode[(value * (ENCODE_STEPS - 1) as f64).round() as usize];
+        }
+    }
+
+    // Convert an RGB image to sRGB, leaving its alpha and any greyscale image as they are
+    pub fn apply(&self, image: DynamicImage) -> DynamicImage {
+        match image {
+            DynamicImage::ImageRgb8(mut image) => {
+                for pixel in image.pixels_mut() {
+                    self.convert(&mut pixel.data);
+                }
+                DynamicImage::ImageRgb8(image)
+            },
+            DynamicImage::ImageRgba8(mut image) => {
+                for pixel in image.pixels_mut() {
+                    self.convert(&mut pixel.data);
+                }
+                DynamicImage::ImageRgba8(image)
+            },
+            image => image
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{self, DynamicImage, GenericImage, ImageBuffer, Rgb, Rgba};
+
+    use super::{curve, embedded_profile, with_profile, Transform};
+
+    // A big-endian 15.16 fixed point number
+    fn fixed(value: f64) -> [u8; 4] {
+        ((value * 65536.0).round() as i32).to_be_bytes()
+    }
+
+    // An RGB profile with Display P3's primaries, adapted to D50, and a gamma of 2.2 on every channel
+    fn display_p3() -> Vec<u8> {
+        let xyz = |x: f64, y: f64, z: f64| [&b"XYZ \0\0\0\0"[..], &fixed(x), &fixed(y), &fixed(z)].concat();
+        let gamma = [&b"curv\0\0\0\0"[..], &1u32.to_be_bytes(), &((2.2 * 256.0) as u16).to_be_bytes(), &[0, 0]].concat();
+        let tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+            (b"rXYZ", xyz(0.5151, 0.2412, -0.0011)),
+            (b"gXYZ", xyz(0.2920, 0.6922, 0.0419)),
+            (b"bXYZ", xyz(0.1571, 0.0666, 0.7841)),
+            (b"rTRC", gamma.clone()),
+            (b"gTRC", gamma.clone()),
+            (b"bTRC", gamma)
+        ];
+
+        let mut header = vec![0; 128];
+        header[16 .. 20].copy_from_slice(b"RGB ");
+        let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+        let mut data = Vec::new();
+        let start = 128 + 4 + 12 * tags.len();
+        for (signature, tag) in &tags {
+            table.extend_from_slice(*signature);
+            table.extend_from_slice(&((start + data.len()) as u32).to_be_bytes());
+            table.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+            data.extend_from_slice(tag);
+        }
+        [header, table, data].concat()
+    }
+
+    fn encoded(format: image::ImageFormat) -> Vec<u8> {
+        let mut data = Vec::new();
+        DynamicImage::ImageRgb8(ImageBuffer::from_pixel(8, 8, Rgb { data: [200, 100, 50] })).save(&mut data, format).unwrap();
+        data
+    }
+
+    #[test]
+    fn profile_embedded_in_a_png_or_jpeg_reads_back() {
+        let profile = display_p3();
+        for format in vec![image::ImageFormat::PNG, image::ImageFormat::JPEG] {
+            let tagged = with_profile(&encoded(format), &profile).unwrap();
+            assert_eq!(embedded_profile(&tagged), Some(profile.clone()));
+            image::load_from_memory(&tagged).unwrap();
+        }
+    }
+
+    #[test]
+    fn wide_gamut_white_and_greys_stay_neutral() {
+        let transform = Transform::to_srgb(&display_p3()).unwrap();
+        let image = ImageBuffer::from_fn(3, 1, |x, _| Rgba { data: [[255, 128, 0][x as usize]; 4] });
+        let converted = transform.apply(DynamicImage::ImageRgba8(image));
+        for (x, _, pixel) in converted.pixels() {
+            let [red, green, blue, alpha] = pixel.data;
+            assert!(red.max(green).max(blue) - red.min(green).min(blue) <= 2, "{:?} isn't grey", pixel.data);
+            assert_eq!(alpha, [255, 128, 0][x as usize]);
+        }
+        assert!(converted.get_pixel(0, 0).data[0] >= 253);
+    }
+
+    #[test]
+    fn truncated_profiles_and_curves_are_not_read() {
+        let profile = display_p3();
+        for length in &[0, 20, 129, 131, 140, profile.len() - 1] {
+            assert!(Transform::to_srgb(&profile[.. *length]).is_none());
+        }
+        assert!(curve(b"curv\0\0\0\0\0\0").is_none());
+        assert!(curve(b"curv\0\0\0\0\0\0\0\x02\0\0").is_none());
+        assert!(curve(b"para\0\0\0\0\0").is_none());
+    }
+}
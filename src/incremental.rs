@@ -0,0 +1,73 @@
+// Remember what each input was cut into and with which settings, so a rerun over the same inputs only cuts the ones
+// that changed, were cut with other settings or whose pieces have gone or are older than they are
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde_json;
+
+use atomic;
+use sha256::sha256;
+
+// The file the state is kept in, in the output directory or the working directory
+pub const STATE_FILE: &str = ".autoguillotine-state.json";
+
+// What an input was when it was last cut, the fingerprint of the settings it was cut with and the files it was cut
+// into
+#[derive(Serialize, Deserialize)]
+pub struct InputState {
+    pub hash: String,
+    pub fingerprint: String,
+    pub outputs: Vec<PathBuf>
+}
+
+// Every input cut so far, by its full path
+#[derive(Default, Serialize, Deserialize)]
+pub struct State {
+    pub inputs: BTreeMap<String, InputState>
+}
+
+// Get the name an input is remembered by, its full path if it can be found
+fn key(input: &str) -> String {
+    Path::new(input).canonicalize().map(|path| path.to_string_lossy().into_owned()).unwrap_or_else(|_| input.to_string())
+}
+
+fn hash_file(path: &Path) -> Option<String> {
+    fs::read(path).ok().map(|data| sha256(&data))
+}
+
+impl State {
+    // Read the state the last runs left, or start afresh if there's none or it can't be read
+    pub fn read(path: &Path) -> State {
+        fs::read(path).ok().and_then(|data| serde_json::from_slice(&data).ok()).unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        atomic::write(path, serde_json::to_string_pretty(self).unwrap().as_bytes())
+    }
+
+    // Whether an input can be skipped: it was last cut with the same settings, every file it was cut into is still
+    // there and no older than it, and it hasn't changed since
+    pub fn up_to_date(&self, input: &str, fingerprint: &str) -> bool {
+        let state = match self.inputs.get(&key(input)) {
+            Some(state) if state.fingerprint == fingerprint => state,
+            _ => return false
+        };
+        let modified = |path: &Path| path.metadata().and_then(|metadata| metadata.modified()).ok();
+        let changed = match modified(Path::new(input)) {
+            Some(changed) => changed,
+            None => return false
+        };
+        state.outputs.iter().all(|output| modified(output).map_or(false, |made| made >= changed))
+            && hash_file(Path::new(input)).map_or(false, |hash| hash == state.hash)
+    }
+
+    // Remember what an input was cut into, and what it was when it was
+    pub fn record(&mut self, input: &str, fingerprint: &str, outputs: Vec<PathBuf>) {
+        if let Some(hash) = hash_file(Path::new(input)) {
+            self.inputs.insert(key(input), InputState { hash, fingerprint: fingerprint.to_string(), outputs });
+        }
+    }
+}
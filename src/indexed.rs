@@ -0,0 +1,46 @@
+// Encode pieces with few enough colours as paletted PNGs, keeping every colour exact
+
+use std::collections::HashMap;
+
+use image::RgbaImage;
+use png::{self, HasParameters};
+
+// Encode an image as a paletted PNG, or give up if it has more colours than a palette can hold
+pub fn encode_indexed(image: &RgbaImage) -> Option<Vec<u8>> {
+    let mut palette: HashMap<[u8; 4], u8> = HashMap::new();
+    let mut colors = Vec::new();
+    let mut indices = Vec::with_capacity(image.width() as usize * image.height() as usize);
+
+    for pixel in image.pixels() {
+        let index = match palette.get(&pixel.data) {
+            Some(&index) => index,
+            None => {
+                if colors.len() == 256 {
+                    return None;
+                }
+                let index = colors.len() as u8;
+                palette.insert(pixel.data, index);
+                colors.push(pixel.data);
+                index
+            }
+        };
+        indices.push(index);
+    }
+
+    let plte: Vec<u8> = colors.iter().flat_map(|color| color[.. 3].to_vec()).collect();
+    let alphas: Vec<u8> = colors.iter().map(|color| color[3]).collect();
+
+    let mut data = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut data, image.width(), image.height());
+        encoder.set(png::ColorType::Indexed).set(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().ok()?;
+        writer.write_chunk(*b"PLTE", &plte).ok()?;
+        // Only store the alpha of each colour if any of them aren't opaque
+        if alphas.iter().any(|&alpha| alpha != 255) {
+            writer.write_chunk(*b"tRNS", &alphas).ok()?;
+        }
+        writer.write_image_data(&indices).ok()?;
+    }
+    Some(data)
+}
@@ -0,0 +1,59 @@
+// Read layered documents flattened for finding the cuts, and cut each of their layers the same way, so text on its own
+// layer can be swapped out of each piece
+
+use std::path::Path;
+
+use image::{GenericImage, RgbaImage};
+
+use naming::slugify;
+use Piece;
+
+// A layered document flattened into one image, and the name and pixels of each of its layers, on a canvas the size of
+// the flattened image
+pub struct Layered {
+    pub flattened: RgbaImage,
+    pub layers: Vec<(String, RgbaImage)>
+}
+
+// Whether layers can be read, which needs the `layers` feature
+pub fn layers_supported() -> bool {
+    cfg!(feature = "layers")
+}
+
+// Whether a path looks like a Photoshop document
+pub fn is_layered(path: &Path) -> bool {
+    path.extension().map(|extension| extension.eq_ignore_ascii_case("psd")).unwrap_or(false)
+}
+
+// Read a Photoshop document's flattened image and layers
+#[cfg(feature = "layers")]
+pub fn read_layered(path: &Path) -> Result<Layered, String> {
+    let data = std::fs::read(path).map_err(|error| error.to_string())?;
+    let document = psd::Psd::from_bytes(&data).map_err(|error| error.to_string())?;
+    let (width, height) = (document.width(), document.height());
+    let canvas = |rgba: Vec<u8>| RgbaImage::from_raw(width, height, rgba).ok_or_else(|| "a layer isn't the size of the document".to_string());
+
+    let flattened = canvas(document.rgba())?;
+    let layers = document.layers().iter()
+        .map(|layer| Ok((layer.name().to_string(), canvas(layer.rgba())?)))
+        .collect::<Result<_, String>>()?;
+    Ok(Layered { flattened, layers })
+}
+
+#[cfg(not(feature = "layers"))]
+pub fn read_layered(_path: &Path) -> Result<Layered, String> {
+    Err("reading layers needs the layers feature".to_string())
+}
+
+// Cut the rectangle a piece came from out of each layer, named by the layer's place in the document and its name.
+// Post-processing only changes the piece itself, so the layers are cut as the piece was before it.
+pub fn cut_layers(layers: &mut [(String, RgbaImage)], piece: &Piece) -> Vec<(String, RgbaImage)> {
+    layers.iter_mut()
+        .enumerate()
+        .map(|(index, &mut (ref name, ref mut layer))| {
+            let slug = slugify(name);
+            let file = if slug.is_empty() { index.to_string() } else { format!("{}-{}", index, slug) };
+            (file, layer.sub_image(piece.x, piece.y, piece.width, piece.height).to_image())
+        })
+        .collect()
+}
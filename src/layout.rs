@@ -0,0 +1,79 @@
+// Recover the rows and columns of a page from where its pieces are, to lay the pieces out in nested directories
+
+// Split pieces into the groups that don't overlap along an axis, in order, where each piece is given as its start and
+// end along that axis
+fn split_groups(indices: &[usize], spans: &[(u32, u32)]) -> Vec<Vec<usize>> {
+    let mut sorted = indices.to_vec();
+    sorted.sort_by_key(|&index| spans[index]);
+
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut end = 0;
+    for index in sorted {
+        let (start, span_end) = spans[index];
+        match groups.last_mut() {
+            Some(group) if start < end => group.push(index),
+            _ => groups.push(vec![index])
+        }
+        end = end.max(span_end);
+    }
+    // Keep the pieces in their original order within each group
+    for group in &mut groups {
+        group.sort();
+    }
+    groups
+}
+
+fn nest(indices: &[usize], rows: &[(u32, u32)], columns: &[(u32, u32)], horizontal: bool, dir: String, paths: &mut [String]) {
+    if indices.len() == 1 {
+        paths[indices[0]] = dir;
+        return;
+    }
+
+    // Split across the expected axis, or the other one if the pieces all overlap along it
+    let (mut groups, mut horizontal) = (split_groups(indices, if horizontal { rows } else { columns }), horizontal);
+    if groups.len() == 1 {
+        horizontal = !horizontal;
+        groups = split_groups(indices, if horizontal { rows } else { columns });
+    }
+    // Pieces that can't be separated either way stay together
+    if groups.len() == 1 {
+        for &index in indices {
+            paths[index] = dir.clone();
+        }
+        return;
+    }
+
+    let prefix = if horizontal { "row" } else { "col" };
+    for (i, group) in groups.iter().enumerate() {
+        nest(group, rows, columns, !horizontal, format!("{}{}-{}/", dir, prefix, i), paths);
+    }
+}
+
+// Find the directory each piece goes in, given as its x, y, width and height: the page is split into rows, each row
+// into columns, each column into rows and so on, with a directory for each.
+// The directories are relative, with a slash after each, so a piece's path is its directory followed by its name.
+pub fn nested_dirs(rectangles: &[(u32, u32, u32, u32)]) -> Vec<String> {
+    let rows: Vec<(u32, u32)> = rectangles.iter().map(|&(_, y, _, height)| (y, y + height)).collect();
+    let columns: Vec<(u32, u32)> = rectangles.iter().map(|&(x, _, width, _)| (x, x + width)).collect();
+    let indices: Vec<usize> = (0 .. rectangles.len()).collect();
+
+    let mut paths = vec![String::new(); rectangles.len()];
+    if !indices.is_empty() {
+        nest(&indices, &rows, &columns, true, String::new(), &mut paths);
+    }
+    paths
+}
+
+// Get the pieces in reading order, given as their x, y, width and height, with the row and column of each: rows are
+// the groups of pieces that don't overlap vertically, top to bottom, and each row is read left to right
+pub fn reading_order(rectangles: &[(u32, u32, u32, u32)]) -> Vec<(usize, usize, usize)> {
+    let rows: Vec<(u32, u32)> = rectangles.iter().map(|&(_, y, _, height)| (y, y + height)).collect();
+    let indices: Vec<usize> = (0 .. rectangles.len()).collect();
+
+    let mut order = Vec::with_capacity(rectangles.len());
+    for (row, mut group) in split_groups(&indices, &rows).into_iter().enumerate() {
+        group.sort_by_key(|&index| (rectangles[index].0, rectangles[index].1));
+        order.extend(group.into_iter().enumerate().map(|(column, index)| (index, row, column)));
+    }
+    order
+}
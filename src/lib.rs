@@ -0,0 +1,2401 @@
+// Find the gaps between the pieces of an image and cut it apart at them, for use from other programs as well as from
+// the command line
+
+extern crate image;
+extern crate ord_subset;
+#[cfg(feature = "cli")]
+extern crate png;
+#[cfg(feature = "parallel")]
+extern crate rayon;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+
+mod color;
+pub mod deskew;
+pub mod detect;
+mod dividers;
+mod edges;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "cli")]
+pub mod lowmem;
+mod median;
+pub mod overrides;
+mod parallel;
+mod receipts;
+mod screenshots;
+mod shading;
+mod stickers;
+#[cfg(any(test, feature = "testutil"))]
+pub mod testutil;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+use image::GenericImage;
+use ord_subset::OrdSubsetIterExt;
+use parallel::*;
+
+use std::collections::BTreeMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+// A 1D line of pixels
+type Line = Vec<image::Rgba<u8>>;
+
+// Get the average difference between two lines of pixels, weighting each channel
+fn average_difference(old: &[image::Rgba<u8>], new: &[image::Rgba<u8>], weights: &[f64; 4]) -> f64 {
+    // Sum up the difference for each channel of each pixel as exact integers, so the sums are the same on every
+    // platform and the weights only come in once at the end
+    let mut sums = [0u64; 4];
+    for (old, new) in old.iter().zip(new) {
+        for channel in 0 .. 4 {
+            sums[channel] += (old[channel] as i32 - new[channel] as i32).unsigned_abs() as u64;
+        }
+    }
+
+    weighted_difference(&sums, old.len(), weights)
+}
+
+// Weight each channel's summed difference, then divide by number of pixels and by the total weight of the channels
+fn weighted_difference(sums: &[u64; 4], pixels: usize, weights: &[f64; 4]) -> f64 {
+    let value: f64 = (0 .. 4).map(|channel| weights[channel] * sums[channel] as f64).sum();
+    value / pixels as f64 / weights.iter().sum::<f64>()
+}
+
+// Get the average difference in luma between two lines of pixels, ignoring hue
+fn average_luma_difference(old: &[image::Rgba<u8>], new: &[image::Rgba<u8>]) -> f64 {
+    old.iter().zip(new).map(|(old, new)| (luma(old) as i32 - luma(new) as i32).unsigned_abs() as u64).sum::<u64>() as f64 / old.len() as f64
+}
+
+// Get the average perceptual difference between two lines of pixels
+fn average_delta_e(old: &[image::Rgba<u8>], new: &[image::Rgba<u8>]) -> f64 {
+    old.iter().zip(new).map(|(old, new)| color::delta_e(old, new)).sum::<f64>() / old.len() as f64
+}
+
+// Get the standard deviation of a line of pixels, averaged over the channels with a weight
+fn line_deviation(line: &Line, weights: &[f64; 4]) -> f64 {
+    let mut sums = ChannelSums::default();
+    for pixel in line {
+        sums.add(pixel);
+    }
+    sums.deviation(weights)
+}
+
+// The sums of each channel of a line's pixels and of their squares, kept as exact integers so the line's deviation
+// comes out the same whether its pixels are added along the line or a row at a time across the image
+#[derive(Clone, Copy, Default)]
+struct ChannelSums {
+    pixels: u64,
+    sums: [u64; 4],
+    squares: [u64; 4]
+}
+
+impl ChannelSums {
+    fn add(&mut self, pixel: &image::Rgba<u8>) {
+        self.pixels += 1;
+        for channel in 0 .. 4 {
+            self.sums[channel] += pixel[channel] as u64;
+            self.squares[channel] += (pixel[channel] as u64).pow(2);
+        }
+    }
+
+    // Get the standard deviation of the pixels, averaged over the channels with a weight
+    fn deviation(&self, weights: &[f64; 4]) -> f64 {
+        let mut variance = 0.0;
+        let mut channels = 0;
+
+        let pixels = self.pixels as u128;
+        for channel in (0 .. 4).filter(|&channel| weights[channel] > 0.0) {
+            // The variance times the square of the number of pixels, which is a whole number
+            let scaled = pixels * self.squares[channel] as u128 - (self.sums[channel] as u128).pow(2);
+            variance += scaled as f64 / (pixels * pixels) as f64;
+            channels += 1;
+        }
+
+        (variance / channels as f64).sqrt()
+    }
+}
+
+// Whether a pixel differs from the background by more than the tolerance in any channel with a weight
+fn is_content(pixel: &image::Rgba<u8>, background: image::Rgba<u8>, weights: &[f64; 4], tolerance: f64) -> bool {
+    (0 .. 4).any(|channel| weights[channel] > 0.0 && (pixel[channel] as f64 - background[channel] as f64).abs() > tolerance)
+}
+
+// Count the pixels in a line that differ from the background
+fn content_pixels(line: &Line, background: image::Rgba<u8>, weights: &[f64; 4], tolerance: f64) -> u32 {
+    line.iter().filter(|pixel| is_content(pixel, background, weights, tolerance)).count() as u32
+}
+
+// Estimate the background colour of an image as the per-channel median of its border pixels
+pub fn estimate_background(image: &image::RgbaImage) -> image::Rgba<u8> {
+    let (width, height) = image.dimensions();
+    let mut border: Line = Vec::with_capacity(2 * (width + height) as usize);
+
+    for x in 0 .. width {
+        border.push(*image.get_pixel(x, 0));
+        border.push(*image.get_pixel(x, height - 1));
+    }
+    for y in 0 .. height {
+        border.push(*image.get_pixel(0, y));
+        border.push(*image.get_pixel(width - 1, y));
+    }
+
+    let mut background = image::Rgba {data: [0; 4]};
+    for channel in 0 .. 4 {
+        let mut values: Vec<u8> = border.iter().map(|pixel| pixel[channel]).collect();
+        values.sort();
+        background[channel] = values[values.len() / 2];
+    }
+    background
+}
+
+// Estimate the colour of the gutters in an image as the most common colour, to within eight levels a channel, among
+// its border pixels and the pixels of its lines that are uniform enough to be gaps, averaging the pixels of that colour
+fn estimate_gutter_color(image: &image::RgbaImage, weights: &[f64; 4], max_deviation: f64) -> [u8; 3] {
+    let (width, height) = image.dimensions();
+    let mut colors: BTreeMap<[u8; 3], (u64, [u64; 3])> = BTreeMap::new();
+    let mut count = |line: &[image::Rgba<u8>]| {
+        for pixel in line {
+            let entry = colors.entry([pixel[0] >> 3, pixel[1] >> 3, pixel[2] >> 3]).or_insert((0, [0; 3]));
+            entry.0 += 1;
+            for channel in 0 .. 3 {
+                entry.1[channel] += pixel[channel] as u64;
+            }
+        }
+    };
+
+    let rows = (0 .. height).map(|y| (0 .. width).map(|x| *image.get_pixel(x, y)).collect::<Line>());
+    let columns = (0 .. width).map(|x| (0 .. height).map(|y| *image.get_pixel(x, y)).collect::<Line>());
+    for (index, line) in rows.enumerate() {
+        if index == 0 || index as u32 == height - 1 || line_deviation(&line, weights) <= max_deviation {
+            count(&line);
+        }
+    }
+    for (index, line) in columns.enumerate() {
+        if index == 0 || index as u32 == width - 1 || line_deviation(&line, weights) <= max_deviation {
+            count(&line);
+        }
+    }
+
+    let (_, &(pixels, sums)) = colors.iter().max_by_key(|&(_, &(pixels, _))| pixels).unwrap();
+    [(sums[0] / pixels) as u8, (sums[1] / pixels) as u8, (sums[2] / pixels) as u8]
+}
+
+// Get the luma of a pixel
+pub fn luma(pixel: &image::Rgba<u8>) -> u8 {
+    ((299 * pixel[0] as u32 + 587 * pixel[1] as u32 + 114 * pixel[2] as u32) / 1000) as u8
+}
+
+// Get the darkest and brightest luma in a line of pixels
+fn luma_range(line: &Line) -> (u8, u8) {
+    line.iter().fold((255, 0), |(min, max), pixel| {
+        let luma = luma(pixel);
+        (min.min(luma), max.max(luma))
+    })
+}
+
+// Get the Shannon entropy, in bits, of an image's luma histogram
+pub fn luma_entropy(image: &image::RgbaImage) -> f64 {
+    let mut histogram = [0u32; 256];
+    for pixel in image.pixels() {
+        histogram[luma(pixel) as usize] += 1;
+    }
+
+    let total = image.width() as f64 * image.height() as f64;
+    histogram.iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| count as f64 / total)
+        .map(|probability| -probability * probability.log2())
+        .sum()
+}
+
+// The share of a line's pixels that have to be the gutter colour for it to be a gutter
+const MIN_GUTTER_SHARE: f64 = 0.9;
+
+// The differences between each pair of neighbouring lines, and the deviation, content and luma range within each line
+#[derive(Clone)]
+struct Profile {
+    values: Vec<f64>,
+    deviations: Vec<f64>,
+    contents: Vec<u32>,
+    ranges: Vec<(u8, u8)>,
+    // The average edge energy along each line, if cuts are found by it
+    energies: Vec<f64>,
+    // The number of pixels in each line
+    line_length: u32
+}
+
+impl Profile {
+    fn with_capacity(lines: usize, line_length: u32) -> Profile {
+        Profile {
+            values: Vec::with_capacity(lines.saturating_sub(1)),
+            deviations: Vec::with_capacity(lines),
+            contents: Vec::with_capacity(lines),
+            ranges: Vec::with_capacity(lines),
+            energies: Vec::new(),
+            line_length
+        }
+    }
+
+    // Get the profile of the lines from `start` up to `end`, as it would be measured on just those lines
+    fn slice(&self, start: usize, end: usize) -> Profile {
+        Profile {
+            values: self.values[start .. end - 1].to_vec(),
+            deviations: self.deviations[start .. end].to_vec(),
+            contents: self.contents[start .. end].to_vec(),
+            ranges: self.ranges[start .. end].to_vec(),
+            energies: if self.energies.is_empty() { Vec::new() } else { self.energies[start .. end].to_vec() },
+            line_length: self.line_length
+        }
+    }
+
+    // Divide each difference by the luma range of the lines within the window around it
+    fn normalize(&mut self, window: usize) {
+        for (index, value) in self.values.iter_mut().enumerate() {
+            let start = (index + 1).saturating_sub(window);
+            let end = (index + 1 + window).min(self.ranges.len());
+            let (min, max) = self.ranges[start .. end].iter()
+                .fold((255, 0), |(min, max), &(low, high)| (min.min(low), max.max(high)));
+            *value /= (max.saturating_sub(min)).max(1) as f64;
+        }
+    }
+
+    // Average each difference over the window of pairs of lines around it, cut off at the ends, where even windows
+    // reach a pair further after the difference than before it
+    fn average(&mut self, window: usize) {
+        let values = self.values.clone();
+        for (index, value) in self.values.iter_mut().enumerate() {
+            let start = index.saturating_sub((window - 1) / 2);
+            let end = (index + window / 2 + 1).min(values.len());
+            *value = values[start .. end].iter().sum::<f64>() / (end - start) as f64;
+        }
+    }
+
+    // Turn the differences into the scores places to cut are chosen by, relative to the local contrast and averaged
+    // over the window if the settings ask for them, or replace them with how little edge each place crosses
+    fn score(&mut self, config: &GuillotineConfig) {
+        if config.strategy == Strategy::Edge {
+            self.values = edges::edge_scores(&self.energies);
+            return;
+        }
+        if config.relative_threshold.is_some() {
+            self.normalize(config.contrast_window);
+        }
+        if config.window > 1 {
+            self.average(config.window);
+        }
+    }
+
+    // Find every place to cut and its difference, skipping cuts that don't sit next to a wide enough gap, that would
+    // leave a piece without enough content or too small on either side or that fall in a forbidden range of the source
+    // image, given where the profiled region starts in it
+    fn candidates(&self, min_gap: u32, forbidden: &[(u32, u32)], offset: u32, config: &GuillotineConfig) -> Vec<(u32, f64)> {
+        // Sum up the content before each line so each side of a cut can be measured at once
+        let mut content_before = Vec::with_capacity(self.contents.len() + 1);
+        content_before.push(0u64);
+        for content in &self.contents {
+            let total = content_before[content_before.len() - 1] + *content as u64;
+            content_before.push(total);
+        }
+        let lines = self.contents.len();
+        let total_content = content_before[lines];
+
+        let has_content = |cut: usize| {
+            let before = content_before[cut] as f64 / (cut as f64 * self.line_length as f64);
+            let after = (total_content - content_before[cut]) as f64 / ((lines - cut) as f64 * self.line_length as f64);
+            before >= config.min_content && after >= config.min_content
+        };
+
+        // With a gutter colour, a cut has to be next to a line that is mostly that colour
+        let is_gutter = |line: usize| self.contents[line] as f64 <= self.line_length as f64 * (1.0 - MIN_GUTTER_SHARE);
+
+        self.values.iter()
+            .enumerate()
+            .filter(|&(index, _)| config.gutter_color.is_none() || is_gutter(index) || is_gutter(index + 1))
+            .filter(|&(index, _)| min_gap == 0 || self.gap_width(index + 1, config.gap_deviation) >= min_gap)
+            .filter(|&(index, _)| config.min_content <= 0.0 || has_content(index + 1))
+            .filter(|&(index, _)| !config.leaves_small(self.line_length, index as u32 + 1, lines as u32))
+            .filter(|&(index, _)| {
+                let cut = offset + index as u32 + 1;
+                !forbidden.iter().any(|&(start, end)| start <= cut && cut <= end)
+            })
+            .map(|(index, value)| (index as u32 + 1, *value))
+            .collect()
+    }
+
+    // Find the maximum difference and its place to cut, taking the first place if several share the maximum
+    fn best_cut(&self, min_gap: u32, forbidden: &[(u32, u32)], offset: u32, config: &GuillotineConfig) -> Option<(u32, f64)> {
+        // `max_by_key` keeps the last of equal maximums, so search from the end
+        self.candidates(min_gap, forbidden, offset, config).into_iter().rev().ord_subset_max_by_key(|&(_, value)| value)
+    }
+
+    // Find up to `count` places to cut with the highest differences over the threshold, each at least `spacing` lines
+    // from the others and the ends, in order. Places with equal differences are taken first to last.
+    fn best_cuts(&self, count: usize, spacing: u32, min_gap: u32, forbidden: &[(u32, u32)], offset: u32, config: &GuillotineConfig) -> Vec<u32> {
+        let threshold = config.relative_threshold.unwrap_or(config.threshold);
+        let lines = self.contents.len() as u32;
+        let mut candidates: Vec<(u32, f64)> = self.candidates(min_gap, forbidden, offset, config).into_iter()
+            .filter(|&(cut, value)| value > threshold && cut >= spacing && lines - cut >= spacing)
+            .collect();
+        // A stable sort, so equal differences stay first to last
+        candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut cuts: Vec<u32> = Vec::new();
+        for (cut, _) in candidates {
+            if cuts.len() == count {
+                break;
+            }
+            if cuts.iter().all(|&other| (other as i64 - cut as i64).unsigned_abs() as u32 >= spacing) {
+                cuts.push(cut);
+            }
+        }
+        cuts.sort();
+        cuts
+    }
+
+    // Find the widest run of gutter lines that doesn't touch either end and is at least the minimum gap wide, where a
+    // gutter line is uniform and mostly background, returning the place at its centre and its width
+    fn widest_gutter(&self, min_gap: u32, forbidden: &[(u32, u32)], offset: u32, config: &GuillotineConfig) -> Option<(u32, f64)> {
+        let lines = self.contents.len();
+        let is_gutter = |line: usize| {
+            self.deviations[line] <= config.gap_deviation && self.contents[line] as f64 <= self.line_length as f64 * (1.0 - MIN_GUTTER_SHARE)
+        };
+
+        let mut gutters = Vec::new();
+        let mut start = None;
+        for line in 0 .. lines {
+            match (is_gutter(line), start) {
+                (true, None) => start = Some(line),
+                (false, Some(gutter_start)) => {
+                    gutters.push((gutter_start, line));
+                    start = None;
+                },
+                _ => {}
+            }
+        }
+
+        gutters.into_iter()
+            .filter(|&(start, end)| start > 0 && (end - start) as u32 >= min_gap.max(1))
+            .map(|(start, end)| ((start + (end - start) / 2) as u32, (end - start) as f64))
+            .filter(|&(cut, _)| !forbidden.iter().any(|&(start, end)| start <= offset + cut && offset + cut <= end))
+            .filter(|&(cut, _)| !config.leaves_small(self.line_length, cut, lines as u32))
+            // Take the first of equally wide gutters
+            .rev()
+            .ord_subset_max_by_key(|&(_, width)| width)
+    }
+
+    // Estimate how far the true peak of the difference at a cut lies from it, between half a line before and after,
+    // by fitting a parabola through the difference and its neighbours
+    fn refine(&self, cut: u32) -> f64 {
+        let index = cut as usize - 1;
+        if index == 0 || index + 1 >= self.values.len() {
+            return 0.0;
+        }
+        let (before, peak, after) = (self.values[index - 1], self.values[index], self.values[index + 1]);
+        let curvature = before - 2.0 * peak + after;
+        if curvature >= 0.0 {
+            return 0.0;
+        }
+        (0.5 * (before - after) / curvature).clamp(-0.5, 0.5)
+    }
+
+    // Count the consecutive low-deviation lines on either side of a cut
+    fn gap_width(&self, cut: usize, gap_deviation: f64) -> u32 {
+        let is_gap = |deviation: &&f64| **deviation <= gap_deviation;
+        let before = self.deviations[.. cut].iter().rev().take_while(is_gap).count();
+        let after = self.deviations[cut ..].iter().take_while(is_gap).count();
+        (before + after) as u32
+    }
+}
+
+// The number of bins the differences are counted into to choose a threshold
+const THRESHOLD_BINS: usize = 256;
+
+// Choose the threshold that best splits differences into a low group and a high group, as Otsu's method does for
+// grey levels, by maximising the variance between the two groups
+fn otsu_threshold(values: &[f64]) -> Option<f64> {
+    let max = values.iter().cloned().fold(0.0, f64::max);
+    if values.is_empty() || max <= 0.0 {
+        return None;
+    }
+    let bin_width = max / THRESHOLD_BINS as f64;
+    let mut histogram = [0u64; THRESHOLD_BINS];
+    for &value in values {
+        histogram[((value / bin_width) as usize).min(THRESHOLD_BINS - 1)] += 1;
+    }
+
+    let total = values.len() as f64;
+    let sum: f64 = histogram.iter().enumerate().map(|(bin, &count)| bin as f64 * count as f64).sum();
+    let (mut low_count, mut low_sum) = (0.0, 0.0);
+    let mut best = (0.0, 0);
+    for (bin, &count) in histogram.iter().enumerate() {
+        low_count += count as f64;
+        low_sum += bin as f64 * count as f64;
+        let high_count = total - low_count;
+        if low_count == 0.0 || high_count == 0.0 {
+            continue;
+        }
+        let difference = low_sum / low_count - (sum - low_sum) / high_count;
+        let between = low_count * high_count * difference * difference;
+        if between > best.0 {
+            best = (between, bin);
+        }
+    }
+    // Cut above the top of the low group
+    Some((best.1 + 1) as f64 * bin_width)
+}
+
+// A way of measuring the difference between two neighbouring lines of pixels of the same length, given how much each
+// of the red, green, blue and alpha channels counts, which other programs can implement to measure it their own way
+pub trait LineMetric: Send + Sync {
+    fn difference(&self, old: &[image::Rgba<u8>], new: &[image::Rgba<u8>], weights: &[f64; 4]) -> f64;
+}
+
+// How the difference between two lines is measured
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Metric {
+    // The weighted average difference of the channels
+    Channels,
+    // The average difference in luma, so anti-aliasing between colours of the same brightness isn't an edge
+    Luma,
+    // The average CIE76 Delta-E, which follows how different colours look
+    DeltaE
+}
+
+impl LineMetric for Metric {
+    fn difference(&self, old: &[image::Rgba<u8>], new: &[image::Rgba<u8>], weights: &[f64; 4]) -> f64 {
+        match *self {
+            Metric::Channels => average_difference(old, new, weights),
+            Metric::Luma => average_luma_difference(old, new),
+            Metric::DeltaE => average_delta_e(old, new)
+        }
+    }
+}
+
+impl std::str::FromStr for Metric {
+    type Err = ();
+
+    fn from_str(string: &str) -> Result<Metric, ()> {
+        match string {
+            "channels" | "rgb-abs" => Ok(Metric::Channels),
+            "luma" => Ok(Metric::Luma),
+            "delta-e" | "lab" => Ok(Metric::DeltaE),
+            _ => Err(())
+        }
+    }
+}
+
+// How the alpha channel takes part in the difference
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AlphaMode {
+    Ignore,
+    Only,
+    Weighted
+}
+
+impl std::str::FromStr for AlphaMode {
+    type Err = ();
+
+    fn from_str(string: &str) -> Result<AlphaMode, ()> {
+        match string {
+            "ignore" => Ok(AlphaMode::Ignore),
+            "only" => Ok(AlphaMode::Only),
+            "weighted" => Ok(AlphaMode::Weighted),
+            _ => Err(())
+        }
+    }
+}
+
+// How the places to cut are found
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    // At the line where the image changes the most
+    Difference,
+    // At the centre of the widest run of uniform background lines
+    Gutter,
+    // At the line that crosses the least edge, compared to the lines around it
+    Edge
+}
+
+impl std::str::FromStr for Strategy {
+    type Err = ();
+
+    fn from_str(string: &str) -> Result<Strategy, ()> {
+        match string {
+            "difference" => Ok(Strategy::Difference),
+            "gutter" => Ok(Strategy::Gutter),
+            "edge" => Ok(Strategy::Edge),
+            _ => Err(())
+        }
+    }
+}
+
+// What becomes of a region too small to be a piece, which is otherwise left joined to the region beside it, by not
+// making cuts that would leave one
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeepSmall {
+    // Cut off and kept as a piece anyway
+    Keep,
+    // Left joined to the region beside it, and kept whole if it's the whole image
+    Merge,
+    // Cut off and left out, noting where it was and why in the log
+    Drop
+}
+
+impl std::str::FromStr for KeepSmall {
+    type Err = ();
+
+    fn from_str(string: &str) -> Result<KeepSmall, ()> {
+        match string {
+            "keep" => Ok(KeepSmall::Keep),
+            "merge" => Ok(KeepSmall::Merge),
+            "drop" => Ok(KeepSmall::Drop),
+            _ => Err(())
+        }
+    }
+}
+
+// The direction of a cut line: horizontal cuts split rows apart, vertical cuts split columns apart
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Direction {
+    Horizontal,
+    Vertical
+}
+
+// A round of guillotining in a single direction, optionally limited to a number of levels of cuts
+#[derive(Clone, Serialize)]
+pub struct Pass {
+    pub direction: Direction,
+    pub depth: Option<u32>
+}
+
+impl std::str::FromStr for Pass {
+    type Err = ();
+
+    // Either `h` or `v`, optionally followed by `:levels`
+    fn from_str(string: &str) -> Result<Pass, ()> {
+        let mut parts = string.splitn(2, ':');
+        let direction = match parts.next() {
+            Some("h") => Direction::Horizontal,
+            Some("v") => Direction::Vertical,
+            _ => return Err(())
+        };
+        let depth = match parts.next() {
+            Some(depth) => Some(depth.parse().map_err(|_| ())?),
+            None => None
+        };
+        Ok(Pass { direction, depth })
+    }
+}
+
+// The colour of the gutters between panels
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GutterColor {
+    // Estimated from each image
+    Auto,
+    Rgb([u8; 3])
+}
+
+impl std::str::FromStr for GutterColor {
+    type Err = ();
+
+    // Either `auto` or `#rrggbb`
+    fn from_str(string: &str) -> Result<GutterColor, ()> {
+        if string == "auto" {
+            return Ok(GutterColor::Auto);
+        }
+        parse_color(string).map(GutterColor::Rgb).ok_or(())
+    }
+}
+
+// The settings that control where and whether to cut
+#[derive(Clone, Serialize)]
+pub struct GuillotineConfig {
+    // How the places to cut are found
+    pub strategy: Strategy,
+    // The minimum difference between two lines to cut between them
+    pub threshold: f64,
+    // What the threshold is multiplied by for each level of cuts a region is below, so separators within a region can
+    // be weaker than the ones around it if it's under 1, or have to be stronger if it's over
+    pub threshold_decay: f64,
+    // Whether to choose the threshold for each image from how its differences are spread instead
+    pub auto_threshold: bool,
+    // How much each of the red, green, blue and alpha channels counts towards the difference
+    pub weights: [f64; 4],
+    // Whether the alpha channel is ignored, compared alone or weighted alongside the colour channels
+    pub alpha: AlphaMode,
+    // How the difference between lines is measured
+    pub metric: Metric,
+    // A metric of the calling program's own to use instead, if set
+    #[serde(skip)]
+    pub custom_metric: Option<std::sync::Arc<dyn LineMetric>>,
+    // Blur the image by this sigma before measuring it, so noise and grain don't look like edges
+    pub blur: Option<f32>,
+    // Take the median of each pixel's neighbourhood out to this radius before measuring, so specks and JPEG artefacts
+    // don't look like edges
+    pub median: Option<u32>,
+    // Even out the light falling off towards the spine of a book scan before measuring it, so the gradient isn't cut
+    pub flatten_shading: bool,
+    // Straighten a copy of the image by the rotation its content was scanned at before measuring it, so gutters that
+    // run at a slant don't smear across many lines
+    pub detect_deskew: bool,
+    // Whether to take the pieces from the straightened copy rather than the image as it was, in which case their
+    // rectangles are in the straightened copy
+    pub apply_deskew: bool,
+    // Find the cuts in a copy of the image scaled by this, which is faster for large images
+    pub detect_scale: f64,
+    // Find the cuts in a copy of the image scaled down to at most this many megapixels, if it's any bigger
+    pub detect_megapixels: Option<f64>,
+    // Whether to only keep cuts that are also found in copies of the image at a half and a quarter of the scale, so
+    // fine texture that only looks like an edge close up isn't cut
+    pub consensus: bool,
+    // How far apart, in pixels of the source image, cuts at different scales can be and still be the same cut
+    pub consensus_tolerance: u32,
+    // The minimum width and height of a piece, which both sides of each cut must have
+    pub min_size: u32,
+    // The minimum area of a piece, checked instead of its width and height if set
+    pub min_area: Option<u64>,
+    // What becomes of regions too small to be pieces, other than leaving them out
+    pub keep_small: Option<KeepSmall>,
+    // The most levels of cuts to make, and the most pieces to cut an image into, leaving regions whole once reached
+    pub max_depth: Option<u32>,
+    pub max_pieces: Option<usize>,
+    // Grow each piece's width and height to a multiple of this, as far as the image allows, for encoders that want
+    // sizes divisible by a block size
+    pub round_to: Option<u32>,
+    // The number of low-deviation lines needed around a horizontal or vertical cut
+    pub min_gap_horizontal: u32,
+    pub min_gap_vertical: u32,
+    // The maximum deviation of a line that is part of a gap
+    pub gap_deviation: f64,
+    // The minimum fraction of content pixels each side of a cut needs
+    pub min_content: f64,
+    // How far a pixel's channels may stray from the background before it counts as content
+    pub content_tolerance: f64,
+    // Regions with a luma entropy above this are treated as a single coherent picture and not cut
+    pub max_entropy: Option<f64>,
+    // If set, differences are divided by the local luma range and compared against this threshold instead
+    pub relative_threshold: Option<f64>,
+    // The number of lines on each side of a difference that make up its local range
+    pub contrast_window: usize,
+    // Score each place to cut by the average difference over this many neighbouring pairs of lines around it, so a
+    // single sharp line such as a rule drawn through a panel doesn't win over an edge between different content
+    pub window: usize,
+    // Whether to cut at every place over the threshold in a direction at once, rather than at the best place and then
+    // again in each half, keeping the best place within the minimum size of each other
+    pub multi_cut: bool,
+    // Whether to measure the pieces either side of the likely cut while the other direction is still being measured,
+    // which is faster for long strips where the cut along them is nearly always the one made
+    pub speculate: bool,
+    // Only cut along an image whose long side is at least this many times its short side, rather than measuring across
+    // it at every level, or none to always measure both directions
+    pub aspect_limit: Option<f64>,
+    // The most horizontal and vertical cuts to make across the image, at the places with the highest differences
+    pub max_cuts_horizontal: Option<usize>,
+    pub max_cuts_vertical: Option<usize>,
+    // Ranges of rows and columns of the source image, inclusive, that horizontal and vertical cuts can't fall in
+    pub forbid_horizontal: Vec<(u32, u32)>,
+    pub forbid_vertical: Vec<(u32, u32)>,
+    // The directional passes to make in order, or none to cut in whichever direction has the larger maximum
+    pub passes: Vec<Pass>,
+    // Whether to separate stickers by their connected opaque regions instead of cutting
+    pub stickers: bool,
+    // The alpha a pixel needs to be above to be part of a sticker
+    pub sticker_alpha: u8,
+    // Whether to cut stitched screenshots where their status and navigation bars repeat
+    pub screenshots: bool,
+    // The height of the status and navigation bars to look for
+    pub chrome_rows: u32,
+    // The maximum average difference between rows of matching bars
+    pub chrome_tolerance: f64,
+    // Whether to split receipt rolls into their blocks of printing instead of cutting
+    pub receipts: bool,
+    // The smallest blank gap between two blocks of a receipt, where lines closer together are kept in the same block
+    pub receipt_gap: u32,
+    // The colour of the gutters, to only cut next to lines made up mostly of it
+    pub gutter_color: Option<GutterColor>,
+    // How far a pixel can be from the gutter colour in any channel to count as gutter
+    pub gutter_tolerance: f64,
+    // Whether to cut through the middle of solid dark rules between panels before cutting at gaps
+    pub dividers: bool,
+    // The luma a rule's pixels have to be at or below
+    pub divider_luma: u8,
+    // Whether to leave the rules out of the pieces on both sides rather than cutting through them
+    pub remove_dividers: bool,
+    // Relaxed settings to retry with when an image isn't cut at all
+    pub fallback: Option<Box<GuillotineConfig>>,
+    // Tighter settings to cut each piece again with, to find panels nested inside panels
+    pub refine: Option<Box<GuillotineConfig>>,
+    // Settings for particular inputs, where the first that matches an input is used
+    pub overrides: Vec<overrides::Override>,
+    // Settings for the inputs in particular directories, outermost first, where every one an input is inside is used
+    // and then any override
+    pub directories: Vec<overrides::DirectorySettings>
+}
+
+impl GuillotineConfig {
+    // Get the config to use for an input, with the settings and matching profile of each directory it's in and then
+    // the first override that matches its name applied, or the error in the first of them that's invalid. The config
+    // got has no directories or overrides left, so getting it again for the same input is cheap and changes nothing.
+    pub fn for_input(&self, name: &str) -> Result<GuillotineConfig, String> {
+        let mut config = GuillotineConfig { overrides: Vec::new(), directories: Vec::new(), ..self.clone() };
+        // An input inside an archive is in the archive's directories. In the browser there are no directories to find.
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let path = if self.directories.is_empty() { None } else { Path::new(name).ancestors().find_map(|path| path.canonicalize().ok()) };
+            if let Some(path) = path {
+                for directory in self.directories.iter().filter(|directory| path.starts_with(&directory.dir)) {
+                    apply_setting_list(&mut config, &directory.settings).map_err(|error| format!("for {}: {}", directory.dir.display(), error))?;
+                    if let Some(profile) = directory.profile_for(name) {
+                        apply_setting_list(&mut config, &profile.settings).map_err(|error| format!("in profile {}: {}", profile.profile, error))?;
+                    }
+                }
+            }
+        }
+        if let Some(matching) = self.overrides.iter().find(|matching| matching.matches(name)) {
+            apply_settings(&mut config, &matching.settings).map_err(|error| format!("for {}: {}", matching.pattern, error))?;
+        }
+        Ok(config)
+    }
+
+    // Get the scale to find the cuts in an image of this size at, the smaller of the detection scale and the one that
+    // brings it down to the detection megapixels
+    pub fn detect_scale_for(&self, width: u32, height: u32) -> f64 {
+        let pixels = width as f64 * height as f64;
+        match self.detect_megapixels {
+            Some(megapixels) if pixels > megapixels * 1e6 => self.detect_scale.min((megapixels * 1e6 / pixels).sqrt()),
+            _ => self.detect_scale
+        }
+    }
+
+    // Whether a region is too small to be a piece
+    pub fn too_small(&self, width: u32, height: u32) -> bool {
+        match self.min_area {
+            Some(min_area) => (width as u64 * height as u64) < min_area,
+            None => width < self.min_size || height < self.min_size
+        }
+    }
+
+    // Get the threshold for a region below a number of levels of cuts
+    fn threshold_at(&self, level: u32) -> f64 {
+        self.relative_threshold.unwrap_or(self.threshold) * self.threshold_decay.powi(level as i32)
+    }
+
+    // Whether a cut across lines this long would leave a region too small to be a piece on either side, which would
+    // be lost, so narrow strips like titles stay with the region beside them instead, unless small regions are to be
+    // cut off and kept or noted
+    fn leaves_small(&self, line_length: u32, cut: u32, lines: u32) -> bool {
+        !matches!(self.keep_small, Some(KeepSmall::Keep) | Some(KeepSmall::Drop))
+            && (self.too_small(line_length, cut) || self.too_small(line_length, lines - cut))
+    }
+
+    // Describe the smallest piece there can be
+    fn minimum(&self) -> String {
+        match self.min_area {
+            Some(min_area) => format!("the minimum area of {}", min_area),
+            None => format!("the minimum size of {}", self.min_size)
+        }
+    }
+
+    // Get the colour content is measured against, which is the gutter colour if there is one
+    fn background(&self, image: &image::RgbaImage) -> image::Rgba<u8> {
+        match self.gutter_color {
+            Some(GutterColor::Rgb([red, green, blue])) => image::Rgba { data: [red, green, blue, 255] },
+            _ => estimate_background(image)
+        }
+    }
+
+    // Get how far a pixel can be from the background to not count as content
+    fn line_tolerance(&self) -> f64 {
+        if self.gutter_color.is_some() { self.gutter_tolerance } else { self.content_tolerance }
+    }
+
+    // Get the metric lines are measured with
+    fn line_metric(&self) -> &dyn LineMetric {
+        match self.custom_metric {
+            Some(ref metric) => &**metric,
+            None => &self.metric
+        }
+    }
+
+    // Get the weights of each channel after applying the alpha mode
+    fn channel_weights(&self) -> [f64; 4] {
+        let [red, green, blue, alpha] = self.weights;
+        match self.alpha {
+            AlphaMode::Ignore => [red, green, blue, 0.0],
+            AlphaMode::Only => [0.0, 0.0, 0.0, 1.0],
+            AlphaMode::Weighted => [red, green, blue, alpha]
+        }
+    }
+}
+
+impl Default for GuillotineConfig {
+    fn default() -> GuillotineConfig {
+        GuillotineConfig {
+            strategy: Strategy::Difference,
+            threshold: 30.0,
+            threshold_decay: 1.0,
+            auto_threshold: false,
+            weights: [1.0; 4],
+            alpha: AlphaMode::Weighted,
+            metric: Metric::Channels,
+            custom_metric: None,
+            blur: None,
+            median: None,
+            flatten_shading: false,
+            detect_deskew: false,
+            apply_deskew: false,
+            detect_scale: 1.0,
+            detect_megapixels: None,
+            consensus: false,
+            consensus_tolerance: 4,
+            min_size: 100,
+            min_area: None,
+            keep_small: None,
+            max_depth: None,
+            max_pieces: None,
+            round_to: None,
+            min_gap_horizontal: 0,
+            min_gap_vertical: 0,
+            gap_deviation: 8.0,
+            min_content: 0.0,
+            content_tolerance: 16.0,
+            max_entropy: None,
+            relative_threshold: None,
+            contrast_window: 8,
+            window: 1,
+            multi_cut: false,
+            speculate: false,
+            aspect_limit: Some(10.0),
+            max_cuts_horizontal: None,
+            max_cuts_vertical: None,
+            forbid_horizontal: Vec::new(),
+            forbid_vertical: Vec::new(),
+            passes: Vec::new(),
+            stickers: false,
+            sticker_alpha: 0,
+            screenshots: false,
+            chrome_rows: 16,
+            chrome_tolerance: 4.0,
+            receipts: false,
+            receipt_gap: 24,
+            gutter_color: None,
+            gutter_tolerance: 12.0,
+            dividers: false,
+            divider_luma: 64,
+            remove_dividers: false,
+            fallback: None,
+            refine: None,
+            overrides: Vec::new(),
+            directories: Vec::new()
+        }
+    }
+}
+
+// Get a pixel from its four bytes in an image's buffer
+fn rgba(bytes: &[u8]) -> image::Rgba<u8> {
+    image::Rgba {data: [bytes[0], bytes[1], bytes[2], bytes[3]]}
+}
+
+// Measure the profile of an image across lines of pixels of the given length, each given as its bytes one pixel after
+// another
+fn difference_lines<'a, I: Iterator<Item = &'a [u8]>>(lines: I, count: u32, length: u32, background: image::Rgba<u8>, config: &GuillotineConfig) -> Profile {
+    // Create the old and new lines of pixels once, swapping them after each line rather than copying
+    let mut old: Line = vec![image::Rgba {data: [0; 4]}; length as usize];
+    let mut new: Line = vec![image::Rgba {data: [0; 4]}; length as usize];
+    let mut profile = Profile::with_capacity(count as usize, length);
+    let weights = config.channel_weights();
+    let tolerance = config.line_tolerance();
+    let metric = config.line_metric();
+
+    for (line, bytes) in lines.enumerate() {
+        for (value, bytes) in new.iter_mut().zip(bytes.chunks(4)) {
+            *value = rgba(bytes);
+        }
+
+        // Add the difference from the line before, if there is one
+        if line > 0 {
+            profile.values.push(metric.difference(&old, &new, &weights));
+        }
+        profile.deviations.push(line_deviation(&new, &weights));
+        profile.contents.push(content_pixels(&new, background, &weights, tolerance));
+        profile.ranges.push(luma_range(&new));
+        std::mem::swap(&mut old, &mut new);
+    }
+
+    profile
+}
+
+// How many lines of an image are measured together, as one of the bands its measuring is shared out between threads
+// in. It doesn't depend on how many threads there are, so the sums come out the same however many there are.
+const BAND_LINES: usize = 256;
+
+// Measure the profile of an image's rows, T -> B, straight from its buffer, where each row's pixels are stored together
+fn difference_horizontal(image: &image::RgbaImage, background: image::Rgba<u8>, config: &GuillotineConfig) -> Profile {
+    let (width, height) = image.dimensions();
+    let row = 4 * width as usize;
+    // Each band after the first starts at the last row of the band before, for the difference across the join
+    let starts: Vec<usize> = (0 .. height as usize).step_by(BAND_LINES).collect();
+    let bands: Vec<Profile> = starts.par_iter()
+        .map(|&start| {
+            let first = start.saturating_sub(1);
+            let end = (start + BAND_LINES).min(height as usize);
+            difference_lines(image[first * row .. end * row].chunks(row), (end - first) as u32, width, background, config)
+        })
+        .collect();
+
+    let mut profile = Profile::with_capacity(height as usize, width);
+    for (index, band) in bands.into_iter().enumerate() {
+        let shared = if index > 0 { 1 } else { 0 };
+        profile.values.extend(band.values);
+        profile.deviations.extend_from_slice(&band.deviations[shared ..]);
+        profile.contents.extend_from_slice(&band.contents[shared ..]);
+        profile.ranges.extend_from_slice(&band.ranges[shared ..]);
+    }
+    with_energies(profile, image, true, config)
+}
+
+// Add the edge energy along each line to a profile, if cuts are found by it
+fn with_energies(mut profile: Profile, image: &image::RgbaImage, horizontal: bool, config: &GuillotineConfig) -> Profile {
+    if config.strategy == Strategy::Edge {
+        profile.energies = edges::edge_energies(image, horizontal);
+    }
+    profile
+}
+
+// The sums each column's profile is measured from, built up a row at a time
+struct ColumnSums {
+    sums: Vec<ChannelSums>,
+    contents: Vec<u32>,
+    ranges: Vec<(u8, u8)>,
+    // The difference between each column and the next so far, as exact sums of each channel's difference, or of the
+    // luma difference in the first, or as a sum of Delta-E
+    differences: Vec<[u64; 4]>,
+    delta_es: Vec<f64>
+}
+
+impl ColumnSums {
+    fn new(columns: usize) -> ColumnSums {
+        ColumnSums {
+            sums: vec![ChannelSums::default(); columns],
+            contents: vec![0; columns],
+            ranges: vec![(255, 0); columns],
+            differences: vec![[0; 4]; columns.saturating_sub(1)],
+            delta_es: vec![0.0; columns.saturating_sub(1)]
+        }
+    }
+
+    fn add_row(&mut self, row: &[image::Rgba<u8>], metric: Metric, background: image::Rgba<u8>, weights: &[f64; 4], tolerance: f64) {
+        for (x, pixel) in row.iter().enumerate() {
+            self.sums[x].add(pixel);
+            if is_content(pixel, background, weights, tolerance) {
+                self.contents[x] += 1;
+            }
+            let luma = luma(pixel);
+            self.ranges[x] = (self.ranges[x].0.min(luma), self.ranges[x].1.max(luma));
+        }
+
+        match metric {
+            Metric::Channels => for (difference, pair) in self.differences.iter_mut().zip(row.windows(2)) {
+                for channel in 0 .. 4 {
+                    difference[channel] += (pair[0][channel] as i32 - pair[1][channel] as i32).unsigned_abs() as u64;
+                }
+            },
+            Metric::Luma => for (difference, pair) in self.differences.iter_mut().zip(row.windows(2)) {
+                difference[0] += (luma(&pair[0]) as i32 - luma(&pair[1]) as i32).unsigned_abs() as u64;
+            },
+            Metric::DeltaE => for (delta_e, pair) in self.delta_es.iter_mut().zip(row.windows(2)) {
+                *delta_e += color::delta_e(&pair[0], &pair[1]);
+            }
+        }
+    }
+
+    // Add the sums of the rows in a band below this one's
+    fn merge(&mut self, band: ColumnSums) {
+        for (sums, band) in self.sums.iter_mut().zip(band.sums) {
+            sums.pixels += band.pixels;
+            for channel in 0 .. 4 {
+                sums.sums[channel] += band.sums[channel];
+                sums.squares[channel] += band.squares[channel];
+            }
+        }
+        for (content, band) in self.contents.iter_mut().zip(band.contents) {
+            *content += band;
+        }
+        for (range, band) in self.ranges.iter_mut().zip(band.ranges) {
+            *range = (range.0.min(band.0), range.1.max(band.1));
+        }
+        for (difference, band) in self.differences.iter_mut().zip(band.differences) {
+            for channel in 0 .. 4 {
+                difference[channel] += band[channel];
+            }
+        }
+        for (delta_e, band) in self.delta_es.iter_mut().zip(band.delta_es) {
+            *delta_e += band;
+        }
+    }
+}
+
+// Measure the profile of an image's columns, L -> R. Reading a column at a time would jump a whole row through memory
+// for every pixel, which on a tall scan means fetching the image again for each column, so instead every column's sums
+// are built up together in sweeps down bands of rows, and the bands' sums are added up.
+fn difference_vertical(image: &image::RgbaImage, background: image::Rgba<u8>, config: &GuillotineConfig) -> Profile {
+    let (width, height) = image.dimensions();
+    let metric = match config.custom_metric {
+        // Another program's metric can only measure whole columns, so take them all out of the image at once
+        Some(_) => return with_energies(difference_lines(transpose(image).chunks(4 * height as usize), width, height, background, config), image, false, config),
+        None => config.metric
+    };
+    let weights = config.channel_weights();
+    let tolerance = config.line_tolerance();
+
+    let columns = width as usize;
+    let bands: Vec<ColumnSums> = image.par_chunks(4 * columns * BAND_LINES)
+        .map(|band| {
+            let mut sums = ColumnSums::new(columns);
+            let mut row: Line = vec![image::Rgba {data: [0; 4]}; columns];
+            for bytes in band.chunks(4 * columns) {
+                for (value, bytes) in row.iter_mut().zip(bytes.chunks(4)) {
+                    *value = rgba(bytes);
+                }
+                sums.add_row(&row, metric, background, &weights, tolerance);
+            }
+            sums
+        })
+        .collect();
+    // Add the bands up in order, so the sums of Delta-E are always added the same way
+    let mut sums = ColumnSums::new(columns);
+    for band in bands {
+        sums.merge(band);
+    }
+
+    let mut profile = Profile::with_capacity(columns, height);
+    profile.values = match metric {
+        Metric::Channels => sums.differences.iter().map(|sums| weighted_difference(sums, height as usize, &weights)).collect(),
+        Metric::Luma => sums.differences.iter().map(|sums| sums[0] as f64 / height as f64).collect(),
+        Metric::DeltaE => sums.delta_es.iter().map(|sum| sum / height as f64).collect()
+    };
+    profile.deviations = sums.sums.iter().map(|sums| sums.deviation(&weights)).collect();
+    profile.contents = sums.contents;
+    profile.ranges = sums.ranges;
+    with_energies(profile, image, false, config)
+}
+
+// Get an image's pixels a column at a time, L -> R, each column T -> B
+fn transpose(image: &image::RgbaImage) -> Vec<u8> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let mut columns = vec![0; image.len()];
+    for (y, row) in image.chunks(4 * width).enumerate() {
+        for (x, bytes) in row.chunks(4).enumerate() {
+            let at = 4 * (x * height + y);
+            columns[at .. at + 4].copy_from_slice(bytes);
+        }
+    }
+    columns
+}
+
+// A piece cut out of an image, and where its top left corner was in that image
+pub struct Piece {
+    pub image: image::RgbaImage,
+    // Where the piece came from in the source image, which its image may since have been resized from
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    // How far the true left, top, right and bottom edges lie from the whole lines that were cut, in lines
+    pub subpixel: [f64; 4],
+    // The difference that made the cut along each of the left, top, right and bottom edges, or none where an edge
+    // wasn't cut, such as the edges of the source image
+    pub scores: [Option<f64>; 4],
+    // Which side of each cut the piece fell on, from the first cut to the last, where the first piece of a split is 0,
+    // the next is 1 and so on, so pieces with the same path up to their last cut were cut apart from each other
+    pub path: Vec<u32>,
+    // The x, y, width and height of the piece this one was found inside by refining, if it was
+    pub parent: Option<[u32; 4]>,
+    // The x, y, width and height the piece was cut at, if it has since been grown to round its size
+    pub nominal: Option<[u32; 4]>
+}
+
+impl Piece {
+    // A piece of a whole image, or of the part of it at `x` and `y`, that hasn't been cut any further
+    pub fn new(image: image::RgbaImage, x: u32, y: u32) -> Piece {
+        let (width, height) = image.dimensions();
+        Piece { image, x, y, width, height, subpixel: [0.0; 4], scores: [None; 4], path: Vec::new(), parent: None, nominal: None }
+    }
+
+    // Move a piece found inside a part of an image to where it is in the whole image, below the part's path
+    fn place_within(&mut self, x: u32, y: u32, path: &[u32]) {
+        self.x += x;
+        self.y += y;
+        self.path.splice(0 .. 0, path.iter().cloned());
+    }
+
+    // Get the piece's path written as its indices separated by dots, like `0.1.0`, which is empty for an image that
+    // wasn't cut
+    pub fn id(&self) -> String {
+        self.path.iter().map(|index| index.to_string()).collect::<Vec<String>>().join(".")
+    }
+}
+
+// The pieces an image was cut into, and a log of the decisions made along the way
+pub struct Guillotined {
+    pub pieces: Vec<Piece>,
+    pub log: Vec<String>
+}
+
+impl Guillotined {
+    // A single piece covering the whole image
+    pub fn whole(image: image::RgbaImage, log: Vec<String>) -> Guillotined {
+        Guillotined { pieces: vec![Piece::new(image, 0, 0)], log }
+    }
+
+    // Move another image's pieces and log onto the end of these
+    pub fn append(&mut self, mut other: Guillotined) {
+        self.pieces.append(&mut other.pieces);
+        self.log.append(&mut other.log);
+    }
+}
+
+// How much further a region can be cut: the levels of cuts left, and the most pieces it can be cut into
+#[derive(Clone, Copy)]
+struct Limits {
+    depth: Option<u32>,
+    pieces: Option<usize>,
+    // How many levels of cuts the region is below
+    level: u32
+}
+
+impl Limits {
+    // The limits on cutting an image, given the levels of cuts in a pass if it has its own limit
+    fn new(config: &GuillotineConfig, depth: Option<u32>) -> Limits {
+        let depth = match (depth, config.max_depth) {
+            (Some(depth), Some(max_depth)) => Some(depth.min(max_depth)),
+            (depth, max_depth) => depth.or(max_depth)
+        };
+        Limits { depth, pieces: config.max_pieces, level: 0 }
+    }
+
+    // Whether a region has to be left whole
+    fn reached(self) -> bool {
+        self.depth == Some(0) || self.pieces.map_or(false, |pieces| pieces <= 1)
+    }
+
+    // Share the pieces out between the regions with the given areas, each getting at least one and the rest going by
+    // how much of the area each has, so the same image is always cut the same way whichever region finishes first
+    fn share(self, areas: &[u64]) -> Vec<Limits> {
+        let total = areas.iter().sum::<u64>().max(1);
+        areas.iter()
+            .map(|&area| Limits {
+                depth: self.depth,
+                pieces: self.pieces.map(|pieces| 1 + (pieces.saturating_sub(areas.len()) as u64 * area / total) as usize),
+                level: self.level
+            })
+            .collect()
+    }
+}
+
+// Guillotine an image, either choosing the direction of each cut by the larger maximum difference or, if there are
+// any passes, cutting in each pass's direction in turn
+fn guillotine_rgba(image: image::RgbaImage, config: &GuillotineConfig) -> Guillotined {
+    if config.max_cuts_horizontal.is_some() || config.max_cuts_vertical.is_some() {
+        return guillotine_limited(image, config);
+    }
+    if config.passes.is_empty() {
+        return guillotine_pass(image, config, (0, 0), None, Limits::new(config, None), None);
+    }
+
+    let mut images = Guillotined::whole(image, Vec::new());
+
+    for pass in &config.passes {
+        // Guillotine each piece of the last pass in parallel
+        let log = images.log;
+        let areas: Vec<u64> = images.pieces.iter().map(|piece| piece.width as u64 * piece.height as u64).collect();
+        let limits = Limits::new(config, pass.depth).share(&areas);
+        let passed: Vec<Guillotined> = images.pieces.into_par_iter()
+            .zip(limits)
+            .map(|(piece, limits)| {
+                let mut guillotined = guillotine_pass(piece.image, config, (piece.x, piece.y), Some(pass.direction), limits, None);
+                // Move the new pieces to where the piece was in the image
+                for new_piece in &mut guillotined.pieces {
+                    new_piece.place_within(piece.x, piece.y, &piece.path);
+                }
+                guillotined
+            })
+            .collect();
+
+        images = Guillotined { pieces: Vec::new(), log };
+        for guillotined in passed {
+            images.append(guillotined);
+        }
+    }
+
+    images
+}
+
+// Split an image at the places with the highest differences across it in a direction, up to a number of cuts
+fn split_limited(piece: Piece, config: &GuillotineConfig, horizontal: bool, count: usize) -> Vec<Piece> {
+    let background = config.background(&piece.image);
+    let (mut profile, min_gap, forbidden, offset) = if horizontal {
+        (difference_horizontal(&piece.image, background, config), config.min_gap_horizontal, &config.forbid_horizontal, piece.y)
+    } else {
+        (difference_vertical(&piece.image, background, config), config.min_gap_vertical, &config.forbid_vertical, piece.x)
+    };
+    profile.score(config);
+
+    let cuts = profile.best_cuts(count, config.min_size.max(1), min_gap, forbidden, offset, config);
+    let (x, y, scores, path) = (piece.x, piece.y, piece.scores, piece.path);
+    let score = |index: usize| cuts.get(index).map(|&cut| profile.values[cut as usize - 1]);
+    let (start, end) = if horizontal { (1, 3) } else { (0, 2) };
+    split_lines(piece.image, &cuts, horizontal).into_iter()
+        .enumerate()
+        .map(|(index, mut new_piece)| {
+            new_piece.place_within(x, y, &path);
+            // The strip between two cuts is edged by the cut before it and the cut after it
+            new_piece.scores = scores;
+            if index > 0 {
+                new_piece.scores[start] = score(index - 1);
+            }
+            if index < cuts.len() {
+                new_piece.scores[end] = score(index);
+            }
+            new_piece
+        })
+        .collect()
+}
+
+// Guillotine an image with a limit on the cuts across it in a direction: the image is split at the best places in
+// each limited direction first, then each piece is guillotined as usual in any direction that isn't limited
+fn guillotine_limited(image: image::RgbaImage, config: &GuillotineConfig) -> Guillotined {
+    let (width, height) = image.dimensions();
+    if config.too_small(width, height) {
+        return Guillotined { pieces: Vec::new(), log: Vec::new() };
+    }
+
+    let mut pieces = Guillotined::whole(image, Vec::new()).pieces;
+    if let Some(count) = config.max_cuts_horizontal {
+        pieces = pieces.into_iter().flat_map(|piece| split_limited(piece, config, true, count)).collect();
+    }
+    if let Some(count) = config.max_cuts_vertical {
+        pieces = pieces.into_iter().flat_map(|piece| split_limited(piece, config, false, count)).collect();
+    }
+    let log = vec![format!("Limited cuts: {} pieces", pieces.len())];
+
+    let direction = match (config.max_cuts_horizontal, config.max_cuts_vertical) {
+        (Some(_), Some(_)) => return Guillotined { pieces, log },
+        (Some(_), None) => Direction::Vertical,
+        _ => Direction::Horizontal
+    };
+    let areas: Vec<u64> = pieces.iter().map(|piece| piece.width as u64 * piece.height as u64).collect();
+    let limits = Limits::new(config, None).share(&areas);
+    let guillotined: Vec<Guillotined> = pieces.into_par_iter()
+        .zip(limits)
+        .map(|(piece, limits)| {
+            let mut guillotined = guillotine_pass(piece.image, config, (piece.x, piece.y), Some(direction), limits, None);
+            for new_piece in &mut guillotined.pieces {
+                new_piece.place_within(piece.x, piece.y, &piece.path);
+            }
+            guillotined
+        })
+        .collect();
+
+    let mut images = Guillotined { pieces: Vec::new(), log };
+    for piece in guillotined {
+        images.append(piece);
+    }
+    images
+}
+
+// The background an image was measured against and its horizontal and vertical profiles, where they were measured,
+// passed down to a piece so it doesn't have to measure them again
+type Measured = (image::Rgba<u8>, Option<Profile>, Option<Profile>);
+
+// Cut an image in two across a cut that hasn't been decided on yet and measure both pieces, slicing the profile along
+// the cut for a piece instead of measuring it again where its background is the same as the image's
+fn measure_children(
+    image: &image::RgbaImage, horizontal: bool, cut: u32, profile: &Profile, background: image::Rgba<u8>, config: &GuillotineConfig
+) -> ((image::RgbaImage, Option<Measured>), (image::RgbaImage, Option<Measured>)) {
+    let (width, height) = image.dimensions();
+    let lines = if horizontal { height } else { width };
+    let child = |start: u32, end: u32| {
+        let piece = if horizontal {
+            image::RgbaImage::from_fn(width, end - start, |x, y| *image.get_pixel(x, start + y))
+        } else {
+            image::RgbaImage::from_fn(end - start, height, |x, y| *image.get_pixel(start + x, y))
+        };
+        let piece_background = config.background(&piece);
+        let along = if piece_background == background {
+            profile.slice(start as usize, end as usize)
+        } else if horizontal {
+            difference_horizontal(&piece, piece_background, config)
+        } else {
+            difference_vertical(&piece, piece_background, config)
+        };
+        let across = if horizontal { difference_vertical(&piece, piece_background, config) } else { difference_horizontal(&piece, piece_background, config) };
+        let measured = if horizontal { (piece_background, Some(along), Some(across)) } else { (piece_background, Some(across), Some(along)) };
+        (piece, Some(measured))
+    };
+    parallel::join(|| child(0, cut), || child(cut, lines))
+}
+
+// Recursively guillotine an image that starts at `origin` in the source image, only cutting in the given direction if
+// there is one and stopping once it reaches the limits. When cutting in a single direction, the
+// lines of a piece are whole lines of its parent, so the parent's profile in that direction and the background it was
+// measured against can be passed down instead of measuring it again, as can both profiles of a piece measured while
+// speculating
+fn guillotine_pass(
+    mut image: image::RgbaImage, config: &GuillotineConfig, origin: (u32, u32), direction: Option<Direction>, limits: Limits,
+    cached: Option<Measured>
+) -> Guillotined {
+    let (width, height) = image.dimensions();
+
+    // If the image is too small, don't return anything unless small pieces are kept, or a note of it if they're noted.
+    // Cuts are only made where they'd leave a region this small if small regions are kept or noted, so otherwise only
+    // a whole image can be, which is kept as it is if small regions are merged.
+    if config.too_small(width, height) {
+        let note = |what: &str| vec![format!("{}: {}x{} at {},{}, smaller than {}", what, width, height, origin.0, origin.1, config.minimum())];
+        return match config.keep_small {
+            Some(KeepSmall::Keep) | Some(KeepSmall::Merge) => Guillotined::whole(image, note("Kept small")),
+            Some(KeepSmall::Drop) => Guillotined { pieces: Vec::new(), log: note("Dropped small") },
+            None => Guillotined { pieces: Vec::new(), log: Vec::new() }
+        };
+    }
+
+    // If the pass has made all the levels of cuts or all the pieces it can, return the image
+    if limits.reached() {
+        return Guillotined::whole(image, Vec::new());
+    }
+
+    // If the region looks like a single picture, return it whole
+    if let Some(max_entropy) = config.max_entropy {
+        let entropy = luma_entropy(&image);
+        if entropy > max_entropy {
+            return Guillotined::whole(image, vec![format!("Cut: false, Entropy: {}", entropy)]);
+        }
+    }
+
+    // A long strip is only cut along its length at this level, though its pieces may not be so long
+    let locked = direction.or_else(|| {
+        let limit = config.aspect_limit?;
+        if height as f64 >= width as f64 * limit {
+            Some(Direction::Horizontal)
+        } else if width as f64 >= height as f64 * limit {
+            Some(Direction::Vertical)
+        } else {
+            None
+        }
+    });
+
+    let background = config.background(&image);
+    // The parent's profiles only still hold if the background their content was measured against is the same
+    let (h_cached, v_cached) = match cached {
+        Some((cached_background, h_profile, v_profile)) if cached_background == background => (h_profile, v_profile),
+        _ => (None, None)
+    };
+
+    // Get the maximum differences and the place to cut in a direction, if any line qualifies
+    let best_cut = |profile: &Option<Profile>, horizontal: bool| {
+        let profile = match *profile {
+            Some(ref profile) => profile,
+            None => return (0, 0.0)
+        };
+        let (min_gap, forbidden, offset) = if horizontal {
+            (config.min_gap_horizontal, &config.forbid_horizontal, origin.1)
+        } else {
+            (config.min_gap_vertical, &config.forbid_vertical, origin.0)
+        };
+        if config.strategy == Strategy::Gutter {
+            return profile.widest_gutter(min_gap, forbidden, offset, config).unwrap_or((0, 0.0));
+        }
+        let scored;
+        let profile = if config.relative_threshold.is_some() || config.window > 1 || config.strategy == Strategy::Edge {
+            scored = {
+                let mut scored = profile.clone();
+                scored.score(config);
+                scored
+            };
+            &scored
+        } else {
+            profile
+        };
+        profile.best_cut(min_gap, forbidden, offset, config).unwrap_or((0, 0.0))
+    };
+    // A gutter is only found if it's wide enough, so any gutter is cut at
+    let cuts_at = |max: f64| match config.strategy {
+        Strategy::Difference | Strategy::Edge => max > config.threshold_at(limits.level),
+        Strategy::Gutter => max > 0.0
+    };
+    let measure = |horizontal: bool, cached: Option<Profile>| {
+        cached.unwrap_or_else(|| if horizontal { difference_horizontal(&image, background, config) } else { difference_vertical(&image, background, config) })
+    };
+
+    // Get the profile in each allowed direction. When speculating, the direction along the image's longer side is
+    // measured first, and if it would be cut, the pieces either side of its best cut are measured while the other
+    // direction is, in case that cut is the one made
+    let mut speculated = None;
+    let (h_profile, v_profile) = if config.speculate && locked.is_none() && !config.multi_cut && limits.depth != Some(1) {
+        let along = height >= width;
+        let (along_cached, across_cached) = if along { (h_cached, v_cached) } else { (v_cached, h_cached) };
+        let along_profile = Some(measure(along, along_cached));
+        let (index, max) = best_cut(&along_profile, along);
+        let (across_profile, children) = if cuts_at(max) {
+            let (across_profile, children) = parallel::join(
+                || measure(!along, across_cached),
+                || measure_children(&image, along, index, along_profile.as_ref().unwrap(), background, config)
+            );
+            (across_profile, Some(children))
+        } else {
+            (measure(!along, across_cached), None)
+        };
+        speculated = children.map(|children| (along, index, children));
+        if along { (along_profile, Some(across_profile)) } else { (Some(across_profile), along_profile) }
+    } else {
+        (
+            if locked != Some(Direction::Vertical) { Some(measure(true, h_cached)) } else { None },
+            if locked != Some(Direction::Horizontal) { Some(measure(false, v_cached)) } else { None }
+        )
+    };
+
+    let (h_index, h_max) = best_cut(&h_profile, true);
+    let (v_index, v_max) = best_cut(&v_profile, false);
+
+    // Cut vertically if the maximums are equal
+    let horizontal = h_max > v_max;
+    let max = if horizontal { h_max } else { v_max };
+    let cut = cuts_at(max);
+
+    let mut log = vec![format!("Cut: {}, Horizontal: {}, Max: {}", cut, horizontal, max)];
+
+    // If the image won't be cut, return it
+    if !cut {
+        return Guillotined::whole(image, log);
+    }
+
+    // Find every place to cut in the chosen direction at once, if there is more than one
+    if config.multi_cut && config.strategy != Strategy::Gutter {
+        let (profile, min_gap, forbidden, offset) = if horizontal {
+            (h_profile.as_ref(), config.min_gap_horizontal, &config.forbid_horizontal, origin.1)
+        } else {
+            (v_profile.as_ref(), config.min_gap_vertical, &config.forbid_vertical, origin.0)
+        };
+        let mut profile = profile.unwrap().clone();
+        profile.score(config);
+        // The places to cut are found against this level's threshold
+        let threshold = config.threshold_at(limits.level);
+        let level_config = GuillotineConfig { threshold, relative_threshold: config.relative_threshold.map(|_| threshold), ..config.clone() };
+        let cuts: Vec<(u32, f64)> = profile.best_cuts(usize::MAX, config.min_size.max(1), min_gap, forbidden, offset, &level_config).into_iter()
+            .map(|cut| (cut, profile.values[cut as usize - 1]))
+            .collect();
+        // Cut at one place at a time instead if there can't be a piece for every strip
+        if cuts.len() > 1 && limits.pieces.map_or(true, |pieces| pieces > cuts.len()) {
+            let mut images = Guillotined { pieces: Vec::new(), log };
+            images.log.push(format!("Multiple cuts: {:?}", cuts.iter().map(|&(cut, _)| cut).collect::<Vec<u32>>()));
+            images.append(guillotine_strips(image, &cuts, horizontal, config, origin, direction, limits));
+            return images;
+        }
+    }
+
+    // Find where between the lines the cut really is, for mapping it back from a downscaled image
+    let refined = if horizontal { h_profile.as_ref().map(|profile| profile.refine(h_index)) } else { v_profile.as_ref().map(|profile| profile.refine(v_index)) };
+    let refined = refined.unwrap_or(0.0);
+
+    let index = if horizontal { h_index } else { v_index };
+    let confirmed = speculated.and_then(|(along, speculated_index, children)| if along == horizontal && speculated_index == index { Some(children) } else { None });
+    let ((sub_a, cached_a), (sub_b, cached_b)) = match confirmed {
+        // The pieces were already cut out and measured while speculating
+        Some(children) => {
+            log.push("Speculated: true".to_string());
+            children
+        },
+        None => {
+            // Create the two sub images
+            let (sub_a, sub_b) = if horizontal {(
+                image.sub_image(0, 0, width, h_index).to_image(),
+                image.sub_image(0, h_index, width, height - h_index).to_image()
+            )} else {(
+                image.sub_image(0, 0, v_index, height).to_image(),
+                image.sub_image(v_index, 0, width - v_index, height).to_image()
+            )};
+
+            // Slice the profile in the locked direction for each sub image
+            let (cached_a, cached_b) = match (locked, h_profile, v_profile) {
+                (Some(Direction::Horizontal), Some(profile), _) | (Some(Direction::Vertical), _, Some(profile)) => {
+                    let lines = if horizontal { height } else { width };
+                    let (profile_a, profile_b) = (profile.slice(0, index as usize), profile.slice(index as usize, lines as usize));
+                    if horizontal {
+                        (Some((background, Some(profile_a), None)), Some((background, Some(profile_b), None)))
+                    } else {
+                        (Some((background, None, Some(profile_a))), Some((background, None, Some(profile_b))))
+                    }
+                },
+                _ => (None, None)
+            };
+            ((sub_a, cached_a), (sub_b, cached_b))
+        }
+    };
+
+    // Guillotine the two sub images in parallel
+    let limits = Limits { depth: limits.depth.map(|depth| depth - 1), level: limits.level + 1, ..limits };
+    let areas = [sub_a.width() as u64 * sub_a.height() as u64, sub_b.width() as u64 * sub_b.height() as u64];
+    let shared = limits.share(&areas);
+    let origin_b = if horizontal { (origin.0, origin.1 + h_index) } else { (origin.0 + v_index, origin.1) };
+    let (mut g_a, mut g_b) = parallel::join(
+        || guillotine_pass(sub_a, config, origin, direction, shared[0], cached_a),
+        || guillotine_pass(sub_b, config, origin_b, direction, shared[1], cached_b)
+    );
+
+    // Mark the edges of the pieces that lie along the cut with where it really is and how strong it was
+    for piece in &mut g_a.pieces {
+        piece.path.insert(0, 0);
+        if horizontal && piece.y + piece.height == h_index {
+            piece.subpixel[3] = refined;
+            piece.scores[3] = Some(max);
+        } else if !horizontal && piece.x + piece.width == v_index {
+            piece.subpixel[2] = refined;
+            piece.scores[2] = Some(max);
+        }
+    }
+
+    // Move the second sub image's pieces to where it was in the image
+    for piece in &mut g_b.pieces {
+        piece.path.insert(0, 1);
+        if horizontal {
+            if piece.y == 0 {
+                piece.subpixel[1] = refined;
+                piece.scores[1] = Some(max);
+            }
+            piece.y += h_index;
+        } else {
+            if piece.x == 0 {
+                piece.subpixel[0] = refined;
+                piece.scores[0] = Some(max);
+            }
+            piece.x += v_index;
+        }
+    }
+
+    // Move the guillotined images into images, in the same order whichever finished first
+    let mut images = Guillotined { pieces: Vec::new(), log };
+    images.append(g_a);
+    images.append(g_b);
+
+    // And return them
+    images
+}
+
+// Cut an image into strips at all of the given places and their differences at once, then guillotine each strip
+fn guillotine_strips(
+    image: image::RgbaImage, cuts: &[(u32, f64)], horizontal: bool, config: &GuillotineConfig, origin: (u32, u32),
+    direction: Option<Direction>, limits: Limits
+) -> Guillotined {
+    let limits = Limits { depth: limits.depth.map(|depth| depth - 1), level: limits.level + 1, ..limits };
+    let (start, end) = if horizontal { (1, 3) } else { (0, 2) };
+    let score = |index: usize| Some(cuts[index].1);
+    let places: Vec<u32> = cuts.iter().map(|&(cut, _)| cut).collect();
+
+    let strips = split_lines(image, &places, horizontal);
+    let areas: Vec<u64> = strips.iter().map(|strip| strip.width as u64 * strip.height as u64).collect();
+    let strips: Vec<Guillotined> = strips.into_par_iter()
+        .zip(limits.share(&areas))
+        .enumerate()
+        .map(|(index, (strip, limits))| {
+            let mut guillotined = guillotine_pass(strip.image, config, (origin.0 + strip.x, origin.1 + strip.y), direction, limits, None);
+            // Mark the edges of the pieces that lie along the cuts before and after the strip with how strong they were
+            for piece in &mut guillotined.pieces {
+                let (piece_start, piece_end, strip_end) = if horizontal {
+                    (piece.y, piece.y + piece.height, strip.height)
+                } else {
+                    (piece.x, piece.x + piece.width, strip.width)
+                };
+                if index > 0 && piece_start == 0 {
+                    piece.scores[start] = score(index - 1);
+                }
+                if index < cuts.len() && piece_end == strip_end {
+                    piece.scores[end] = score(index);
+                }
+                piece.place_within(strip.x, strip.y, &[index as u32]);
+            }
+            guillotined
+        })
+        .collect();
+
+    let mut images = Guillotined { pieces: Vec::new(), log: Vec::new() };
+    for strip in strips {
+        images.append(strip);
+    }
+    images
+}
+
+// Cut an image into horizontal strips at each of the given rows, or vertical strips at each of the given columns
+fn split_lines(mut image: image::RgbaImage, cuts: &[u32], horizontal: bool) -> Vec<Piece> {
+    let (width, height) = image.dimensions();
+    let mut starts = vec![0];
+    starts.extend_from_slice(cuts);
+    let mut ends = cuts.to_vec();
+    ends.push(if horizontal { height } else { width });
+
+    starts.iter().zip(&ends).enumerate()
+        .map(|(index, (&start, &end))| {
+            let (x, y, width, height) = if horizontal { (0, start, width, end - start) } else { (start, 0, end - start, height) };
+            Piece { path: vec![index as u32], ..Piece::new(image.sub_image(x, y, width, height).to_image(), x, y) }
+        })
+        .collect()
+}
+
+// Split stickers on a transparent background into a piece each
+fn split_stickers(image: &image::RgbaImage, config: &GuillotineConfig) -> Guillotined {
+    let pieces: Vec<Piece> = stickers::split_stickers(image, config.sticker_alpha, config.min_size).into_iter()
+        .enumerate()
+        .map(|(index, (image, x, y))| Piece { path: vec![index as u32], ..Piece::new(image, x, y) })
+        .collect();
+    Guillotined { log: vec![format!("Stickers: {}", pieces.len())], pieces }
+}
+
+// Split stitched screenshots at their status and navigation bars, falling back to guillotining if there are none
+fn split_screenshots(image: image::RgbaImage, config: &GuillotineConfig) -> Guillotined {
+    let cuts = screenshots::chrome_cuts(&image, config.chrome_rows, config.chrome_tolerance);
+    let mut images = Guillotined { pieces: Vec::new(), log: vec![format!("Screenshot cuts: {:?}", cuts)] };
+
+    if cuts.is_empty() {
+        images.append(guillotine_rgba(image, config));
+    } else {
+        images.pieces = split_lines(image, &cuts, true);
+    }
+    images
+}
+
+// Split an image at the solid dark rules across it, in whichever direction has any, then split each part again until
+// there are no rules left to split at, guillotining what's left at its gaps
+fn split_dividers(mut image: image::RgbaImage, config: &GuillotineConfig) -> Guillotined {
+    let (width, height) = image.dimensions();
+
+    for &horizontal in &[true, false] {
+        let rules = dividers::find_rules(&image, horizontal, config.divider_luma);
+        let lines = if horizontal { height } else { width };
+        let spans = dividers::spans_between(&rules, lines, config.remove_dividers);
+        if spans == [(0, lines)] {
+            continue;
+        }
+
+        let mut images = Guillotined { pieces: Vec::new(), log: vec![format!("Divider cuts: {:?}, Horizontal: {}", spans, horizontal)] };
+        for (index, (start, end)) in spans.into_iter().enumerate() {
+            let (x, y, part_width, part_height) = if horizontal { (0, start, width, end - start) } else { (start, 0, end - start, height) };
+            let mut part = split_dividers(image.sub_image(x, y, part_width, part_height).to_image(), config);
+            for piece in &mut part.pieces {
+                piece.place_within(x, y, &[index as u32]);
+            }
+            images.append(part);
+        }
+        return images;
+    }
+
+    guillotine_rgba(image, config)
+}
+
+// Split a receipt roll into a piece for each block of printing, trimmed to its ink
+fn split_receipts(image: &image::RgbaImage, config: &GuillotineConfig) -> Guillotined {
+    let background = estimate_background(image);
+    let blocks = receipts::receipt_blocks(image, background, &config.channel_weights(), config.content_tolerance, config.receipt_gap);
+    if blocks.is_empty() {
+        return Guillotined::whole(image.clone(), vec!["Receipt blocks: none".to_string()]);
+    }
+
+    let mut image = image.clone();
+    let pieces: Vec<Piece> = blocks.into_iter()
+        .enumerate()
+        .map(|(index, (x, y, width, height))| {
+            Piece { path: vec![index as u32], ..Piece::new(image.sub_image(x, y, width, height).to_image(), x, y) }
+        })
+        .collect();
+    Guillotined { log: vec![format!("Receipt blocks: {}", pieces.len())], pieces }
+}
+
+// Guillotine an image into its pieces, each with the rectangle of the image it came from. Without a name to match
+// against, none of the config's overrides apply.
+pub fn guillotine(image: image::DynamicImage, config: &GuillotineConfig) -> Guillotined {
+    let config = GuillotineConfig { overrides: Vec::new(), ..config.clone() };
+    cut_image("", image, &config).0
+}
+
+// Guillotine a decoded image with the settings for its name, returning its pieces and whether they should keep their
+// transparency, or no pieces and a warning if the settings for its name are invalid
+pub fn cut_image(name: &str, image: image::DynamicImage, config: &GuillotineConfig) -> (Guillotined, bool) {
+    // Remember whether the image had transparency to preserve
+    let has_alpha = matches!(image.color(), image::ColorType::RGBA(_) | image::ColorType::GrayA(_));
+    let (width, height) = image.dimensions();
+    if !cuttable(width, height) {
+        let log = vec![format!("Warning: {} is {}x{}, which is empty or too big to cut", name, width, height)];
+        return (Guillotined { pieces: Vec::new(), log }, has_alpha);
+    }
+    let config = match config.for_input(name) {
+        Ok(config) => config,
+        Err(error) => {
+            let log = vec![format!("Warning: {}'s settings are invalid, so it wasn't cut: {}", name, error)];
+            return (Guillotined { pieces: Vec::new(), log }, has_alpha);
+        }
+    };
+    let image = image.to_rgba();
+    // Keep the whole image to grow the pieces out into if their sizes are to be rounded, straightened as well if the
+    // pieces are taken from a straightened copy, so it's the image their rectangles are in
+    let source = config.round_to.map(|_| if config.apply_deskew { straighten(&image) } else { image.clone() });
+    let guillotined = cut_prepared(image, has_alpha, &config, cut_rgba);
+    let guillotined = refine_pieces(guillotined, has_alpha, &config);
+    match (config.round_to, source) {
+        (Some(multiple), Some(source)) => (round_pieces(guillotined, source, multiple), has_alpha),
+        _ => (guillotined, has_alpha)
+    }
+}
+
+// The widest or tallest image that can be cut, so that positions plus sizes and differences between positions never
+// overflow
+pub const MAX_DIMENSION: u32 = i32::MAX as u32;
+
+// Whether an image of this size can be cut, which it can't if it has no pixels, or is too wide or tall to address or
+// too big to hold
+pub fn cuttable(width: u32, height: u32) -> bool {
+    let bytes = (width as u64 * height as u64).checked_mul(4);
+    width > 0 && height > 0 && width <= MAX_DIMENSION && height <= MAX_DIMENSION && bytes.map_or(false, |bytes| bytes <= isize::MAX as u64)
+}
+
+// Grow each piece evenly on both sides until its width and height are multiples of a number, or as close as the
+// image's edges allow, keeping where it was cut
+fn round_pieces(mut guillotined: Guillotined, mut image: image::RgbaImage, multiple: u32) -> Guillotined {
+    for index in round_rectangles(&mut guillotined, image.dimensions(), multiple) {
+        let piece = &mut guillotined.pieces[index];
+        piece.image = image.sub_image(piece.x, piece.y, piece.width, piece.height).to_image();
+    }
+    guillotined
+}
+
+// Grow the rectangles of the pieces as round_pieces does, in an image of the given size, leaving their images to be
+// cut again. Returns the indices of the pieces that grew.
+fn round_rectangles(guillotined: &mut Guillotined, (width, height): (u32, u32), multiple: u32) -> Vec<usize> {
+    // Get the start and size of a span grown to a multiple, shifted back inside the bounds where it would cross them
+    let round = |start: u32, size: u32, bound: u32| {
+        let rounded = ((size as u64 + multiple as u64 - 1) / multiple as u64 * multiple as u64).min(bound as u64) as u32;
+        let grown_start = start.saturating_sub((rounded - size) / 2).min(bound - rounded);
+        (grown_start, rounded)
+    };
+
+    let mut grown = Vec::new();
+    for (index, piece) in guillotined.pieces.iter_mut().enumerate() {
+        let (x, piece_width) = round(piece.x, piece.width, width);
+        let (y, piece_height) = round(piece.y, piece.height, height);
+        if (piece_width, piece_height) == (piece.width, piece.height) {
+            continue;
+        }
+        piece.nominal = Some([piece.x, piece.y, piece.width, piece.height]);
+        piece.x = x;
+        piece.y = y;
+        piece.width = piece_width;
+        piece.height = piece_height;
+        grown.push(index);
+    }
+    guillotined.log.push(format!("Rounded to multiples of {}: {} pieces grown", multiple, grown.len()));
+    grown
+}
+
+// Cut each piece again with the refining settings of the config got for its input, if there are any, replacing the
+// pieces that are cut with the pieces found inside them
+fn refine_pieces(guillotined: Guillotined, has_alpha: bool, config: &GuillotineConfig) -> Guillotined {
+    let mut refine_config = match config.refine {
+        Some(ref refine) => (**refine).clone(),
+        None => return guillotined
+    };
+    if !has_alpha {
+        if let AlphaMode::Weighted = refine_config.alpha {
+            refine_config.alpha = AlphaMode::Ignore;
+        }
+    }
+
+    let mut refined = Guillotined { pieces: Vec::new(), log: guillotined.log };
+    for (index, piece) in guillotined.pieces.into_iter().enumerate() {
+        let inner = cut_rgba(piece.image.clone(), &refine_config);
+        if inner.pieces.len() <= 1 {
+            refined.pieces.push(piece);
+            continue;
+        }
+
+        refined.log.push(format!("Refined piece {}: {} pieces", index, inner.pieces.len()));
+        let parent = [piece.x, piece.y, piece.width, piece.height];
+        for mut nested in inner.pieces {
+            nested.place_within(piece.x, piece.y, &piece.path);
+            nested.parent = Some(parent);
+            refined.pieces.push(nested);
+        }
+    }
+    refined
+}
+
+// Autoguillotine an image with the config got for its input using the given detection, retrying with the fallback
+// settings if it made no cuts
+fn cut_prepared<F: Fn(image::RgbaImage, &GuillotineConfig) -> Guillotined>(image: image::RgbaImage, has_alpha: bool, config: &GuillotineConfig, cut: F) -> Guillotined {
+    // Opaque images have nothing to compare in the alpha channel
+    let mut image_config = config.clone();
+    if !has_alpha {
+        if let AlphaMode::Weighted = config.alpha {
+            image_config.alpha = AlphaMode::Ignore;
+        }
+    }
+    let mut fallback_config = config.fallback.as_ref().map(|fallback| GuillotineConfig { alpha: image_config.alpha, ..(**fallback).clone() });
+
+    // Estimate the gutter colour if either set of settings asks for it
+    let mut log = Vec::new();
+    let is_auto = |config: &GuillotineConfig| matches!(config.gutter_color, Some(GutterColor::Auto));
+    if is_auto(&image_config) || fallback_config.as_ref().map(is_auto).unwrap_or(false) {
+        let color = estimate_gutter_color(&image, &image_config.channel_weights(), image_config.gap_deviation);
+        log.push(format!("Gutter colour: #{:02x}{:02x}{:02x}", color[0], color[1], color[2]));
+        for config in Some(&mut image_config).into_iter().chain(fallback_config.as_mut()) {
+            if is_auto(config) {
+                config.gutter_color = Some(GutterColor::Rgb(color));
+            }
+        }
+    }
+
+    // Autoguillotine the image, keeping a copy to retry with if there are fallback settings
+    let retry = fallback_config.as_ref().map(|_| image.clone());
+    let mut pieces = cut(image, &image_config);
+    log.append(&mut pieces.log);
+    pieces.log = log;
+
+    if let (Some(fallback_config), Some(image)) = (fallback_config, retry) {
+        if pieces.pieces.len() <= 1 {
+            let mut retried = cut(image, &fallback_config);
+            let mut log = pieces.log;
+            log.push(format!("No cuts, retried with fallback settings: {} pieces", retried.pieces.len()));
+            log.append(&mut retried.log);
+            pieces = Guillotined { pieces: retried.pieces, log };
+        }
+    }
+    pieces
+}
+
+// Find the cuts in a copy of an image downscaled by the scale, then take the pieces from the full image
+fn cut_downscaled(mut image: image::RgbaImage, scale: f64, config: &GuillotineConfig) -> Guillotined {
+    let (width, height) = image.dimensions();
+    let small_width = ((width as f64 * scale).round() as u32).max(1);
+    let small_height = ((height as f64 * scale).round() as u32).max(1);
+    let small = image::imageops::resize(&image, small_width, small_height, image::imageops::FilterType::Triangle);
+
+    let mut guillotined = detect_downscaled(small, width, height, scale, config);
+    for piece in &mut guillotined.pieces {
+        piece.image = image.sub_image(piece.x, piece.y, piece.width, piece.height).to_image();
+    }
+    guillotined
+}
+
+// Find the cuts in a copy of a width by height image downscaled by the scale, with the sizes in the config scaled
+// down to match, placing each cut between the lines where its difference peaked.
+// The pieces' rectangles are in the full image, but their images are still from the small copy.
+fn detect_downscaled(small: image::RgbaImage, width: u32, height: u32, scale: f64, config: &GuillotineConfig) -> Guillotined {
+    let (small_width, small_height) = small.dimensions();
+
+    let scaled = |value: u32| (value as f64 * scale).round() as u32;
+    let scaled_ranges = |ranges: &[(u32, u32)]| ranges.iter().map(|&(start, end)| (scaled(start), scaled(end))).collect();
+    let small_config = GuillotineConfig {
+        detect_scale: 1.0,
+        detect_megapixels: None,
+        min_size: scaled(config.min_size),
+        min_area: config.min_area.map(|min_area| (min_area as f64 * scale * scale).round() as u64),
+        min_gap_horizontal: scaled(config.min_gap_horizontal),
+        min_gap_vertical: scaled(config.min_gap_vertical),
+        forbid_horizontal: scaled_ranges(&config.forbid_horizontal),
+        forbid_vertical: scaled_ranges(&config.forbid_vertical),
+        chrome_rows: scaled(config.chrome_rows),
+        receipt_gap: scaled(config.receipt_gap),
+        ..config.clone()
+    };
+    let mut guillotined = cut_rgba(small, &small_config);
+
+    // Map an edge back to the full image, keeping the image's own edges exact
+    let full = |edge: u32, subpixel: f64, small_size: u32, size: u32| {
+        if edge == 0 {
+            0
+        } else if edge == small_size {
+            size
+        } else {
+            (((edge as f64 + subpixel) / scale).round() as u32).min(size)
+        }
+    };
+    for piece in &mut guillotined.pieces {
+        let left = full(piece.x, piece.subpixel[0], small_width, width);
+        let top = full(piece.y, piece.subpixel[1], small_height, height);
+        let right = full(piece.x + piece.width, piece.subpixel[2], small_width, width).max(left + 1);
+        let bottom = full(piece.y + piece.height, piece.subpixel[3], small_height, height).max(top + 1);
+        piece.x = left;
+        piece.y = top;
+        piece.width = right - left;
+        piece.height = bottom - top;
+        piece.subpixel = [0.0; 4];
+    }
+    guillotined
+}
+
+// The scales, relative to the detection scale, that cuts have to be confirmed at for consensus
+const CONSENSUS_SCALES: [f64; 2] = [0.5, 0.25];
+
+// The most times to cut again after forbidding the cuts that weren't confirmed
+const CONSENSUS_ROUNDS: u32 = 8;
+
+// Get the rows and columns of the source image that were cut, in order, from the edges of the pieces that have a
+// difference
+fn cut_lines(guillotined: &Guillotined) -> (Vec<u32>, Vec<u32>) {
+    let (mut rows, mut columns) = (Vec::new(), Vec::new());
+    for piece in &guillotined.pieces {
+        let edges = [piece.x, piece.y, piece.x + piece.width, piece.y + piece.height];
+        for (side, edge) in edges.iter().enumerate() {
+            if piece.scores[side].is_some() {
+                if side % 2 == 1 { rows.push(*edge) } else { columns.push(*edge) }
+            }
+        }
+    }
+    rows.sort();
+    rows.dedup();
+    columns.sort();
+    columns.dedup();
+    (rows, columns)
+}
+
+// Guillotine an image, only keeping the cuts that are also found at a smaller scale. Cuts that aren't are forbidden
+// and the image is cut again, until every cut is confirmed or there have been too many rounds.
+fn cut_consensus(image: image::RgbaImage, config: &GuillotineConfig) -> Guillotined {
+    let mut config = GuillotineConfig { consensus: false, ..config.clone() };
+    let (width, height) = image.dimensions();
+    let tolerance = config.consensus_tolerance;
+
+    // The smaller scales' cuts don't change between rounds, so only find them once
+    let others: Vec<(Vec<u32>, Vec<u32>)> = CONSENSUS_SCALES.par_iter()
+        .map(|&scale| {
+            let scale = config.detect_scale_for(width, height) * scale;
+            let small_width = ((width as f64 * scale).round() as u32).max(1);
+            let small_height = ((height as f64 * scale).round() as u32).max(1);
+            let small = image::imageops::resize(&image, small_width, small_height, image::imageops::FilterType::Triangle);
+            cut_lines(&detect_downscaled(small, width, height, scale, &config))
+        })
+        .collect();
+    let confirmed = |line: u32, lines: &[u32]| lines.iter().any(|&other| (other as i64 - line as i64).unsigned_abs() as u32 <= tolerance);
+
+    let mut log = Vec::new();
+    let mut round = 1;
+    loop {
+        let mut guillotined = cut_rgba(image.clone(), &config);
+        let (rows, columns) = cut_lines(&guillotined);
+        let unconfirmed_rows: Vec<u32> = rows.into_iter().filter(|&row| !others.iter().any(|other| confirmed(row, &other.0))).collect();
+        let unconfirmed_columns: Vec<u32> = columns.into_iter().filter(|&column| !others.iter().any(|other| confirmed(column, &other.1))).collect();
+
+        let settled = unconfirmed_rows.is_empty() && unconfirmed_columns.is_empty();
+        if settled || round == CONSENSUS_ROUNDS {
+            if !settled {
+                log.push(format!("Consensus: gave up after {} rounds", round));
+            }
+            log.append(&mut guillotined.log);
+            guillotined.log = log;
+            return guillotined;
+        }
+
+        log.push(format!("Consensus: unconfirmed rows {:?}, columns {:?}", unconfirmed_rows, unconfirmed_columns));
+        let around = |line: u32| (line.saturating_sub(tolerance), line + tolerance);
+        config.forbid_horizontal.extend(unconfirmed_rows.into_iter().map(around));
+        config.forbid_vertical.extend(unconfirmed_columns.into_iter().map(around));
+        round += 1;
+    }
+}
+
+// Replace the images of pieces found in a copy of an image with the same rectangles of the image itself
+fn take_pieces(mut guillotined: Guillotined, mut image: image::RgbaImage) -> Guillotined {
+    for piece in &mut guillotined.pieces {
+        piece.image = image.sub_image(piece.x, piece.y, piece.width, piece.height).to_image();
+    }
+    guillotined
+}
+
+// Guillotine an image with a threshold chosen from the differences across all of its rows and columns, or the
+// threshold given if they're all the same
+fn cut_auto_threshold(image: image::RgbaImage, config: &GuillotineConfig) -> Guillotined {
+    let background = config.background(&image);
+    let mut values = difference_horizontal(&image, background, config).values;
+    values.append(&mut difference_vertical(&image, background, config).values);
+    let threshold = otsu_threshold(&values).unwrap_or(config.threshold);
+
+    let mut guillotined = cut_rgba(image, &GuillotineConfig { auto_threshold: false, threshold, ..config.clone() });
+    guillotined.log.insert(0, format!("Auto threshold: {}", threshold));
+    guillotined
+}
+
+// Guillotine a straightened copy of an image, then take the pieces from the copy if the straightening is to be applied,
+// or else from the image as it was, each around where its rectangle lies in it
+fn cut_deskewed(image: image::RgbaImage, config: &GuillotineConfig) -> Guillotined {
+    let straight_config = GuillotineConfig { detect_deskew: false, ..config.clone() };
+    let angle = deskew::skew_angle(&image);
+    if angle == 0.0 {
+        return cut_rgba(image, &straight_config);
+    }
+    let mut guillotined = cut_rgba(deskew::rotate(&image, angle), &straight_config);
+    guillotined.log.insert(0, format!("Skew: {} degrees", angle));
+    if config.apply_deskew {
+        return guillotined;
+    }
+
+    let (width, height) = image.dimensions();
+    for piece in &mut guillotined.pieces {
+        let [x, y, piece_width, piece_height] = deskew::source_rectangle([piece.x, piece.y, piece.width, piece.height], width, height, angle);
+        piece.x = x;
+        piece.y = y;
+        piece.width = piece_width;
+        piece.height = piece_height;
+        piece.subpixel = [0.0; 4];
+    }
+    take_pieces(guillotined, image)
+}
+
+// Straighten an image the way cut_deskewed does before finding the cuts
+fn straighten(image: &image::RgbaImage) -> image::RgbaImage {
+    let angle = deskew::skew_angle(image);
+    if angle == 0.0 {
+        image.clone()
+    } else {
+        deskew::rotate(image, angle)
+    }
+}
+
+// Guillotine an image with the detection its config chooses
+fn cut_rgba(image: image::RgbaImage, config: &GuillotineConfig) -> Guillotined {
+    if config.detect_deskew {
+        return cut_deskewed(image, config);
+    }
+    if config.consensus {
+        return cut_consensus(image, config);
+    }
+    let scale = config.detect_scale_for(image.width(), image.height());
+    if scale < 1.0 {
+        return cut_downscaled(image, scale, config);
+    }
+
+    // Find the cuts in an evenly lit copy, then take the pieces from the image as it was
+    if config.flatten_shading {
+        let flattened = shading::flatten_shading(&image);
+        let guillotined = cut_rgba(flattened, &GuillotineConfig { flatten_shading: false, ..config.clone() });
+        return take_pieces(guillotined, image);
+    }
+
+    // Find the cuts in a smoothed copy, then take the pieces from the image as it was
+    if let Some(radius) = config.median {
+        let smoothed = median::median_filter(&image, radius);
+        let guillotined = cut_rgba(smoothed, &GuillotineConfig { median: None, ..config.clone() });
+        return take_pieces(guillotined, image);
+    }
+
+    // Find the cuts in a blurred copy, then take the pieces from the sharp image
+    if let Some(sigma) = config.blur {
+        let blurred = image::imageops::blur(&image, sigma);
+        let guillotined = cut_rgba(blurred, &GuillotineConfig { blur: None, ..config.clone() });
+        return take_pieces(guillotined, image);
+    }
+
+    // Choose the threshold from the image as it will be measured
+    if config.auto_threshold {
+        return cut_auto_threshold(image, config);
+    }
+
+    if config.stickers {
+        split_stickers(&image, config)
+    } else if config.screenshots {
+        split_screenshots(image, config)
+    } else if config.receipts {
+        split_receipts(&image, config)
+    } else if config.dividers {
+        split_dividers(image, config)
+    } else {
+        guillotine_rgba(image, config)
+    }
+}
+
+// Parse a colour given as `#rrggbb`
+fn parse_color(value: &str) -> Option<[u8; 3]> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |index: usize| u8::from_str_radix(&hex[index * 2 .. index * 2 + 2], 16).ok();
+    Some([channel(0)?, channel(1)?, channel(2)?])
+}
+
+// Split an `h:value` or `v:value` argument into the axes it applies to and its value, with no prefix meaning both
+fn split_axis(value: &str) -> (bool, bool, &str) {
+    if let Some(value) = value.strip_prefix("h:") {
+        (true, false, value)
+    } else if let Some(value) = value.strip_prefix("v:") {
+        (false, true, value)
+    } else {
+        (true, true, value)
+    }
+}
+
+// Parse a flag's value, failing if it is missing or invalid
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} requires a value", flag))?;
+    value.parse().map_err(|_| format!("invalid value for {}: {}", flag, value))
+}
+
+// The settings that can be steps of `--pre`
+const PRE_STEPS: [&str; 6] = ["blur", "median", "flatten-shading", "detect-deskew", "detect-scale", "detect-megapixels"];
+
+// Parse an argument that changes the config, returning whether it was one, or why its value is invalid
+pub fn parse_config_arg<I: Iterator<Item = String>>(config: &mut GuillotineConfig, arg: &str, args: &mut I) -> Result<bool, String> {
+    match arg {
+        "--strategy" => {
+            let value: String = parse_value(arg, args.next())?;
+            config.strategy = value.parse().map_err(|_| format!("{} must be difference, gutter or edge: {}", arg, value))?;
+        },
+        "--threshold" => config.threshold = parse_value(arg, args.next())?,
+        "--threshold-decay" => {
+            config.threshold_decay = parse_value(arg, args.next())?;
+            if !(config.threshold_decay > 0.0 && config.threshold_decay.is_finite()) {
+                return Err(format!("{} must be a positive number", arg));
+            }
+        },
+        "--auto-threshold" => config.auto_threshold = true,
+        "--min-size" => config.min_size = parse_value(arg, args.next())?,
+        "--min-area" => config.min_area = Some(parse_value(arg, args.next())?),
+        "--keep-small" => {
+            let value: String = parse_value(arg, args.next())?;
+            config.keep_small = Some(value.parse().map_err(|_| format!("{} must be keep, merge or drop: {}", arg, value))?);
+        },
+        "--round-to" => {
+            config.round_to = Some(parse_value(arg, args.next())?);
+            if config.round_to == Some(0) {
+                return Err(format!("{} must be at least 1", arg));
+            }
+        },
+        "--max-depth" => config.max_depth = Some(parse_value(arg, args.next())?),
+        "--max-pieces" => {
+            config.max_pieces = Some(parse_value(arg, args.next())?);
+            if config.max_pieces == Some(0) {
+                return Err(format!("{} must be at least 1", arg));
+            }
+        },
+        "--metric" => {
+            let value: String = parse_value(arg, args.next())?;
+            config.metric = value.parse().map_err(|_| format!("{} must be rgb-abs, luma or delta-e: {}", arg, value))?;
+        },
+        "--blur" => config.blur = Some(parse_value(arg, args.next())?),
+        // A pipeline of the steps that prepare the copy of the image the cuts are found in, like `median:2|blur:1.5`,
+        // each the setting of the same name with its value after a colon
+        "--pre" => {
+            let value: String = parse_value(arg, args.next())?;
+            for step in value.split('|').map(str::trim).filter(|step| !step.is_empty()) {
+                let mut parts = step.splitn(2, ':');
+                let name = parts.next().unwrap().trim();
+                if !PRE_STEPS.contains(&name) {
+                    return Err(format!("{} steps must be {}: {}", arg, PRE_STEPS.join(", "), name));
+                }
+                parse_config_arg(config, &format!("--{}", name), &mut parts.next().map(|value| value.trim().to_string()).into_iter())?;
+            }
+        },
+        "--median" => config.median = Some(parse_value(arg, args.next())?),
+        "--flatten-shading" => config.flatten_shading = true,
+        "--detect-deskew" => config.detect_deskew = true,
+        "--apply-deskew" => {
+            config.detect_deskew = true;
+            config.apply_deskew = true;
+        },
+        "--detect-scale" => {
+            config.detect_scale = parse_value(arg, args.next())?;
+            if !(config.detect_scale > 0.0 && config.detect_scale <= 1.0) {
+                return Err(format!("{} must be more than 0 and at most 1", arg));
+            }
+        },
+        "--detect-megapixels" => {
+            let megapixels: f64 = parse_value(arg, args.next())?;
+            if !(megapixels > 0.0 && megapixels.is_finite()) {
+                return Err(format!("{} must be more than 0", arg));
+            }
+            config.detect_megapixels = Some(megapixels);
+        },
+        "--min-gap" => {
+            let value: String = parse_value(arg, args.next())?;
+            let (horizontal, vertical, gap) = split_axis(&value);
+            let gap = parse_value(arg, Some(gap.into()))?;
+            if horizontal { config.min_gap_horizontal = gap; }
+            if vertical { config.min_gap_vertical = gap; }
+        },
+        "--weights" => {
+            let value: String = parse_value(arg, args.next())?;
+            let weights: Vec<f64> = value.split(',').map(|weight| parse_value(arg, Some(weight.into()))).collect::<Result<_, _>>()?;
+            if weights.len() < 3 || weights.len() > 4 || weights.iter().any(|&weight| weight < 0.0) || weights.iter().sum::<f64>() <= 0.0 {
+                return Err(format!("{} takes three or four non-negative weights with a positive sum: {}", arg, value));
+            }
+            config.weights[.. weights.len()].copy_from_slice(&weights);
+        },
+        "--alpha" => {
+            let value: String = parse_value(arg, args.next())?;
+            config.alpha = value.parse().map_err(|_| format!("{} must be ignore, only or weighted: {}", arg, value))?;
+        },
+        "--gap-deviation" => config.gap_deviation = parse_value(arg, args.next())?,
+        "--min-content" => config.min_content = parse_value(arg, args.next())?,
+        "--content-tolerance" => config.content_tolerance = parse_value(arg, args.next())?,
+        "--max-entropy" => config.max_entropy = Some(parse_value(arg, args.next())?),
+        "--relative" => config.relative_threshold = Some(parse_value(arg, args.next())?),
+        "--passes" => {
+            let value: String = parse_value(arg, args.next())?;
+            config.passes = value.split(',').map(|pass| parse_value(arg, Some(pass.into()))).collect::<Result<_, _>>()?;
+        },
+        "--only-horizontal" => config.passes = vec![Pass { direction: Direction::Horizontal, depth: None }],
+        "--only-vertical" => config.passes = vec![Pass { direction: Direction::Vertical, depth: None }],
+        // A single pass never measures the other direction at all
+        "--direction" => {
+            let value: String = parse_value(arg, args.next())?;
+            config.passes = match value.as_str() {
+                "horizontal" => vec![Pass { direction: Direction::Horizontal, depth: None }],
+                "vertical" => vec![Pass { direction: Direction::Vertical, depth: None }],
+                "both" => Vec::new(),
+                _ => return Err(format!("{} must be horizontal, vertical or both: {}", arg, value))
+            };
+        },
+        "--stickers" => config.stickers = true,
+        "--sticker-alpha" => config.sticker_alpha = parse_value(arg, args.next())?,
+        "--screenshots" => config.screenshots = true,
+        "--chrome-rows" => config.chrome_rows = parse_value(arg, args.next())?,
+        "--chrome-tolerance" => config.chrome_tolerance = parse_value(arg, args.next())?,
+        "--receipts" => config.receipts = true,
+        "--gutter-color" => {
+            let value: String = parse_value(arg, args.next())?;
+            config.gutter_color = Some(value.parse().map_err(|_| format!("{} takes a colour like #f0f0f0 or auto", arg))?);
+        },
+        "--gutter-tolerance" => config.gutter_tolerance = parse_value(arg, args.next())?,
+        "--dividers" => config.dividers = true,
+        "--divider-luma" => config.divider_luma = parse_value(arg, args.next())?,
+        "--remove-dividers" => {
+            config.dividers = true;
+            config.remove_dividers = true;
+        },
+        "--receipt-gap" => config.receipt_gap = parse_value(arg, args.next())?,
+        "--multi-cut" => config.multi_cut = true,
+        "--speculate" => config.speculate = true,
+        "--aspect-limit" => config.aspect_limit = Some(parse_value(arg, args.next())?),
+        "--no-aspect-limit" => config.aspect_limit = None,
+        "--max-cuts-h" => config.max_cuts_horizontal = Some(parse_value(arg, args.next())?),
+        "--max-cuts-v" => config.max_cuts_vertical = Some(parse_value(arg, args.next())?),
+        "--forbid" => {
+            let value: String = parse_value(arg, args.next())?;
+            let (horizontal, vertical, range) = split_axis(&value);
+            let mut bounds = range.splitn(2, '-');
+            let start: u32 = parse_value(arg, bounds.next().map(String::from))?;
+            let end: u32 = parse_value(arg, bounds.next().map(String::from))?;
+            if start > end {
+                return Err(format!("{} takes a range from low to high: {}", arg, value));
+            }
+            if horizontal { config.forbid_horizontal.push((start, end)); }
+            if vertical { config.forbid_vertical.push((start, end)); }
+        },
+        "--contrast-window" => config.contrast_window = parse_value(arg, args.next())?,
+        "--window" => {
+            config.window = parse_value(arg, args.next())?;
+            if config.window == 0 {
+                return Err(format!("{} must be at least 1", arg));
+            }
+        },
+        "--consensus" => config.consensus = true,
+        "--consensus-tolerance" => config.consensus_tolerance = parse_value(arg, args.next())?,
+        _ => return Ok(false)
+    }
+    Ok(true)
+}
+
+// Apply settings in the form `name=value,name=value`, where each name is a config option without its dashes,
+// failing on any setting that isn't one or has an invalid value
+pub fn apply_settings(config: &mut GuillotineConfig, settings: &str) -> Result<(), String> {
+    for setting in settings.split(',').map(str::trim).filter(|setting| !setting.is_empty()) {
+        let mut parts = setting.splitn(2, '=');
+        let flag = format!("--{}", parts.next().unwrap().trim());
+        let mut value = parts.next().map(|value| value.trim().to_string()).into_iter();
+        if !parse_config_arg(config, &flag, &mut value)? {
+            return Err(format!("unknown setting: {}", setting));
+        }
+    }
+    Ok(())
+}
+
+// Apply settings already split into each name, without its dashes, and its value if it has one, failing in the same
+// way
+pub fn apply_setting_list(config: &mut GuillotineConfig, settings: &[(String, Option<String>)]) -> Result<(), String> {
+    for (name, value) in settings {
+        let flag = format!("--{}", name);
+        if !parse_config_arg(config, &flag, &mut value.clone().into_iter())? {
+            return Err(format!("unknown setting: {}", name));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{self, ImageBuffer, Rgba};
+
+    use testutil::{self, PageSpec};
+    use {guillotine, GuillotineConfig, Strategy};
+
+    // A white image with black rows from `start` to `end`
+    fn banded(width: u32, height: u32, start: u32, end: u32) -> image::DynamicImage {
+        image::DynamicImage::ImageRgba8(ImageBuffer::from_fn(width, height, |_, y| {
+            if start <= y && y < end { Rgba { data: [0, 0, 0, 255] } } else { Rgba { data: [255; 4] } }
+        }))
+    }
+
+    // Two panels shaded from black at the top and bottom of the image to grey, running into a band of flat grey
+    // from `start` to `end` with no line on either side of it
+    fn shaded(width: u32, height: u32, start: u32, end: u32) -> image::DynamicImage {
+        image::DynamicImage::ImageRgba8(ImageBuffer::from_fn(width, height, |_, y| {
+            let luma = if y < start { 128 * y / start } else if y < end { 128 } else { 128 * (height - 1 - y) / (height - 1 - end) };
+            Rgba { data: [luma as u8, luma as u8, luma as u8, 255] }
+        }))
+    }
+
+    fn rectangles(image: image::DynamicImage, config: &GuillotineConfig) -> Vec<(u32, u32, u32, u32)> {
+        guillotine(image, config).pieces.iter().map(|piece| (piece.x, piece.y, piece.width, piece.height)).collect()
+    }
+
+    #[test]
+    fn cut_leaving_a_narrow_strip_is_not_made() {
+        // The only edge is 30 rows down, so cutting there would leave a strip under the minimum size above it
+        let config = GuillotineConfig { min_size: 50, ..GuillotineConfig::default() };
+        assert_eq!(rectangles(banded(300, 300, 0, 30), &config), vec![(0, 0, 300, 300)]);
+    }
+
+    #[test]
+    fn cut_leaving_both_sides_big_enough_is_made() {
+        let config = GuillotineConfig { min_size: 50, ..GuillotineConfig::default() };
+        assert_eq!(rectangles(banded(300, 300, 0, 100), &config), vec![(0, 0, 300, 100), (0, 100, 300, 200)]);
+    }
+
+    #[test]
+    fn edge_cuts_shaded_panels_at_a_soft_border() {
+        // Neighbouring rows barely differ anywhere, so only the edges crossed find the border
+        assert_eq!(rectangles(shaded(300, 400, 190, 210), &GuillotineConfig::default()).len(), 1);
+        let config = GuillotineConfig { strategy: Strategy::Edge, ..GuillotineConfig::default() };
+        let pieces = rectangles(shaded(300, 400, 190, 210), &config);
+        assert_eq!(pieces.len(), 2);
+        assert!(190 <= pieces[1].1 && pieces[1].1 <= 210);
+    }
+
+    #[test]
+    fn difference_finds_the_panels_of_a_page() {
+        let spec = PageSpec::default();
+        assert!(testutil::finds_panels(&GuillotineConfig::default(), &testutil::page(&spec), spec.gutter));
+    }
+
+    #[test]
+    fn gutter_finds_the_panels_of_a_page() {
+        let config = GuillotineConfig { strategy: Strategy::Gutter, ..GuillotineConfig::default() };
+        let spec = PageSpec { rows: 2, columns: 3, ..PageSpec::default() };
+        assert!(testutil::finds_panels(&config, &testutil::page(&spec), spec.gutter));
+    }
+}
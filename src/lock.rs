@@ -0,0 +1,40 @@
+// Stop two runs writing to the same place at once
+
+use std::collections::BTreeSet;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+
+// The lock file left in each directory outputs are written to
+const LOCK_NAME: &str = ".autoguillotine.lock";
+
+// The directory an output path is written into, resolved so the same directory reached by different paths is only
+// locked once
+fn output_root(path: &Path) -> PathBuf {
+    let root = match path.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new(".")
+    };
+    root.canonicalize().unwrap_or_else(|_| root.to_path_buf())
+}
+
+// Take an advisory lock on every directory the outputs will be written into, which is the directory of each input
+// and of any book or sprite sheet. The locks are held until the files are dropped or the process exits, so a run that
+// crashes never leaves a stale lock behind.
+pub fn lock_outputs<'a, I: IntoIterator<Item = &'a Path>>(outputs: I) -> Result<Vec<File>, String> {
+    let roots: BTreeSet<PathBuf> = outputs.into_iter().map(output_root).collect();
+
+    let mut locks = Vec::new();
+    for root in roots {
+        let path = root.join(LOCK_NAME);
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(&path)
+            .map_err(|error| format!("couldn't create {}: {}", path.display(), error))?;
+        match file.try_lock() {
+            Ok(()) => locks.push(file),
+            Err(TryLockError::WouldBlock) => {
+                return Err(format!("another run is writing to {}, wait for it to finish or pass --no-lock", root.display()));
+            },
+            Err(TryLockError::Error(error)) => return Err(format!("couldn't lock {}: {}", path.display(), error))
+        }
+    }
+    Ok(locks)
+}
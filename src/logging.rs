@@ -0,0 +1,174 @@
+// Print what came of each input, as much of it as the verbosity asks for, as lines of text or of JSON for programs
+// watching a run, and show how far through its inputs a run is on a terminal
+
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde_json;
+
+use manifest::Stamp;
+
+// How much is printed
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Verbosity {
+    // Only errors
+    Quiet,
+    // What went wrong and a line for each input
+    #[default]
+    Normal,
+    // Every decision made and file saved
+    Verbose
+}
+
+// How what's printed is written
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    // An object a line, with the input it's about and its level, or how many pieces the input made
+    Json
+}
+
+impl ::std::str::FromStr for LogFormat {
+    type Err = ();
+
+    fn from_str(string: &str) -> Result<LogFormat, ()> {
+        match string {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(())
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    input: &'a str,
+    level: &'static str,
+    message: &'a str
+}
+
+#[derive(Serialize)]
+struct Summary<'a> {
+    input: &'a str,
+    pieces: usize,
+    failed: bool
+}
+
+// Get how serious a line of a log is, by how it starts
+fn level(line: &str) -> &'static str {
+    if line.starts_with("Error") {
+        "error"
+    } else if line.starts_with("Warning") {
+        "warning"
+    } else if line.starts_with("Mismatch") {
+        "mismatch"
+    } else {
+        "info"
+    }
+}
+
+// Whether a line of an input's log says something went wrong, which is printed unless quiet
+pub fn is_problem(line: &str) -> bool {
+    level(line) != "info"
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Logging {
+    pub verbosity: Verbosity,
+    pub format: LogFormat
+}
+
+impl Logging {
+    pub fn verbose(&self) -> bool {
+        self.verbosity == Verbosity::Verbose
+    }
+
+    fn write(&self, text: &str, to_stderr: bool) {
+        if to_stderr { eprintln!("{}", text) } else { println!("{}", text) }
+    }
+
+    // Print the lines of an input's log that the verbosity asks for: errors when quiet, the lines `usual` picks out
+    // otherwise and all of them when verbose. They go to stderr when stdout is taken up by the pieces.
+    pub fn print_log(&self, input: &str, log: &[String], usual: fn(&str) -> bool, to_stderr: bool) {
+        let shown = log.iter().filter(|line| match self.verbosity {
+            Verbosity::Quiet => level(line) == "error",
+            Verbosity::Normal => usual(line),
+            Verbosity::Verbose => true
+        });
+        for line in shown {
+            match self.format {
+                LogFormat::Text => self.write(line, to_stderr),
+                LogFormat::Json => self.write(&serde_json::to_string(&Line { input, level: level(line), message: line }).unwrap(), to_stderr)
+            }
+        }
+    }
+
+    // Print how many pieces an input made, which is a line of its own in text only at the usual verbosity, as the
+    // verbose log already says, and always in JSON
+    pub fn print_summary(&self, input: &str, pieces: usize, failed: bool, to_stderr: bool) {
+        match (self.format, self.verbosity) {
+            (LogFormat::Json, _) => self.write(&serde_json::to_string(&Summary { input, pieces, failed }).unwrap(), to_stderr),
+            (LogFormat::Text, Verbosity::Normal) => self.write(&format!("{} -> {} pieces", input, pieces), to_stderr),
+            _ => {}
+        }
+    }
+
+    // Print what a run is doing, unless quiet
+    pub fn print(&self, message: &str) {
+        match (self.format, self.verbosity) {
+            (_, Verbosity::Quiet) => {},
+            (LogFormat::Text, _) => println!("{}", message),
+            (LogFormat::Json, _) => println!("{}", serde_json::to_string(&Line { input: "", level: "info", message }).unwrap())
+        }
+    }
+
+    // Print the version and settings a run started with
+    pub fn print_run(&self, run: &Stamp) {
+        match self.format {
+            LogFormat::Text => self.print(&format!("autoguillotine {}, settings {}: {}", run.version, run.fingerprint, run.settings)),
+            LogFormat::Json if self.verbosity != Verbosity::Quiet => println!("{}", serde_json::to_string(run).unwrap()),
+            LogFormat::Json => {}
+        }
+    }
+
+    // Whether to show progress, which only makes sense in text on a terminal
+    pub fn shows_progress(&self) -> bool {
+        self.format == LogFormat::Text && self.verbosity != Verbosity::Quiet && io::stderr().is_terminal()
+    }
+}
+
+// How many of a run's inputs are done, shown on one line of stderr that's written over as each finishes
+pub struct Progress {
+    total: usize,
+    done: AtomicUsize,
+    shown: bool
+}
+
+impl Progress {
+    pub fn new(total: usize, shown: bool) -> Progress {
+        Progress { total, done: AtomicUsize::new(0), shown: shown && total > 1 }
+    }
+
+    // Count an input as done, showing a bar of how many are
+    pub fn advance(&self, input: &str) {
+        let done = self.done.fetch_add(1, Ordering::SeqCst) + 1;
+        if !self.shown {
+            return;
+        }
+        const WIDTH: usize = 30;
+        let filled = done * WIDTH / self.total;
+        let name: String = input.chars().rev().take(40).collect::<Vec<char>>().into_iter().rev().collect();
+        let stderr = io::stderr();
+        let mut stderr = stderr.lock();
+        let _ = write!(stderr, "\r\x1b[K[{}{}] {}/{} {}", "#".repeat(filled), " ".repeat(WIDTH - filled), done, self.total, name);
+        let _ = stderr.flush();
+    }
+
+    // Clear the bar once every input is done, before what came of them is printed
+    pub fn finish(&self) {
+        if self.shown {
+            eprint!("\r\x1b[K");
+        }
+    }
+}
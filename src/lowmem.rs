@@ -0,0 +1,147 @@
+// Guillotine huge PNGs without ever holding them whole, by streaming their rows into a small copy to find the cuts
+// in, then streaming them again for each piece to take just its pixels
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use image::{Rgba, RgbaImage};
+use png;
+
+use {cut_prepared, detect_downscaled, refine_pieces, round_rectangles, GuillotineConfig, Guillotined};
+
+// How far to scale images down to find the cuts in, unless the settings ask for a smaller scale
+const LOW_MEMORY_SCALE: f64 = 0.25;
+
+// Whether an image can be streamed a row at a time, which needs it to be a PNG that isn't interlaced
+pub fn is_streamable(path: &Path) -> bool {
+    // The signature, then the header chunk's length and type, width, height, bit depth, colour type, compression
+    // method and filter method come before the interlace method
+    let mut header = [0; 29];
+    let read = File::open(path).and_then(|mut file| file.read_exact(&mut header));
+    read.is_ok() && header.starts_with(b"\x89PNG\r\n\x1a\n") && &header[12 .. 16] == b"IHDR" && header[28] == 0
+}
+
+// A PNG being decoded a row at a time
+type Rows = png::Reader<BufReader<File>>;
+
+// Start decoding a PNG, returning its width, height and whether it has transparency along with the reader
+fn open(path: &Path) -> Result<(u32, u32, bool, Rows), String> {
+    let file = File::open(path).map_err(|error| error.to_string())?;
+    let (info, mut reader) = png::Decoder::new(BufReader::new(file)).read_info().map_err(|error| error.to_string())?;
+    let (color, _) = reader.output_color_type();
+    let has_alpha = matches!(color, png::ColorType::RGBA | png::ColorType::GrayscaleAlpha);
+    Ok((info.width, info.height, has_alpha, reader))
+}
+
+// Get a pixel of a decoded row, which the decoder has already expanded to eight bits a channel
+fn row_pixel(row: &[u8], x: u32, samples: usize) -> Rgba<u8> {
+    let sample = &row[x as usize * samples ..];
+    let data = match samples {
+        1 => [sample[0], sample[0], sample[0], 255],
+        2 => [sample[0], sample[0], sample[0], sample[1]],
+        3 => [sample[0], sample[1], sample[2], 255],
+        _ => [sample[0], sample[1], sample[2], sample[3]]
+    };
+    Rgba { data }
+}
+
+// Decode a PNG into a copy downscaled by the scale, averaging the pixels that fall into each of its pixels.
+// Returns the copy, the size of the full image and whether it has transparency.
+fn decode_downscaled(path: &Path, scale: f64) -> Result<(RgbaImage, u32, u32, bool), String> {
+    let (width, height, has_alpha, mut reader) = open(path)?;
+    let samples = reader.output_color_type().0.samples();
+    let small_width = ((width as f64 * scale).round() as u32).max(1);
+    let small_height = ((height as f64 * scale).round() as u32).max(1);
+    let mut small = RgbaImage::new(small_width, small_height);
+
+    // The sums of the channels of the full pixels in each small pixel of the current small row, and how many there are
+    let mut sums = vec![[0u64; 4]; small_width as usize];
+    let mut counts = vec![0u64; small_width as usize];
+    let mut flush = |small_y: u32, sums: &mut [[u64; 4]], counts: &mut [u64]| {
+        for (small_x, (sum, count)) in sums.iter_mut().zip(counts.iter_mut()).enumerate() {
+            let pixels = (*count).max(1);
+            let mut data = [0; 4];
+            for (channel, value) in data.iter_mut().enumerate() {
+                *value = (sum[channel] / pixels) as u8;
+            }
+            small.put_pixel(small_x as u32, small_y, Rgba { data });
+            *sum = [0; 4];
+            *count = 0;
+        }
+    };
+
+    let mut current = 0;
+    for y in 0 .. height {
+        let row = reader.next_row().map_err(|error| error.to_string())?.ok_or("the image data ended early")?;
+        let small_y = (y as u64 * small_height as u64 / height as u64) as u32;
+        if small_y != current {
+            flush(current, &mut sums, &mut counts);
+            current = small_y;
+        }
+        for x in 0 .. width {
+            let small_x = (x as u64 * small_width as u64 / width as u64) as usize;
+            let pixel = row_pixel(row, x, samples);
+            for (sum, &value) in sums[small_x].iter_mut().zip(pixel.data.iter()) {
+                *sum += value as u64;
+            }
+            counts[small_x] += 1;
+        }
+    }
+    flush(current, &mut sums, &mut counts);
+
+    Ok((small, width, height, has_alpha))
+}
+
+// Decode just the rectangles of a PNG given as their x, y, width and height, all in one pass over its rows that reads
+// no further than the last row any of them needs, rather than decoding the image again for each of them
+fn decode_regions(path: &Path, regions: &[(u32, u32, u32, u32)]) -> Result<Vec<RgbaImage>, String> {
+    let (_, _, _, mut reader) = open(path)?;
+    let samples = reader.output_color_type().0.samples();
+    let mut images: Vec<RgbaImage> = regions.iter().map(|&(_, _, width, height)| RgbaImage::new(width, height)).collect();
+    let end = regions.iter().map(|&(_, y, _, height)| y + height).max().unwrap_or(0);
+
+    for row_y in 0 .. end {
+        let row = reader.next_row().map_err(|error| error.to_string())?.ok_or("the image data ended early")?;
+        for (image, &(x, y, width, height)) in images.iter_mut().zip(regions) {
+            if row_y >= y && row_y < y + height {
+                for column in 0 .. width {
+                    image.put_pixel(column, row_y - y, row_pixel(row, x + column, samples));
+                }
+            }
+        }
+    }
+    Ok(images)
+}
+
+// Autoguillotine a PNG, holding only a small copy of it and then its pieces rather than the whole image.
+// Returns the pieces and whether the image had transparency.
+pub fn cut_png(path: &Path, name: &str, config: &GuillotineConfig) -> Result<(Guillotined, bool), String> {
+    let (width, height, _, _) = open(path)?;
+    let config = &config.for_input(name)?;
+    let detect_scale = config.detect_scale_for(width, height);
+    let scale = if detect_scale < 1.0 { detect_scale } else { LOW_MEMORY_SCALE };
+    let (small, width, height, has_alpha) = decode_downscaled(path, scale)?;
+
+    let mut guillotined = cut_prepared(small, has_alpha, config, |small, config| {
+        detect_downscaled(small, width, height, scale, config)
+    });
+    let regions: Vec<(u32, u32, u32, u32)> = guillotined.pieces.iter().map(|piece| (piece.x, piece.y, piece.width, piece.height)).collect();
+    for (piece, image) in guillotined.pieces.iter_mut().zip(decode_regions(path, &regions)?) {
+        piece.image = image;
+    }
+    let mut guillotined = refine_pieces(guillotined, has_alpha, config);
+
+    // Grow the pieces to the multiple, decoding the ones that grew again in a second pass
+    if let Some(multiple) = config.round_to {
+        let grown = round_rectangles(&mut guillotined, (width, height), multiple);
+        let regions: Vec<(u32, u32, u32, u32)> = grown.iter()
+            .map(|&index| &guillotined.pieces[index])
+            .map(|piece| (piece.x, piece.y, piece.width, piece.height))
+            .collect();
+        for (&index, image) in grown.iter().zip(decode_regions(path, &regions)?) {
+            guillotined.pieces[index].image = image;
+        }
+    }
+    Ok((guillotined, has_alpha))
+}
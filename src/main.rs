@@ -1,156 +1,2390 @@
+extern crate autoguillotine;
+extern crate flate2;
 extern crate image;
-extern crate ord_subset;
+#[cfg(feature = "watch")]
+extern crate notify;
+extern crate png;
 extern crate rayon;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate zip;
 
+mod analyze;
+mod archive;
+mod atomic;
+mod barcodes;
+mod blurhash;
+mod cbz;
+mod collection;
+mod check;
+mod dedupe;
+mod depth;
+mod diff;
+mod epub;
+mod error;
+mod events;
+mod formats;
+mod frames;
+mod furniture;
+mod hooks;
+mod icc;
+mod incremental;
+mod indexed;
+mod layers;
+mod layout;
+mod logging;
+mod lock;
+mod manifest;
+mod montage;
+mod naming;
+mod pack;
+mod pdf;
+mod physical;
+mod plan;
+mod postprocess;
+mod preview;
+mod rundir;
+mod sha256;
+mod slices;
+mod sprites;
+mod stats;
+mod stream;
+mod summary;
+mod verify;
+mod watch;
+
+use autoguillotine::{apply_setting_list, apply_settings, cut_image, cuttable, deskew, estimate_background, luma, luma_entropy, lowmem, overrides, parse_config_arg};
+use autoguillotine::{GuillotineConfig, Guillotined, Metric, Piece};
 use image::GenericImage;
-use ord_subset::OrdSubsetIterExt;
+use rayon::prelude::*;
 
+use std::collections::BTreeMap;
 use std::env::args;
-use std::path::Path;
-use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use std::fs::{create_dir_all, File};
+use std::io::Read;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::process::exit;
+
+use error::Error;
 
-// A 1D line of pixels
-type Line = Vec<image::Rgb<u8>>;
+// What to do with an image too small to make any pieces
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum TooSmall {
+    // Keep it whole as a single piece
+    Copy,
+    // Make no pieces from it, with a warning
+    Warn,
+    // Fail it, like an image that can't be decoded
+    Error
+}
 
-// Get the average difference between two lines of pixels
-fn average_difference(old: &Line, new: &Line) -> f64 {
-    let mut value = 0.0;
+impl std::str::FromStr for TooSmall {
+    type Err = ();
 
-    // Sum up the difference for each channel of each pixel
-    for index in 0 .. old.len() {
-        for channel in 0 .. 3 {
-            value += (old[index][channel] as f64 - new[index][channel] as f64).abs();
+    fn from_str(string: &str) -> Result<TooSmall, ()> {
+        match string {
+            "copy" => Ok(TooSmall::Copy),
+            "warn" => Ok(TooSmall::Warn),
+            "error" => Ok(TooSmall::Error),
+            _ => Err(())
         }
     }
+}
+
+// The settings that control how the pieces are written
+#[derive(Serialize)]
+struct OutputOptions {
+    // Whether to write a CBZ archive next to each input instead of a directory of pieces
+    cbz: bool,
+    // Where to write a single EPUB of every input's pieces instead of a directory of pieces
+    epub: Option<PathBuf>,
+    // Where to write a single sprite sheet of every input's pieces, with a stylesheet and an index beside it
+    sprites: Option<PathBuf>,
+    // Where to write an ffmpeg concat script showing every input's pieces in order, and for how many seconds each
+    montage: Option<PathBuf>,
+    montage_duration: f64,
+    // Whether to write each distinct piece of an input once, pointing the duplicates' rectangles in the manifest at
+    // its file, and how many bits of their perceptual hashes pieces can differ by to count as the same, if any
+    dedupe: bool,
+    dedupe_distance: Option<u32>,
+    // Whether to save a sheet of each input's pieces, numbered and labelled with their sizes, beside them
+    summary: bool,
+    // A command to run on each piece written to a directory, with `{piece}` replaced by its path
+    post_command: Option<String>,
+    // How many post commands may run at once, which doesn't change the results
+    #[serde(skip_serializing)]
+    post_jobs: usize,
+    // Where to send a message about each piece written to a directory, which is left out of the settings as it may
+    // hold credentials
+    #[serde(skip_serializing)]
+    events: Option<events::Sink>,
+    // Whether to only report how many pieces would be written and how big they would be, which is left out of the
+    // settings so a dry run has the same fingerprint as the run it previews
+    #[serde(skip_serializing)]
+    dry_run: bool,
+    // Whether to only print the rectangle and cut differences of each piece as JSON, saving nothing
+    #[serde(skip_serializing)]
+    coords_only: bool,
+    // Whether to only save a copy of each input with its cuts drawn on it
+    #[serde(skip_serializing)]
+    preview: bool,
+    // Whether to only record where each input would be cut as slices, beside it and in a copy of it if it's a PNG
+    #[serde(skip_serializing)]
+    export_slices: bool,
+    // How much to print, and whether as text or JSON
+    #[serde(skip_serializing)]
+    log: logging::Logging,
+    // How many inputs may be processed at once, which doesn't change the results
+    #[serde(skip_serializing)]
+    jobs: usize,
+    // Whether to stop the run at the first input that fails rather than reporting the failures at the end
+    #[serde(skip_serializing)]
+    fail_fast: bool,
+    // Whether to skip locking the output directories against other runs
+    #[serde(skip_serializing)]
+    no_lock: bool,
+    // Where to write each input's pieces, archive or zip instead of next to it, which doesn't change the results
+    #[serde(skip_serializing)]
+    output_dir: Option<PathBuf>,
+    // Where to make a new directory for the run, named after when it started, to write the outputs into along with
+    // the run's settings, report and log
+    #[serde(skip_serializing)]
+    run_dir: Option<PathBuf>,
+    // Whether to only cut the inputs that changed since they were last cut, or whose settings or outputs did, and
+    // whether to cut them all anyway while still remembering them
+    #[serde(skip_serializing)]
+    incremental: bool,
+    #[serde(skip_serializing)]
+    force: bool,
+    // A directory to keep cutting the images that arrive in, instead of cutting the inputs given
+    #[serde(skip_serializing)]
+    watch: Option<PathBuf>,
+    // What to write to stdout instead of saving any files, which doesn't change the results either
+    #[serde(skip_serializing)]
+    stdout: Option<stream::Stdout>,
+    // The directory to add every input's pieces to, numbered on from the pieces added to it by earlier runs, rather
+    // than writing a directory for each input
+    #[serde(skip_serializing)]
+    append: Option<PathBuf>,
+    // The percentages of their size to save each piece at, where those below 100 go into a directory beside the
+    // input's named after the percentage
+    ladder: Vec<u32>,
+    // The units of the rectangles in manifests
+    units: manifest::Units,
+    // The steps to run on each piece before it's saved
+    post_process: postprocess::PostProcess,
+    // Whether to find the cuts in PNGs from a small copy streamed from the file and then decode each piece on its own,
+    // rather than decoding the whole image
+    low_memory: bool,
+    // Whether to save pieces with up to 256 colours as paletted PNGs
+    indexed: bool,
+    // Whether to find the cuts in layered documents flattened and save each layer of each piece beside it
+    layers: bool,
+    // The resolution to render PDF pages at, and whether to take each page's biggest image instead where it has one
+    pdf_dpi: u32,
+    pdf_images: bool,
+    // Whether to cut PNGs with slices stored in them at their slices instead of detecting where to cut
+    slices: bool,
+    // Directories of other versions of the inputs, where the image with the same name as each input is cut into the
+    // same pieces
+    paired: Vec<PathBuf>,
+    // The format to save pieces in
+    format: cbz::Encoding,
+    // Whether to save each input's pieces in the input's own format instead, at its own quality if it's a JPEG, where
+    // the format can be written
+    same_format: bool,
+    // The quality to save JPEGs at
+    jpeg_quality: u8,
+    // Whether to find the headers and footers repeated across pages of the same size and crop them off before cutting
+    crop_furniture: bool,
+    // The furniture found on each input
+    #[serde(skip_serializing)]
+    furniture: BTreeMap<String, furniture::Furniture>,
+    // Cut each image into a grid of pieces this physical size instead of at its gaps
+    piece_size: Option<physical::PieceSize>,
+    // Cut each image into the rectangles of a plan saved from another image, instead of at its gaps
+    #[serde(skip_serializing)]
+    apply_plan: Option<plan::Plan>,
+    // Where to save the rectangles the input was cut into as a plan for other images
+    #[serde(skip_serializing)]
+    save_plan: Option<PathBuf>,
+    // The resolution to assume for images without one in their metadata
+    dpi: Option<f64>,
+    // What to do with images too small to make any pieces
+    too_small: TooSmall,
+    // Whether to cut every frame of an animated GIF where the average of them all would be cut, rather than cutting
+    // each on its own
+    consistent_cuts: bool,
+    // Whether to read the barcodes and QR codes in each image and anchor its pieces to them, for `{code}` in names and
+    // the manifest
+    barcodes: bool,
+    // Whether to find the cuts in images with a wide gamut colour profile after converting them to sRGB, so
+    // differences are measured the same whatever space an image was saved in
+    color_management: bool,
+    // Where to copy images that couldn't be decoded
+    quarantine: Option<PathBuf>,
+    // The template pieces are named from, without their extension
+    name_template: String,
+    // Whether to put the pieces in a directory for each row and column of the page instead of all together
+    nested: bool,
+    // How many pieces, rows and columns each image is expected to make, to flag the ones that don't
+    expect_pieces: Option<usize>,
+    expect_rows: Option<usize>,
+    expect_columns: Option<usize>
+}
 
-    // Divide by number of pixels and then by the number of channels
-    value / old.len() as f64 / 3.0
+impl Default for OutputOptions {
+    fn default() -> OutputOptions {
+        OutputOptions {
+            cbz: false,
+            epub: None,
+            sprites: None,
+            montage: None,
+            montage_duration: 2.0,
+            summary: false,
+            dedupe: false,
+            dedupe_distance: None,
+            post_command: None,
+            post_jobs: rayon::current_num_threads(),
+            events: None,
+            dry_run: false,
+            coords_only: false,
+            preview: false,
+            export_slices: false,
+            log: logging::Logging::default(),
+            fail_fast: false,
+            jobs: 1,
+            no_lock: false,
+            output_dir: None,
+            run_dir: None,
+            incremental: false,
+            force: false,
+            watch: None,
+            stdout: None,
+            append: None,
+            ladder: Vec::new(),
+            units: manifest::Units::Pixels,
+            post_process: postprocess::PostProcess::default(),
+            low_memory: false,
+            indexed: false,
+            layers: false,
+            pdf_dpi: 300,
+            pdf_images: false,
+            slices: false,
+            paired: Vec::new(),
+            format: cbz::Encoding::Png,
+            same_format: false,
+            jpeg_quality: DEFAULT_JPEG_QUALITY,
+            crop_furniture: false,
+            furniture: BTreeMap::new(),
+            piece_size: None,
+            apply_plan: None,
+            save_plan: None,
+            dpi: None,
+            too_small: TooSmall::Warn,
+            consistent_cuts: false,
+            barcodes: false,
+            color_management: true,
+            quarantine: None,
+            name_template: "{index}".to_string(),
+            nested: false,
+            expect_pieces: None,
+            expect_rows: None,
+            expect_columns: None
+        }
+    }
 }
 
-fn difference_horizontal(image: &image::RgbImage) -> (u32, f64) {
-    // Create vecs for the old and new lines of pixels
-    let mut old: Line = Vec::with_capacity(image.width() as usize);
-    let mut new: Line = vec![image::Rgb {data: [0; 3]}; image.width() as usize];
-    let mut values = Vec::with_capacity(image.height() as usize - 1);
+// Get a piece ready to save, dropping the alpha channel if the source had none
+fn output_image(image: &image::RgbaImage, has_alpha: bool) -> image::DynamicImage {
+    let image = image::DynamicImage::ImageRgba8(image.clone());
+    if has_alpha {
+        image
+    } else {
+        image::DynamicImage::ImageRgb8(image.to_rgb())
+    }
+}
 
-    // Loop through the pixels T -> B, L -> R
-    for (x, _, pixel) in image.enumerate_pixels() {
-        // set a pixel in the line
-        new[x as usize] = *pixel;
+// Encode a piece as a PNG page in memory
+fn encode_page(image: &image::DynamicImage) -> cbz::Page {
+    let (width, height) = image.dimensions();
+    let mut data = Vec::new();
+    image.save(&mut data, image::ImageFormat::PNG).unwrap();
+    cbz::Page { width, height, encoding: cbz::Encoding::Png, data }
+}
 
-        if x == image.width() - 1 {
-            // If the pixel is at the end of the line and the old line of pixels is not empty, add the difference
-            if !old.is_empty() {
-                values.push(average_difference(&old, &new));
+// Blend a transparent piece onto white, since JPEGs can't keep transparency and dropping the alpha would show
+// whatever colour the transparent pixels happen to have
+fn flatten(image: &image::RgbaImage) -> image::RgbImage {
+    image::ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+        let pixel = image.get_pixel(x, y);
+        let alpha = pixel[3] as u32;
+        let blend = |channel: u8| ((channel as u32 * alpha + 255 * (255 - alpha) + 127) / 255) as u8;
+        image::Rgb { data: [blend(pixel[0]), blend(pixel[1]), blend(pixel[2])] }
+    })
+}
+
+// How much of an input to read to find its format and JPEG quality, which are in its first few segments
+const SOURCE_HEADER_LENGTH: u64 = 64 * 1024;
+
+// Read the start of an input, to find the format and quality to save its pieces in
+fn read_source_header(path: &Path) -> Option<Vec<u8>> {
+    let mut data = Vec::new();
+    File::open(path).and_then(|file| file.take(SOURCE_HEADER_LENGTH).read_to_end(&mut data)).ok()?;
+    Some(data)
+}
+
+// Get the encoding to save an input's pieces in and the quality of any JPEGs, from the start of the input if its
+// pieces keep its format, falling back to PNGs for formats that can't be written
+fn piece_encoding(source: Option<&[u8]>, output: &OutputOptions) -> (cbz::Encoding, u8) {
+    match source {
+        Some(data) if output.same_format => match formats::source_encoding(data) {
+            Some(cbz::Encoding::Jpeg) => (cbz::Encoding::Jpeg, formats::jpeg_quality(data).unwrap_or(output.jpeg_quality)),
+            Some(encoding) => (encoding, output.jpeg_quality),
+            None => (cbz::Encoding::Png, output.jpeg_quality)
+        },
+        _ => (output.format, output.jpeg_quality)
+    }
+}
+
+// Encode a piece in the given encoding, where PNGs are paletted if that's wanted and it has few enough colours.
+// A failure fails the input rather than quietly saving it in another format.
+fn encode_piece(image: &image::RgbaImage, has_alpha: bool, (encoding, quality): (cbz::Encoding, u8), output: &OutputOptions) -> Result<cbz::Page, String> {
+    let (width, height) = image.dimensions();
+    let page = match encoding {
+        cbz::Encoding::Jpeg => {
+            let rgb = if has_alpha { flatten(image) } else { image::DynamicImage::ImageRgba8(image.clone()).to_rgb() };
+            let mut data = Vec::new();
+            image::jpeg::JPEGEncoder::new_with_quality(&mut data, quality)
+                .encode(&rgb, width, height, image::ColorType::RGB(8))
+                .map_err(|error| error.to_string())?;
+            cbz::Page { width, height, encoding, data }
+        },
+        cbz::Encoding::Bmp => cbz::Page { width, height, encoding, data: formats::encode_bmp(image, has_alpha) },
+        cbz::Encoding::Tiff => cbz::Page { width, height, encoding, data: formats::encode_tiff(image, has_alpha) },
+        cbz::Encoding::Webp => {
+            let png = encode_page(&output_image(image, has_alpha)).data;
+            let data = formats::encode_webp(&png, quality).map_err(|error| format!("couldn't save a WebP: {}", error))?;
+            cbz::Page { width, height, encoding, data }
+        },
+        cbz::Encoding::Png => {
+            if output.indexed {
+                if let Some(data) = indexed::encode_indexed(image) {
+                    return Ok(cbz::Page { width, height, encoding, data });
+                }
             }
-            // Copy the new line to the old line
-            old = new.clone();
+            encode_page(&output_image(image, has_alpha))
+        }
+    };
+    Ok(page)
+}
+
+// Embed the colour profile of the input a piece came from in it, if the input had one and the piece's format can hold
+// one, so pieces from inputs in different colour spaces aren't shown alike
+fn tag_profile(page: cbz::Page, profile: Option<&[u8]>) -> cbz::Page {
+    match profile.and_then(|profile| icc::with_profile(&page.data, profile)) {
+        Some(data) => cbz::Page { data, ..page },
+        None => page
+    }
+}
+
+// Take each piece's image again from the image it was cut from with a margin of the pixels around it, cut short at
+// the image's edges. The piece keeps the rectangle it was cut at, so the pieces still don't overlap in the manifest.
+fn bleed_from(source: &mut image::RgbaImage, guillotined: &mut Guillotined, margin: u32) {
+    let (width, height) = source.dimensions();
+    for piece in &mut guillotined.pieces {
+        let (left, top) = (piece.x.saturating_sub(margin), piece.y.saturating_sub(margin));
+        let right = piece.x.saturating_add(piece.width).saturating_add(margin).min(width);
+        let bottom = piece.y.saturating_add(piece.height).saturating_add(margin).min(height);
+        piece.image = source.sub_image(left, top, right - left, bottom - top).to_image();
+    }
+}
+
+// Autoguillotine a page, with a margin of the pixels around each piece if the bleed is taken from the image
+fn cut_page(name: &str, image: image::DynamicImage, config: &GuillotineConfig, output: &OutputOptions) -> Result<(Guillotined, bool), Error> {
+    let margin = match output.post_process.image_bleed() {
+        Some(margin) => margin,
+        None => return cut_source(name, image, config, output)
+    };
+    let mut source = image.to_rgba();
+    let (mut guillotined, has_alpha) = cut_source(name, image, config, output)?;
+    bleed_from(&mut source, &mut guillotined, margin);
+    Ok((guillotined, has_alpha))
+}
+
+// Autoguillotine a page, finding the cuts in sRGB if it has a wide gamut colour profile, or cut it into pieces of a
+// physical size if there is one. A page too small to make any pieces fails if that's the policy for them.
+fn cut_source(name: &str, image: image::DynamicImage, config: &GuillotineConfig, output: &OutputOptions) -> Result<(Guillotined, bool), Error> {
+    let (width, height) = image.dimensions();
+    if !cuttable(width, height) {
+        return Err(Error::Dimensions(width, height));
+    }
+    let config = &config.for_input(name).map_err(Error::Settings)?;
+    if let Some(size) = output.piece_size {
+        return Ok(cut_physical(name, image, size, output));
+    }
+    if let Some(ref plan) = output.apply_plan {
+        return Ok(cut_planned(image, plan));
+    }
+    if let Some(cut) = cut_too_small(name, &image, config, output) {
+        return cut;
+    }
+
+    // Find the cuts in sRGB, but keep the pieces in the image's own colours
+    let transform = match srgb_transform(Path::new(name), output) {
+        Some(transform) => transform,
+        None => return Ok(cut_furniture(name, image, config, output))
+    };
+    let mut original = image.to_rgba();
+    let (mut guillotined, has_alpha) = cut_furniture(name, transform.apply(image), config, output);
+    for piece in &mut guillotined.pieces {
+        if piece.image.dimensions() == (piece.width, piece.height) {
+            piece.image = original.sub_image(piece.x, piece.y, piece.width, piece.height).to_image();
         }
     }
+    guillotined.log.insert(0, "Colour profile: converted to sRGB to find the cuts".to_string());
+    Ok((guillotined, has_alpha))
+}
 
-    // Find the maximum difference and its place to cut
-    values.iter()
+// The conversion to sRGB of an input's embedded colour profile, if it has a wide gamut one and colours are managed
+fn srgb_transform(path: &Path, output: &OutputOptions) -> Option<icc::Transform> {
+    if !output.color_management {
+        return None;
+    }
+    read_source_header(path).and_then(|data| icc::embedded_profile(&data)).and_then(|profile| icc::Transform::to_srgb(&profile))
+}
+
+// Whether an image file is too small to make any pieces with the config got for it, going by its header, so it's
+// decoded whole for the policy for such images rather than streamed
+fn too_small_file(path: &Path, config: &GuillotineConfig) -> bool {
+    check::image_dimensions(path).map(|(width, height)| config.too_small(width, height)).unwrap_or(false)
+}
+
+// Deal with an image too small to make any pieces with the config got for it as the policy for them says, keeping it
+// whole, making no pieces from it with a warning or failing it, or get nothing if it's big enough to cut
+fn cut_too_small(name: &str, image: &image::DynamicImage, config: &GuillotineConfig, output: &OutputOptions) -> Option<Result<(Guillotined, bool), Error>> {
+    let (width, height) = image.dimensions();
+    if !config.too_small(width, height) {
+        return None;
+    }
+    let has_alpha = matches!(image.color(), image::ColorType::RGBA(_) | image::ColorType::GrayA(_));
+    let warning = |what: &str| vec![format!("Warning: {} is {}x{}, too small to make any pieces, so {}", name, width, height, what)];
+    Some(match output.too_small {
+        TooSmall::Copy => Ok((Guillotined::whole(image.to_rgba(), warning("it was kept whole")), has_alpha)),
+        TooSmall::Warn => Ok((Guillotined { pieces: Vec::new(), log: warning("it made none") }, has_alpha)),
+        TooSmall::Error => Err(Error::TooSmall(width, height))
+    })
+}
+
+// Autoguillotine a page with any furniture found on it cropped off first, moving the pieces back to where they are on
+// the whole page
+fn cut_furniture(name: &str, mut image: image::DynamicImage, config: &GuillotineConfig, output: &OutputOptions) -> (Guillotined, bool) {
+    let furniture = output.furniture.get(name).cloned().unwrap_or_default();
+    if furniture.top == 0 && furniture.bottom == 0 {
+        return cut_image(name, image, config);
+    }
+
+    let (width, height) = image.dimensions();
+    let cropped = image.crop(0, furniture.top, width, height - furniture.top - furniture.bottom);
+    let (mut guillotined, has_alpha) = cut_image(name, cropped, config);
+    for piece in &mut guillotined.pieces {
+        piece.y += furniture.top;
+    }
+    guillotined.log.insert(0, format!("Furniture: cropped {} rows off the top and {} off the bottom", furniture.top, furniture.bottom));
+    (guillotined, has_alpha)
+}
+
+// Cut an image into a grid of pieces of a physical size, at the resolution in its metadata or the one given
+fn cut_physical(name: &str, image: image::DynamicImage, size: physical::PieceSize, output: &OutputOptions) -> (Guillotined, bool) {
+    let has_alpha = matches!(image.color(), image::ColorType::RGBA(_) | image::ColorType::GrayA(_));
+    let mut image = image.to_rgba();
+    let (width, height) = image.dimensions();
+
+    let dpi = match physical::read_dpi(Path::new(name)).or(output.dpi) {
+        Some(dpi) => dpi,
+        None => {
+            let log = vec![format!("Warning: {} has no resolution in its metadata and no --dpi was given, so it wasn't cut", name)];
+            return (Guillotined::whole(image, log), has_alpha);
+        }
+    };
+    let (piece_width, piece_height) = size.pixels(dpi);
+    let pieces: Vec<Piece> = physical::grid(width, height, piece_width, piece_height).into_iter()
         .enumerate()
-        .ord_subset_max_by_key(|&(_, value)| value)
-        .map(|(x, y)| (x as u32 + 1, *y))
-        .unwrap()
+        .map(|(index, (x, y, width, height))| {
+            Piece { path: vec![index as u32], ..Piece::new(image.sub_image(x, y, width, height).to_image(), x, y) }
+        })
+        .collect();
+    let log = vec![format!("Physical size: {}x{} pixels at {:.0} DPI, {} pieces", piece_width, piece_height, dpi, pieces.len())];
+    (Guillotined { pieces, log }, has_alpha)
+}
+
+// Cut an image into the rectangles of a plan, scaled to its size, without measuring it
+fn cut_planned(image: image::DynamicImage, plan: &plan::Plan) -> (Guillotined, bool) {
+    let has_alpha = matches!(image.color(), image::ColorType::RGBA(_) | image::ColorType::GrayA(_));
+    let mut image = image.to_rgba();
+    let (width, height) = image.dimensions();
+    let pieces: Vec<Piece> = plan.rectangles(width, height).into_iter()
+        .enumerate()
+        .map(|(index, (x, y, width, height))| {
+            Piece { path: vec![index as u32], ..Piece::new(image.sub_image(x, y, width, height).to_image(), x, y) }
+        })
+        .collect();
+    let log = vec![format!("Plan: {} pieces of the plan's {}", pieces.len(), plan.pieces.len())];
+    (Guillotined { pieces, log }, has_alpha)
+}
+
+// Estimate how many bytes a piece will take up once encoded as a PNG, assuming it deflates to about half its size
+fn estimated_size(width: u32, height: u32, has_alpha: bool) -> u64 {
+    // The signature and the header, end and a typical number of data chunks
+    const PNG_OVERHEAD: u64 = 8 + 25 + 12 + 12 * 4;
+    let channels = if has_alpha { 4 } else { 3 };
+    PNG_OVERHEAD + width as u64 * height as u64 * channels / 2
+}
+
+// Format a number of bytes in the largest unit that keeps it above one
+fn format_bytes(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < units.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, units[unit])
+}
+
+// Describe how an image's pieces differ from what was expected, if they do, where the rows and columns are the
+// distinct places pieces start at down and across the image
+fn check_expectations(name: &str, pieces: &[Piece], output: &OutputOptions) -> Option<String> {
+    let distinct = |starts: Vec<u32>| {
+        let mut starts = starts;
+        starts.sort();
+        starts.dedup();
+        starts.len()
+    };
+    let results = [
+        ("pieces", output.expect_pieces, pieces.len()),
+        ("rows", output.expect_rows, distinct(pieces.iter().map(|piece| piece.y).collect())),
+        ("columns", output.expect_columns, distinct(pieces.iter().map(|piece| piece.x).collect()))
+    ];
+
+    let mismatches: Vec<String> = results.iter()
+        .filter_map(|&(what, expected, actual)| match expected {
+            Some(expected) if expected != actual => Some(format!("expected {} {} but got {}", expected, what, actual)),
+            _ => None
+        })
+        .collect();
+    if mismatches.is_empty() {
+        None
+    } else {
+        Some(format!("Mismatch: {}: {}", name, mismatches.join(", ")))
+    }
+}
+
+// Guillotine an image without saving anything, returning how many pieces it makes and their estimated size
+fn dry_run(name: &str, image: image::DynamicImage, config: &GuillotineConfig, output: &OutputOptions, log: &mut Vec<String>) -> Result<(usize, u64, bool), Error> {
+    let (mut images, has_alpha) = cut_page(name, image, config, output)?;
+    let mismatch = check_expectations(name, &images.pieces, output);
+    images.pieces = postprocess::post_process(name, images.pieces, &output.post_process, &mut images.log);
+    let bytes = images.pieces.iter()
+        .map(|piece| estimated_size(piece.image.width(), piece.image.height(), has_alpha))
+        .sum();
+    log.append(&mut images.log);
+    log.push(format!("{}: {} pieces, ~{}", name, images.pieces.len(), format_bytes(bytes)));
+    let mismatched = mismatch.is_some();
+    log.extend(mismatch);
+    Ok((images.pieces.len(), bytes, mismatched))
+}
+
+// What came of processing an input, kept until every input before it has been reported so the output is in order
+#[derive(Default)]
+struct Processed {
+    log: Vec<String>,
+    // The pieces to go into the EPUB
+    pages: Vec<cbz::Page>,
+    // The named pieces to go into the sprite sheet
+    sprites: Vec<(String, image::RgbaImage)>,
+    // The pieces written to directories
+    written: Vec<PathBuf>,
+    // The messages about them to send, if there's somewhere to send them
+    events: Vec<events::PieceEvent>,
+    // The pieces to show in the montage, which leaves out copies cut from paired images
+    montage: Vec<PathBuf>,
+    // The pieces and manifests to write to stdout, named by where they'd be in a zip
+    streamed: Vec<(String, Vec<u8>)>,
+    // The pieces to name and save once every input before them has been
+    collected: Vec<Collected>,
+    // The number of pieces made, and their estimated size for dry runs
+    pieces: usize,
+    bytes: u64,
+    // The number of images that failed, and what went wrong with each
+    failed: usize,
+    errors: Vec<String>,
+    // The number of images that didn't make the expected pieces
+    mismatched: usize
+}
+
+impl Processed {
+    // Record that an input, or an image in it, failed
+    fn fail(&mut self, name: &str, error: Error) {
+        let message = format!("{}: {}", name, error);
+        self.log.push(format!("Error: {}", message));
+        self.errors.push(message);
+        self.failed += 1;
+    }
+}
+
+// Estimate the pieces an input would make, without saving anything
+fn dry_run_file(arg: &str, config: &GuillotineConfig, output: &OutputOptions) -> Processed {
+    for_each_input(arg, |name, image, processed| {
+        let (pieces, bytes, mismatched) = match dry_run(name, image, config, output, &mut processed.log) {
+            Ok(estimated) => estimated,
+            Err(error) => {
+                processed.fail(name, error);
+                return;
+            }
+        };
+        processed.pieces += pieces;
+        processed.bytes += bytes;
+        if mismatched {
+            processed.mismatched += 1;
+        }
+    })
+}
+
+// A piece's rectangle in the source image and the differences of the cuts around it, for `--coords-only`
+#[derive(Serialize)]
+struct Coordinates<'a> {
+    source: &'a str,
+    id: String,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    scores: [Option<f64>; 4]
+}
+
+// Cut an input without saving anything, logging each piece's rectangle and the differences of its cuts as a line of
+// JSON
+fn coordinates_file(arg: &str, config: &GuillotineConfig, output: &OutputOptions) -> Processed {
+    for_each_input(arg, |name, image, processed| {
+        let (guillotined, _) = match cut_page(name, image, config, output) {
+            Ok(cut) => cut,
+            Err(error) => {
+                processed.fail(name, error);
+                return;
+            }
+        };
+        for piece in &guillotined.pieces {
+            let coordinates = Coordinates {
+                source: name, id: piece.id(), x: piece.x, y: piece.y, width: piece.width, height: piece.height, scores: piece.scores
+            };
+            processed.log.push(serde_json::to_string(&coordinates).unwrap());
+        }
+        processed.pieces += guillotined.pieces.len();
+    })
+}
+
+// Get the path of a file made from an image in an input, named after the input with the suffix on the end, and after
+// the image too if it's in a zip, flattening its path into the name
+fn derived_path(arg: &str, name: &str, suffix: &str, output: &OutputOptions) -> PathBuf {
+    let location = output_location(Path::new(arg), output);
+    let stem = location.file_stem().unwrap().to_string_lossy().into_owned();
+    let entry = name[arg.len() ..].trim_start_matches('/');
+    let file_name = if entry.is_empty() {
+        format!("{}{}", stem, suffix)
+    } else {
+        format!("{}-{}{}", stem, Path::new(entry).with_extension("").to_string_lossy().replace(['/', '\\'], "_"), suffix)
+    };
+    location.with_file_name(file_name)
+}
+
+// Save a copy of each image in an input with where it would be cut drawn on it, named after the image with
+// `-preview` on the end
+fn preview_file(arg: &str, config: &GuillotineConfig, output: &OutputOptions) -> Processed {
+    for_each_input(arg, |name, image, processed| {
+        let rgba = image.to_rgba();
+        let (mut guillotined, _) = match cut_page(name, image, config, output) {
+            Ok(cut) => cut,
+            Err(error) => {
+                processed.fail(name, error);
+                return;
+            }
+        };
+        processed.log.append(&mut guillotined.log);
+
+        let path = derived_path(arg, name, "-preview.png", output);
+        let page = encode_page(&image::DynamicImage::ImageRgba8(preview::draw_cuts(&rgba, &guillotined.pieces)));
+        processed.log.push(format!("Saving {}...", path.display()));
+        if let Err(error) = atomic::write(&path, &page.data).map_err(Error::writing(&path)) {
+            processed.fail(name, error);
+        }
+        processed.pieces += guillotined.pieces.len();
+    })
 }
 
-fn difference_vertical(image: &image::RgbImage) -> (u32, f64) {
-    // Create vecs for the old and new lines of pixels
-    let mut old: Line = Vec::with_capacity(image.height() as usize);
-    let mut new: Line = vec![image::Rgb {data: [0; 3]}; image.height() as usize];
-    let mut values = Vec::with_capacity(image.width() as usize - 1);
+// Record where each image in an input would be cut as slices in a JSON file named after the image with `-slices.json`
+// on the end, and for a PNG, in a copy of it with `-sliced` on the end that `--slices` can cut
+fn export_slices_file(arg: &str, config: &GuillotineConfig, output: &OutputOptions) -> Processed {
+    for_each_input(arg, |name, image, processed| {
+        let (mut guillotined, _) = match cut_page(name, image, config, output) {
+            Ok(cut) => cut,
+            Err(error) => {
+                processed.fail(name, error);
+                return;
+            }
+        };
+        processed.log.append(&mut guillotined.log);
+        processed.pieces += guillotined.pieces.len();
 
-    // Loop through the pixels L -> R, T -> B
-    for x in 0 .. image.width() {
-        for y in 0 .. image.height() {
-            // set a pixel in the line
-            new[y as usize] = *image.get_pixel(x, y);
+        let found: Vec<slices::Slice> = guillotined.pieces.iter()
+            .map(|piece| slices::Slice { x: piece.x, y: piece.y, width: piece.width, height: piece.height })
+            .collect();
+        let json = serde_json::to_string(&found).unwrap();
+        let path = derived_path(arg, name, "-slices.json", output);
+        processed.log.push(format!("Saving {}...", path.display()));
+        if let Err(error) = atomic::write(&path, json.as_bytes()).map_err(Error::writing(&path)) {
+            processed.fail(name, error);
+            return;
+        }
 
-            if y == image.height() - 1 {
-                // If the pixel is at the end of the line and the old line of pixels is not empty, add the difference
-                if !old.is_empty() {
-                    values.push(average_difference(&old, &new));
+        // Images in zips aren't copied, as their data isn't kept once they're decoded
+        if name == arg {
+            if let Some(png) = std::fs::read(arg).ok().and_then(|data| slices::with_slices(&data, &json)) {
+                let path = derived_path(arg, name, "-sliced.png", output);
+                processed.log.push(format!("Saving {}...", path.display()));
+                if let Err(error) = atomic::write(&path, &png).map_err(Error::writing(&path)) {
+                    processed.fail(name, error);
                 }
-                // Copy the new line to the old line
-                old = new.clone();
             }
         }
+    })
+}
+
+// An image's pieces in reading order, with the row and column of each and each encoded, kept until they're named
+struct Collected {
+    source: String,
+    source_size: (u32, u32),
+    // What the image's outputs are named after
+    stem: String,
+    pieces: Vec<Piece>,
+    grid: Vec<(usize, usize)>,
+    pages: Vec<cbz::Page>
+}
+
+// Cut each image in an input and keep its encoded pieces, to be named and saved once every input before it has been
+fn collect_file(arg: &str, config: &GuillotineConfig, output: &OutputOptions) -> Processed {
+    for_each_input(arg, |name, image, processed| {
+        let source_size = image.dimensions();
+        let (mut guillotined, has_alpha) = match cut_page(name, image, config, output) {
+            Ok(cut) => cut,
+            Err(error) => {
+                processed.fail(name, error);
+                return;
+            }
+        };
+        processed.log.append(&mut guillotined.log);
+        if let Some(mismatch) = check_expectations(name, &guillotined.pieces, output) {
+            processed.log.push(mismatch);
+            processed.mismatched += 1;
+        }
+        let pieces = postprocess::post_process(name, guillotined.pieces, &output.post_process, &mut processed.log);
+        let (pieces, grid) = reading_order(pieces);
+        processed.pieces += pieces.len();
+
+        let stem = derived_path(arg, name, "", output).file_name().unwrap().to_string_lossy().into_owned();
+        let source = read_source_header(Path::new(name));
+        let encoding = piece_encoding(source.as_deref(), output);
+        let profile = source.as_deref().and_then(icc::embedded_profile);
+        let pages = match pieces.iter().map(|piece| Ok(tag_profile(encode_piece(&piece.image, has_alpha, encoding, output)?, profile.as_deref()))).collect::<Result<Vec<_>, String>>() {
+            Ok(pages) => pages,
+            Err(error) => {
+                processed.fail(name, Error::Write(PathBuf::from(&stem), error));
+                return;
+            }
+        };
+        processed.collected.push(Collected { source: name.to_string(), source_size, stem, pieces, grid, pages });
+    })
+}
+
+// Cut each image in an input and keep its pieces and manifest to write to stdout, in a directory named after the
+// image like the files `derived_path` names
+fn stream_file(arg: &str, config: &GuillotineConfig, output: &OutputOptions) -> Processed {
+    let run = stamp(config, output);
+    let mut processed = collect_file(arg, config, output);
+    for collected in std::mem::take(&mut processed.collected) {
+        let (source_width, source_height) = collected.source_size;
+        let mut manifest = manifest::Manifest::new(collected.source, source_width, source_height, output.units, run.clone());
+        let mut namer = naming::Namer::new(&output.name_template, &collected.stem);
+        let dirs = piece_dirs(&collected.pieces, output);
+        for (i, (piece, page)) in collected.pieces.iter().zip(collected.pages).enumerate() {
+            let file = format!("{}{}.{}", dirs[i], namer.name(i, collected.grid[i], &piece.image), page.encoding.extension());
+            manifest.push(file.clone(), piece, &page.data);
+            processed.streamed.push((format!("{}/{}", collected.stem, file), page.data));
+        }
+        processed.streamed.push((format!("{}/manifest.json", collected.stem), manifest.to_json().into_bytes()));
     }
+    processed
+}
 
-    // Find the maximum difference and its place to cut
-    values.iter()
-        .enumerate()
-        .ord_subset_max_by_key(|&(_, value)| value)
-        .map(|(x, y)| (x as u32 + 1, *y))
-        .unwrap()
+// Save the pieces collected from the inputs into a collection's directory in order, numbering them on from the pieces
+// already in it and skipping images that have already been added, then save the list of its inputs
+fn append_collected(dir: &Path, collected: Vec<Collected>, config: &GuillotineConfig, output: &OutputOptions, processed: &mut Processed) -> Result<(), Error> {
+    let mut collection = collection::Collection::open(dir).map_err(Error::Read)?;
+    let (pieces_before, inputs_before) = (collection.pieces(), collection.inputs());
+    let run = stamp(config, output);
+    for collected in collected {
+        if collection.contains(&collected.source) {
+            processed.log.push(format!("Warning: {} is already in {}, so it was skipped", collected.source, dir.display()));
+            continue;
+        }
+
+        let (source_width, source_height) = collected.source_size;
+        let mut manifest = manifest::Manifest::new(collected.source, source_width, source_height, output.units, run.clone());
+        let mut namer = naming::Namer::new(&output.name_template, &collected.stem);
+        let dirs = piece_dirs(&collected.pieces, output);
+        for (i, (piece, page)) in collected.pieces.iter().zip(collected.pages).enumerate() {
+            let name = namer.name(collection.pieces() + i, collected.grid[i], &piece.image);
+            // Number a name that a piece from an earlier run already has, so it isn't overwritten
+            let mut file = format!("{}{}.{}", dirs[i], name, page.encoding.extension());
+            let mut count = 2;
+            while dir.join(&file).exists() {
+                file = format!("{}{}-{}.{}", dirs[i], name, count, page.encoding.extension());
+                count += 1;
+            }
+
+            let path = dir.join(&file);
+            create_dir_all(path.parent().unwrap()).map_err(Error::writing(&path))?;
+            processed.log.push(format!("Saving {}...", path.display()));
+            atomic::write(&path, &page.data).map_err(Error::writing(&path))?;
+            processed.written.push(path.clone());
+            if output.events.is_some() {
+                processed.events.push(events::PieceEvent::new(&manifest.source, &path, piece));
+            }
+            if output.montage.is_some() {
+                processed.montage.push(path);
+            }
+            manifest.push(file, piece, &page.data);
+        }
+        collection.add(&manifest);
+    }
+
+    let collection_path = dir.join(collection::COLLECTION_FILE);
+    collection.save().map_err(Error::writing(&collection_path))?;
+    processed.log.push(format!(
+        "Appended {} pieces from {} images to {}, which now has {} pieces from {} images",
+        collection.pieces() - pieces_before, collection.inputs() - inputs_before, dir.display(), collection.pieces(), collection.inputs()
+    ));
+    Ok(())
+}
+
+// Decode an input, or each image in it if it's a zip, and pass each image and its name to the function along with
+// what came of the input so far, counting the images that couldn't be decoded
+fn for_each_input<F: FnMut(&str, image::DynamicImage, &mut Processed)>(arg: &str, mut function: F) -> Processed {
+    let path = Path::new(arg);
+    let mut processed = Processed::default();
+    let mut add = |name: &str, image: image::ImageResult<image::DynamicImage>, processed: &mut Processed| match image {
+        Ok(image) => function(name, image, processed),
+        Err(error) => processed.fail(name, Error::Decode(error.to_string()))
+    };
+
+    if archive::is_zip(path) {
+        if let Err(error) = archive::for_each_image(path, |name, image| add(&format!("{}/{}", arg, name), image, &mut processed)) {
+            processed.fail(arg, Error::Read(error.to_string()));
+        }
+    } else {
+        add(arg, stream::open_input(arg), &mut processed);
+    }
+
+    processed
+}
+
+// Guillotine an input and save its pieces, recording what went wrong if it failed
+fn process_file(arg: &str, config: &GuillotineConfig, output: &OutputOptions) -> Processed {
+    let mut processed = Processed::default();
+    if let Err(error) = guillotine_file(arg, config, output, &mut processed) {
+        processed.fail(arg, error);
+    }
+    processed
+}
+
+// Send the messages about the pieces an input was cut into, if there's somewhere to send them
+fn send_events(processed: &mut Processed, output: &OutputOptions) {
+    if let Some(ref sink) = output.events {
+        events::publish(sink, &processed.events, &mut processed.log);
+        processed.events.clear();
+    }
+}
+
+// Find what an input needs that this build can't do, so it fails on its own with the feature it needs named, rather
+// than the whole run failing or the input being cut without it
+fn unsupported_feature(path: &Path, output: &OutputOptions) -> Option<Error> {
+    if output.layers && layers::is_layered(path) && !layers::layers_supported() {
+        Some(Error::UnsupportedFeature("its layers read", "layers"))
+    } else if pdf::is_pdf(path) && !pdf::pdf_supported() {
+        Some(Error::UnsupportedFeature("its pages read", "pdf"))
+    } else if output.name_template.contains("{heading}") && !naming::headings_supported() {
+        Some(Error::UnsupportedFeature("its pieces' headings read for {heading}", "ocr"))
+    } else if output.barcodes && !barcodes::barcodes_supported() {
+        Some(Error::UnsupportedFeature("its barcodes read", "barcodes"))
+    } else {
+        None
+    }
+}
+
+// Guillotine an input and save its pieces, adding what came of it to what was processed so far
+fn guillotine_file(arg: &str, config: &GuillotineConfig, output: &OutputOptions, processed: &mut Processed) -> Result<(), Error> {
+    let path = Path::new(arg);
+    if !archive::is_zip(path) {
+        if let Some(error) = unsupported_feature(path, output) {
+            return Err(error);
+        }
+    }
+
+    // Mirror zips into new zips of pieces
+    if archive::is_zip(path) {
+        let output_path = archive::output_path(&output_location(path, output), archive::is_comic(path, output));
+        *processed = archive::guillotine_zip(path, &output_path, config, output).map_err(|error| Error::Read(error.to_string()))?;
+        processed.log.push(format!("Saving {}...", output_path.display()));
+        return Ok(());
+    }
+
+    // Cut each frame of an animation or page of a PDF into a directory of its own, unless its pieces are going into a
+    // single file, which PDFs' can't yet
+    let single_file = output.cbz || output.epub.is_some() || output.sprites.is_some();
+    if pdf::is_pdf(path) {
+        if single_file {
+            return Err(Error::Read("a PDF's pages can only be cut into directories, not a CBZ, EPUB or sprite sheet".to_string()));
+        }
+        return guillotine_pdf(arg, config, output, processed);
+    }
+    if !single_file {
+        if let Some(frames) = frames::read_frames(path) {
+            return guillotine_frames(arg, &frames, config, output, processed);
+        }
+    }
+
+    // Load and autoguillotine the image, skipping it if it's corrupt, with its settings got once for every step
+    let input_config = &config.for_input(arg).map_err(Error::Settings)?;
+    let mut document_layers = None;
+    let slices = if output.slices { slices::read_slices(path) } else { None };
+    let cut = if let Some(slices) = slices {
+        image::open(path)
+            .map(|image| {
+                let source_size = image.dimensions();
+                (Ok(slices::cut_slices(image, &slices)), source_size)
+            })
+            .map_err(|error| error.to_string())
+    } else if output.layers && layers::is_layered(path) {
+        layers::read_layered(path).map(|layered| {
+            document_layers = Some(layered.layers);
+            let image = image::DynamicImage::ImageRgba8(layered.flattened);
+            let source_size = image.dimensions();
+            (cut_page(arg, image, input_config, output), source_size)
+        })
+    } else if output.low_memory && output.piece_size.is_none() && output.apply_plan.is_none() && output.post_process.image_bleed().is_none() && !input_config.apply_deskew && lowmem::is_streamable(path) && !too_small_file(path, input_config)
+        && output.furniture.get(arg).map_or(true, |furniture| furniture.top == 0 && furniture.bottom == 0) && srgb_transform(path, output).is_none()
+        && check::image_dimensions(path).map_or(false, |(width, height)| cuttable(width, height)) {
+        let source_size = check::image_dimensions(path).unwrap_or((0, 0));
+        lowmem::cut_png(path, arg, input_config).map(|cut| (Ok(cut), source_size))
+    } else {
+        stream::open_input(arg)
+            .map(|image| {
+                let source_size = image.dimensions();
+                (cut_page(arg, image, input_config, output), source_size)
+            })
+            .map_err(|error| error.to_string())
+    };
+    let (cut, source_size) = match cut {
+        Ok(cut) => cut,
+        Err(error) => {
+            if let Ok(data) = std::fs::read(path) {
+                quarantine(output, arg, &data, &mut processed.log);
+            }
+            return Err(Error::Decode(error));
+        }
+    };
+    let (guillotined, has_alpha) = cut?;
+    processed.log = guillotined.log;
+    if let Some(ref plan_path) = output.save_plan {
+        // The cuts as they were made, before any post-processing moves the pieces' edges
+        let (source_width, source_height) = source_size;
+        let rectangles: Vec<(u32, u32, u32, u32)> = guillotined.pieces.iter().map(|piece| (piece.x, piece.y, piece.width, piece.height)).collect();
+        let ordered: Vec<(u32, u32, u32, u32)> = layout::reading_order(&rectangles).into_iter().map(|(index, _, _)| rectangles[index]).collect();
+        plan::Plan::new(source_width, source_height, &ordered).save(plan_path).map_err(Error::writing(plan_path))?;
+        processed.log.push(format!("Plan: saved {} pieces to {}", ordered.len(), plan_path.display()));
+    }
+    if let Some(mismatch) = check_expectations(arg, &guillotined.pieces, output) {
+        processed.log.push(mismatch);
+        processed.mismatched = 1;
+    }
+    let pieces = postprocess::post_process(arg, guillotined.pieces, &output.post_process, &mut processed.log);
+    let (pieces, grid) = reading_order(pieces);
+    processed.pieces = pieces.len();
+    let location = output_location(path, output);
+    let stem = location.file_stem().unwrap().to_string_lossy();
+    // Keep the pieces to go into the sprite sheet after the other inputs' pieces, named after the input and piece
+    if output.sprites.is_some() {
+        let mut namer = naming::Namer::new(&output.name_template, &stem);
+        processed.sprites = pieces.into_iter().enumerate()
+            .map(|(i, piece)| (format!("{}-{}", stem, namer.name(i, grid[i], &piece.image)), piece.image))
+            .collect();
+        return Ok(());
+    }
+
+    let source = read_source_header(path);
+    let encoding = piece_encoding(source.as_deref(), output);
+    let profile = source.as_deref().and_then(icc::embedded_profile);
+    // Take the pieces of 16-bit and greyscale PNGs and TIFFs from the input at its own depth, as long as they're still
+    // just the rectangles they were cut at, and unless saving memory, as that decodes the input a second time
+    let keeps_depth = matches!(encoding.0, cbz::Encoding::Png | cbz::Encoding::Tiff) && !output.indexed && output.post_process.is_empty() && !input_config.apply_deskew
+        && !output.low_memory;
+    let deep = if keeps_depth { depth::read(path) } else { None };
+    // Get the filename without the extension as the dir
+    let dir = location.parent().unwrap().join(location.file_stem().unwrap());
+    let pages: Vec<cbz::Page> = pieces.iter()
+        .map(|piece| {
+            let deep_page = deep.as_ref()
+                .filter(|_| piece.image.dimensions() == (piece.width, piece.height))
+                .and_then(|deep| deep.encode_piece(piece, encoding.0));
+            let page = match deep_page {
+                Some(page) => page,
+                None => encode_piece(&piece.image, has_alpha, encoding, output)?
+            };
+            Ok(tag_profile(page, profile.as_deref()))
+        })
+        .collect::<Result<_, String>>()
+        .map_err(Error::writing(&dir))?;
+
+    if output.cbz || output.epub.is_some() {
+        if output.cbz {
+            // Put the pieces into an archive next to the input
+            let cbz_path = output_location(path, output).with_extension("cbz");
+            let title = path.file_stem().unwrap().to_string_lossy();
+            let source = path.file_name().unwrap().to_string_lossy();
+            processed.log.push(format!("Saving {}...", cbz_path.display()));
+            cbz::write_cbz(&cbz_path, &title, &source, &pages).map_err(Error::writing(&cbz_path))?;
+        }
+
+        // Keep the pieces to go into the book after the other inputs' pieces
+        if output.epub.is_some() {
+            processed.pages = pages;
+        }
+        return Ok(());
+    }
+
+    // Create the dir
+    create_dir_all(&dir).map_err(Error::writing(&dir))?;
+
+    // Save the images, describing where each came from in a manifest beside them
+    let (source_width, source_height) = source_size;
+    let mut manifest = manifest::Manifest::new(arg.to_string(), source_width, source_height, output.units, stamp(config, output));
+    let mut namer = naming::Namer::new(&output.name_template, &stem);
+    let dirs = piece_dirs(&pieces, output);
+    let codes = if output.barcodes { barcodes::anchor(barcodes::read_codes(path), &pieces) } else { vec![None; pieces.len()] };
+    if output.barcodes {
+        processed.log.push(format!("Barcodes: anchored {} of {} pieces", codes.iter().filter(|code| code.is_some()).count(), pieces.len()));
+    }
+    let mut dedupe = if output.dedupe { Some(dedupe::Dedupe::new(output.dedupe_distance)) } else { None };
+    let mut files = Vec::new();
+    let mut duplicates = Vec::new();
+    for (i, (page, piece)) in pages.iter().zip(&pieces).enumerate() {
+        let file = format!("{}{}.{}", dirs[i], namer.name_coded(i, grid[i], &piece.image, codes[i].as_deref()), page.encoding.extension());
+        // Point a duplicate at the piece it's the same as instead of writing it
+        if let Some(original) = dedupe.as_mut().and_then(|dedupe| dedupe.original(&piece.image, &file, &page.data)) {
+            processed.log.push(format!("Duplicate: {} is the same as {}", file, original.file));
+            manifest.push(original.file.clone(), piece, &page.data);
+            let entry = manifest.pieces.last_mut().unwrap();
+            entry.sha256 = original.sha256;
+            entry.duplicate = true;
+            entry.code = codes[i].clone();
+            files.push(original.file);
+            duplicates.push(true);
+            continue;
+        }
+        files.push(file.clone());
+        duplicates.push(false);
+        let path = dir.join(&file);
+        create_dir_all(path.parent().unwrap()).map_err(Error::writing(&path))?;
+        processed.log.push(format!("Saving {}...", path.display()));
+        atomic::write(&path, &page.data).map_err(Error::writing(&path))?;
+        processed.written.push(path.clone());
+        if output.events.is_some() {
+            processed.events.push(events::PieceEvent::new(arg, &path, piece));
+        }
+        if output.montage.is_some() {
+            processed.montage.push(path.clone());
+        }
+        manifest.push(file, piece, &page.data);
+        manifest.pieces.last_mut().unwrap().code = codes[i].clone();
+
+        // Save the piece's layers in a directory named after it
+        if let Some(ref mut layers) = document_layers {
+            let layers_dir = path.with_extension("layers");
+            create_dir_all(&layers_dir).map_err(Error::writing(&layers_dir))?;
+            for (name, layer) in layers::cut_layers(layers, piece) {
+                let layer_path = layers_dir.join(format!("{}.png", name));
+                processed.log.push(format!("Saving {}...", layer_path.display()));
+                atomic::write(&layer_path, &encode_page(&output_image(&layer, true)).data).map_err(Error::writing(&layer_path))?;
+            }
+        }
+    }
+    let manifest_path = dir.join("manifest.json");
+    atomic::write(&manifest_path, manifest.to_json().as_bytes()).map_err(Error::writing(&manifest_path))?;
+    if output.summary {
+        let summary_path = dir.join(summary::SUMMARY_FILE);
+        processed.log.push(format!("Saving {}...", summary_path.display()));
+        let page = encode_page(&image::DynamicImage::ImageRgba8(summary::contact_sheet(&pieces)));
+        atomic::write(&summary_path, &page.data).map_err(Error::writing(&summary_path))?;
+    }
+
+    // Save the pieces again at each smaller size, from the pieces already cut rather than the input again
+    for &percent in output.ladder.iter().filter(|&&percent| percent < 100) {
+        let rung = dir.with_file_name(format!("{}@{}", stem, percent));
+        let mut manifest = manifest::Manifest::new(arg.to_string(), source_width, source_height, output.units, stamp(config, output));
+        for (i, (piece, file)) in pieces.iter().zip(&files).enumerate() {
+            let image = postprocess::scale(&piece.image, percent as f64 / 100.0, output.post_process.filter);
+            let page = encode_piece(&image, has_alpha, encoding, output).map_err(Error::writing(&rung.join(file)))?;
+            let page = tag_profile(page, profile.as_deref());
+            // Point duplicates at the smaller copy of the piece they're the same as, which was written before them
+            if duplicates[i] {
+                let sha256 = manifest.pieces.iter().find(|entry| entry.file == *file && !entry.duplicate).map(|entry| entry.sha256.clone());
+                manifest.push(file.clone(), piece, &page.data);
+                let entry = manifest.pieces.last_mut().unwrap();
+                entry.sha256 = sha256.unwrap_or_default();
+                entry.duplicate = true;
+                entry.code = codes[i].clone();
+                continue;
+            }
+            let path = rung.join(file);
+            create_dir_all(path.parent().unwrap()).map_err(Error::writing(&path))?;
+            processed.log.push(format!("Saving {}...", path.display()));
+            atomic::write(&path, &page.data).map_err(Error::writing(&path))?;
+            if output.events.is_some() {
+                processed.events.push(events::PieceEvent::new(arg, &path, piece));
+            }
+            processed.written.push(path);
+            manifest.push(file.clone(), piece, &page.data);
+            manifest.pieces.last_mut().unwrap().code = codes[i].clone();
+        }
+        let manifest_path = rung.join("manifest.json");
+        atomic::write(&manifest_path, manifest.to_json().as_bytes()).map_err(Error::writing(&manifest_path))?;
+    }
+
+    for paired_dir in &output.paired {
+        save_paired(&paired_dir.join(path.file_name().unwrap()), source_size, &pieces, &files, encoding, output, processed)?;
+    }
+
+    Ok(())
+}
+
+// Guillotine each frame of an animation into a directory of its own inside the input's, named after its number. With
+// consistent cuts, every frame is cut into the pieces found in the average of them all, so each piece's frames line up
+// and can be put back together into an animation.
+fn guillotine_frames(arg: &str, frames: &[image::RgbaImage], config: &GuillotineConfig, output: &OutputOptions, processed: &mut Processed) -> Result<(), Error> {
+    let location = output_location(Path::new(arg), output);
+    let dir = location.parent().unwrap().join(location.file_stem().unwrap());
+    let stem = location.file_stem().unwrap().to_string_lossy();
+    let has_alpha = frames.iter().any(|frame| frame.pixels().any(|pixel| pixel[3] < 255));
+    let source = if output.same_format { read_source_header(Path::new(arg)) } else { None };
+    let encoding = piece_encoding(source.as_deref(), output);
+
+    let reference = if output.consistent_cuts {
+        let (mut guillotined, _) = cut_page(arg, image::DynamicImage::ImageRgba8(frames::average(frames)), config, output)?;
+        processed.log.append(&mut guillotined.log);
+        processed.log.push(format!("Consistent cuts: {} pieces in each of {} frames", guillotined.pieces.len(), frames.len()));
+        Some(guillotined.pieces)
+    } else {
+        None
+    };
+
+    let digits = (frames.len() - 1).to_string().len();
+    for (number, frame) in frames.iter().enumerate() {
+        let frame_name = format!("frame-{:0digits$}", number, digits = digits);
+        let name = format!("{}/{}", arg, frame_name);
+        let shared = SharedFrames { stem: &stem, reference: reference.as_deref(), has_alpha, encoding };
+        guillotine_frame(&name, frame, &dir.join(&frame_name), &shared, config, output, processed)?;
+    }
+    Ok(())
+}
+
+// Guillotine each page of a PDF into a directory of its own inside the input's, named after its number from 1, reading
+// a page at a time so long documents don't have to fit in memory
+fn guillotine_pdf(arg: &str, config: &GuillotineConfig, output: &OutputOptions, processed: &mut Processed) -> Result<(), Error> {
+    let path = Path::new(arg);
+    let location = output_location(path, output);
+    let dir = location.parent().unwrap().join(location.file_stem().unwrap());
+    let stem = location.file_stem().unwrap().to_string_lossy();
+    let encoding = piece_encoding(None, output);
+
+    let count = pdf::page_count(path).map_err(Error::Decode)?;
+    let digits = count.to_string().len();
+    for number in 1 ..= count {
+        let page_name = format!("page-{:0digits$}", number, digits = digits);
+        let page = pdf::read_page(path, number, output.pdf_dpi, output.pdf_images).map_err(Error::Decode)?;
+        let has_alpha = page.pixels().any(|pixel| pixel[3] < 255);
+        let name = format!("{}/{}", arg, page_name);
+        let shared = SharedFrames { stem: &stem, reference: None, has_alpha, encoding };
+        guillotine_frame(&name, &page, &dir.join(&page_name), &shared, config, output, processed)?;
+    }
+    Ok(())
 }
 
-fn guillotine(mut image: image::RgbImage, threshold: f64, min_size: u32) -> Vec<image::RgbImage> {
+// What the frames of an input have in common: the stem their pieces are named from, the pieces each is cut into if they
+// have consistent cuts, whether their pieces keep transparency and what they're encoded as
+struct SharedFrames<'a> {
+    stem: &'a str,
+    reference: Option<&'a [Piece]>,
+    has_alpha: bool,
+    encoding: (cbz::Encoding, u8)
+}
+
+// Cut a frame or page into the reference pieces if there are any, or else the pieces found in it, and save them in a
+// directory of its own with a manifest describing where each came from beside them
+fn guillotine_frame(
+    name: &str, frame: &image::RgbaImage, frame_dir: &Path, shared: &SharedFrames, config: &GuillotineConfig, output: &OutputOptions,
+    processed: &mut Processed
+) -> Result<(), Error> {
+    let pieces = match shared.reference {
+        Some(reference) => {
+            let mut frame = frame.clone();
+            reference.iter()
+                .map(|piece| {
+                    let image = frame.sub_image(piece.x, piece.y, piece.width, piece.height).to_image();
+                    Piece {
+                        subpixel: piece.subpixel, scores: piece.scores, path: piece.path.clone(), parent: piece.parent, nominal: piece.nominal,
+                        ..Piece::new(image, piece.x, piece.y)
+                    }
+                })
+                .collect()
+        },
+        None => {
+            let (mut guillotined, _) = cut_page(name, image::DynamicImage::ImageRgba8(frame.clone()), config, output)?;
+            processed.log.append(&mut guillotined.log);
+            guillotined.pieces
+        }
+    };
+    if let Some(mismatch) = check_expectations(name, &pieces, output) {
+        processed.log.push(mismatch);
+        processed.mismatched += 1;
+    }
+    let pieces = postprocess::post_process(name, pieces, &output.post_process, &mut processed.log);
+    let (pieces, grid) = reading_order(pieces);
+    processed.pieces += pieces.len();
+
+    create_dir_all(frame_dir).map_err(Error::writing(frame_dir))?;
+    let (width, height) = frame.dimensions();
+    let mut manifest = manifest::Manifest::new(name.to_string(), width, height, output.units, stamp(config, output));
+    let mut namer = naming::Namer::new(&output.name_template, shared.stem);
+    let dirs = piece_dirs(&pieces, output);
+    for (i, piece) in pieces.iter().enumerate() {
+        let page = encode_piece(&piece.image, shared.has_alpha, shared.encoding, output).map_err(Error::writing(frame_dir))?;
+        let file = format!("{}{}.{}", dirs[i], namer.name(i, grid[i], &piece.image), page.encoding.extension());
+        let path = frame_dir.join(&file);
+        create_dir_all(path.parent().unwrap()).map_err(Error::writing(&path))?;
+        processed.log.push(format!("Saving {}...", path.display()));
+        atomic::write(&path, &page.data).map_err(Error::writing(&path))?;
+        processed.written.push(path.clone());
+        if output.events.is_some() {
+            processed.events.push(events::PieceEvent::new(&manifest.source, &path, piece));
+        }
+        if output.montage.is_some() {
+            processed.montage.push(path);
+        }
+        manifest.push(file, piece, &page.data);
+    }
+    let manifest_path = frame_dir.join("manifest.json");
+    atomic::write(&manifest_path, manifest.to_json().as_bytes()).map_err(Error::writing(&manifest_path))?;
+    Ok(())
+}
+
+// Cut another version of an input into the same rectangles as the input's pieces, scaled if it's a different size,
+// and save them with the same names in a directory beside it
+fn save_paired(
+    path: &Path, source_size: (u32, u32), pieces: &[Piece], files: &[String], encoding: (cbz::Encoding, u8), output: &OutputOptions,
+    processed: &mut Processed
+) -> Result<(), Error> {
+    // A paired image that can't be decoded doesn't stop the input's own pieces from counting
+    let image = match image::open(path) {
+        Ok(image) => image,
+        Err(error) => {
+            processed.fail(&path.display().to_string(), Error::Decode(error.to_string()));
+            return Ok(());
+        }
+    };
+    let has_alpha = matches!(image.color(), image::ColorType::RGBA(_) | image::ColorType::GrayA(_));
+    let profile = read_source_header(path).and_then(|data| icc::embedded_profile(&data));
+    let mut image = image.to_rgba();
     let (width, height) = image.dimensions();
+    let (source_width, source_height) = source_size;
+    let scale = |value: u32, size: u32, source_size: u32| (value as u64 * size as u64 / source_size.max(1) as u64) as u32;
+
+    let dir = path.with_extension("");
+    for (piece, file) in pieces.iter().zip(files) {
+        let x = scale(piece.x, width, source_width);
+        let y = scale(piece.y, height, source_height);
+        let piece_width = (scale(piece.x + piece.width, width, source_width) - x).max(1).min(width - x);
+        let piece_height = (scale(piece.y + piece.height, height, source_height) - y).max(1).min(height - y);
+        let piece_path = dir.join(file);
+        let page = encode_piece(&image.sub_image(x, y, piece_width, piece_height).to_image(), has_alpha, encoding, output)
+            .map_err(Error::writing(&piece_path))?;
+        let page = tag_profile(page, profile.as_deref());
+
+        create_dir_all(piece_path.parent().unwrap()).map_err(Error::writing(&piece_path))?;
+        processed.log.push(format!("Saving {}...", piece_path.display()));
+        atomic::write(&piece_path, &page.data).map_err(Error::writing(&piece_path))?;
+        if output.events.is_some() {
+            processed.events.push(events::PieceEvent::new(&path.display().to_string(), &piece_path, piece));
+        }
+        processed.written.push(piece_path);
+    }
+    Ok(())
+}
+
+// Whether an input's outputs were made since it last changed, going by its manifest, or its new zip for a zip
+fn up_to_date(arg: &str, output: &OutputOptions) -> bool {
+    let path = Path::new(arg);
+    let location = output_location(path, output);
+    let made = if archive::is_zip(path) {
+        archive::output_path(&location, archive::is_comic(path, output))
+    } else {
+        location.parent().unwrap().join(location.file_stem().unwrap()).join("manifest.json")
+    };
+    let modified = |path: &Path| path.metadata().and_then(|metadata| metadata.modified()).ok();
+    match (modified(path), modified(&made)) {
+        (Some(changed), Some(made)) => made >= changed,
+        _ => false
+    }
+}
+
+// Get where an input's outputs are named after and written next to, which is the input itself unless there's an
+// output directory to write them into
+fn output_location(path: &Path, output: &OutputOptions) -> PathBuf {
+    // Name what's made from stdin as if it were a file called `stdin`
+    if path == Path::new(stream::STDIN) {
+        return output_location(Path::new(stream::STDIN_NAME), output);
+    }
+    match output.output_dir {
+        Some(ref dir) => dir.join(path.file_name().unwrap()),
+        None => path.to_path_buf()
+    }
+}
+
+// Put pieces in reading order by where they were in the source image, top to bottom and then left to right, with the
+// row and column of each
+fn reading_order(pieces: Vec<Piece>) -> (Vec<Piece>, Vec<(usize, usize)>) {
+    let order = layout::reading_order(&pieces.iter().map(|piece| (piece.x, piece.y, piece.width, piece.height)).collect::<Vec<_>>());
+    let mut pieces: Vec<Option<Piece>> = pieces.into_iter().map(Some).collect();
+    order.into_iter()
+        .map(|(index, row, column)| (pieces[index].take().unwrap(), (row, column)))
+        .unzip()
+}
+
+// Get the directory each piece goes in relative to its image's, with a slash after each, which is empty unless the
+// pieces are nested by rows and columns
+fn piece_dirs(pieces: &[Piece], output: &OutputOptions) -> Vec<String> {
+    if output.nested {
+        layout::nested_dirs(&pieces.iter().map(|piece| (piece.x, piece.y, piece.width, piece.height)).collect::<Vec<_>>())
+    } else {
+        vec![String::new(); pieces.len()]
+    }
+}
+
+// Copy an image that couldn't be decoded into the quarantine directory, if there is one, flattening its path into
+// its name so images from different places don't collide
+fn quarantine(output: &OutputOptions, name: &str, data: &[u8], log: &mut Vec<String>) {
+    if let Some(ref dir) = output.quarantine {
+        let path = dir.join(name.trim_start_matches('/').replace(['/', '\\'], "_"));
+        let copied = create_dir_all(dir).and_then(|_| atomic::write(&path, data));
+        match copied {
+            Ok(()) => log.push(format!("Quarantined {}", path.display())),
+            Err(error) => log.push(format!("Warning: couldn't quarantine {}: {}", name, error))
+        }
+    }
+}
+
+// Get the message a panic was started with
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    match (panic.downcast_ref::<&str>(), panic.downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (_, Some(message)) => message.clone(),
+        _ => "unknown panic".to_string()
+    }
+}
+
+// Process every input, up to `jobs` at once, returning what came of each in the order they were given. An input that
+// panics fails without taking the others down with it. When failing fast, no more inputs are started once one fails,
+// and the run ends at the first that failed, printing what came of it.
+fn process_files<F: Fn(&str) -> Processed + Sync>(files: &[String], output: &OutputOptions, process: F) -> Vec<Processed> {
+    let pool = rayon::ThreadPool::new(rayon::Configuration::new().num_threads(output.jobs)).unwrap();
+    let progress = logging::Progress::new(files.len(), output.log.shows_progress());
+    let run = |arg: &String| {
+        let processed = catch_unwind(AssertUnwindSafe(|| process(arg))).unwrap_or_else(|panic| {
+            let mut processed = Processed::default();
+            processed.fail(arg, Error::Panic(panic_message(&*panic)));
+            processed
+        });
+        progress.advance(arg);
+        processed
+    };
+    if !output.fail_fast {
+        let processed = pool.install(|| files.par_iter().map(run).collect());
+        progress.finish();
+        return processed;
+    }
+
+    // Inputs that haven't started once one has failed are skipped
+    let stopped = AtomicBool::new(false);
+    let processed: Vec<Option<Processed>> = pool.install(|| {
+        files.par_iter()
+            .map(|arg| {
+                if stopped.load(Ordering::SeqCst) {
+                    return None;
+                }
+                let processed = run(arg);
+                if processed.failed > 0 {
+                    stopped.store(true, Ordering::SeqCst);
+                }
+                Some(processed)
+            })
+            .collect()
+    });
+    progress.finish();
+    if let Some((file, failed)) = files.iter().zip(&processed).filter_map(|(file, processed)| processed.as_ref().map(|processed| (file, processed))).find(|&(_, processed)| processed.failed > 0) {
+        output.log.print_log(file, &failed.log, |_| true, false);
+        exit(1);
+    }
+    processed.into_iter().flatten().collect()
+}
+
+// The quality pieces are saved at as JPEGs when none is given
+const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+// What `--help` prints
+const USAGE: &str = "\
+Usage: autoguillotine [options] <images, zips or CBZs, or - for stdin>...
+       autoguillotine check [options] <images or zips>...
+       autoguillotine analyze --ground-truth <json> [options] <images>...
+       autoguillotine diff <old manifest or zip> <new manifest or zip>
+       autoguillotine verify <manifests>...
+       autoguillotine pack [--size WxH] [--margin N] [--spacing N] [--output DIR] <manifests or dirs>...
+
+Cutting:
+  --strategy S            cut at the biggest difference, the centre of the widest gutter, or along the line
+                          crossing the least edge, where the threshold is how many percent below the average edge
+                          strength it has to be (difference, gutter or edge)
+  --threshold N           the minimum difference between two lines to cut between them (default 30)
+  --threshold-decay N     multiply the threshold by N for each level of cuts a region is below, so panels can be
+                          split by weaker separators than the page's gutters (default 1)
+  --auto-threshold        choose the threshold for each image from its differences
+  --min-size N            the minimum width and height of a piece, which cuts leave on both sides (default 100)
+  --min-area N            the minimum area of a piece, instead of its width and height
+  --keep-small P          cut off regions too small to be pieces and keep them or drop them with a note in the log,
+                          or leave them joined to the region beside them as by default, keeping images too small
+                          for any pieces whole (keep, drop or merge)
+  --too-small P           keep images too small for any pieces whole (copy), warn (default) or fail (error)
+  --consistent-cuts       cut every frame of an animated GIF where the average of them would be cut
+  --max-depth N           make at most N levels of cuts
+  --max-pieces N          cut each image into at most N pieces
+  --round-to N            grow pieces to widths and heights that are multiples of N
+  --metric M              rgb-abs (or channels), luma or delta-e (or lab)
+  --weights R,G,B[,A]     how much each channel counts towards the difference
+  --alpha M               ignore, only or weighted
+  --blur SIGMA            blur before measuring, so grain doesn't look like edges
+  --median N              take the median of each pixel's N pixel neighbourhood before measuring
+  --pre STEPS             the measuring steps above as a pipeline, like median:2|blur:1.5
+  --flatten-shading       even out the shadow towards a book's spine before measuring
+  --detect-deskew         straighten a rotated scan before measuring, cutting the pieces from it as it was
+  --apply-deskew          straighten a rotated scan before measuring and cut the pieces from it straightened
+  --detect-scale S        find the cuts in a copy scaled by S, from 0 to 1
+  --detect-megapixels N   find the cuts in a copy scaled down to at most N megapixels
+  --consensus             only keep cuts also found at a half and a quarter of the scale
+  --consensus-tolerance N how far apart the same cut can be at different scales (default 4)
+  --min-gap [h:|v:]N      the number of uniform lines needed around a cut
+  --gap-deviation N       the most a line in a gap may deviate
+  --min-content F         the smallest share of content each side of a cut needs
+  --content-tolerance N   how far a pixel may be from the background and not be content
+  --max-entropy N         leave regions busier than this whole
+  --relative N            compare differences relative to the local contrast against N
+  --contrast-window N     the lines each side that make up the local contrast
+  --window N              score each cut by the average difference over N pairs of lines around it
+  --passes h[:N],v[:N]    cut in each direction in turn
+  --only-horizontal       only make horizontal cuts
+  --only-vertical         only make vertical cuts
+  --direction D           only make horizontal or vertical cuts, or both (the default)
+  --multi-cut             cut at every place over the threshold in a direction at once
+  --speculate             measure the pieces either side of a likely cut before it's decided
+  --aspect-limit N        only cut along images N times longer than wide (default 10)
+  --no-aspect-limit       always consider cutting both ways
+  --max-cuts-h N          the most horizontal cuts across the image
+  --max-cuts-v N          the most vertical cuts across the image
+  --forbid [h:|v:]A-B     never cut within rows or columns A to B
+  --gutter-color C        only cut next to gutters of colour #rrggbb, or auto
+  --gutter-tolerance N    how far a pixel may be from the gutter colour
+  --dividers              cut through solid dark rules first
+  --divider-luma N        how dark a rule has to be
+  --remove-dividers       leave the rules out of the pieces
+  --stickers              separate stickers on a transparent background
+  --sticker-alpha N       the alpha a sticker's pixels are above
+  --screenshots           cut stitched screenshots at their status bars
+  --chrome-rows N         the height of the bars
+  --chrome-tolerance N    how closely the bars have to match
+  --receipts              split receipt rolls into their blocks of printing
+  --receipt-gap N         the smallest gap between blocks
+  --slices                cut PNGs at the slices or 9-patch stored in them, if they have any
+  --piece-size WxH(in|cm) cut into pieces of a physical size instead
+  --save-plan FILE        save the rectangles the input was cut into as a plan
+  --apply-plan FILE       cut every input into a saved plan's rectangles instead
+  --dpi N                 the resolution of images without one
+  --no-color-management   find the cuts in wide gamut images' own colours instead of sRGB's
+  --fallback SETTINGS     retry images that aren't cut with these settings, like threshold=20,min-gap=2
+  --refine SETTINGS       cut each piece again with these settings
+  --config FILE           defaults for any of these options, as in a .autoguillotine.toml, with [directories.PATH]
+                          tables of settings for the inputs in directories (default ./autoguillotine.toml)
+  --no-config             don't use ./autoguillotine.toml
+  --overrides FILE        settings for inputs matching patterns
+                          (a .autoguillotine.toml in an input's directories applies to it too, along with
+                          the profile its [patterns] table names for the input, such as \"*.webp\" = \"webtoon\")
+  --furniture             crop off headers and footers repeated across pages
+  --pixel-art             keep pixel art's exact colours
+  --photo                 suit photos scanned on a flatbed
+
+Pieces:
+  --deskew                straighten each piece
+  --trim N                trim borders within N of the background
+  --trim-padding N        keep N pixels of the border around the content when trimming
+  --resize N              shrink pieces to at most N on their longest side
+  --filter F              nearest, triangle, catmull-rom, gaussian or lanczos
+  --min-sharpness N       flag pieces blurrier than N
+  --drop-blurry           drop the flagged pieces
+  --auto-contrast         stretch each piece's contrast
+  --invert                invert each piece's colours, turning negatives into positives
+  --white-balance         scale each piece's colour channels so it averages out to grey
+  --film F                make viewable frames from scanned film strips, inverting negatives, balancing their
+                          colours and stretching their contrast (negative or positive)
+  --quantize N            reduce each channel to N levels
+  --bleed N               add an N pixel margin around each piece for printing
+  --bleed-fill F          fill the margin with the stretched edge, the background or the pixels around the piece
+                          in the image (edge, background or image), which overlap its neighbours
+  --redact FILE           fill in the regions of each input listed in a JSON file, in its pixels
+  --post STEPS            the piece steps above as a pipeline, like trim|pad:4 for a background margin
+  --expect-pieces N       flag images that don't make N pieces
+  --expect-rows N         flag images that don't make N rows
+  --expect-cols N         flag images that don't make N columns
+
+Output:
+  --output-dir DIR        write outputs into DIR instead of next to each input
+  --watch DIR             cut the images in DIR, then each one added or changed as it arrives, until stopped,
+                          skipping those whose outputs are newer than they are
+  --incremental           only cut inputs that changed, were cut with other settings or whose pieces are missing or
+                          older than them since the last --incremental run, remembered in the output directory
+  --force                 cut every input for --incremental anyway
+  --run-dir DIR           write outputs into a new directory in DIR named after the time, along with the run's
+                          settings (config.json), what came of each input (report.json) and its whole log (log.txt)
+  --stdout zip|manifest   write a zip of the pieces and manifests, or only the manifests, to stdout
+  --append DIR            add every input's pieces to DIR, numbered on from those added before
+  --ladder P,P...         also save each piece at smaller percentages of its size, like 100%,50%,25%,
+                          into directories beside the input's named like page@50
+  --format F              png, jpeg, bmp, tiff or webp, which needs the webp feature and libwebp's cwebp command
+                          and is saved at the JPEG quality; pieces of 16-bit and greyscale PNGs and TIFFs saved as
+                          PNGs or TIFFs keep their depth, and all others are saved at 8 bits a channel
+  --same-format           save each input's pieces in its own format, and JPEGs at its quality
+  --jpeg Q                save pieces as JPEGs of quality Q
+  --indexed               save pieces with few colours as paletted PNGs
+  --layers                cut PSDs flattened and save each piece's layers beside it
+  --pdf-dpi N             render the pages of PDFs at N dots per inch (default 300)
+  --pdf-images            take each PDF page's biggest image, as it was scanned, rather than rendering it
+  --paired DIR            cut the image with each input's name in DIR into the same pieces
+  --name TEMPLATE         name pieces from a template of {index}, {row}, {col}, {stem}, {heading}
+                          and {code}, with widths like {index:03} (default {index})
+  --barcodes              anchor pieces to the barcodes and QR codes on them, named in manifests
+  --nested                put pieces in a directory per row and column
+  --units U               px, norm, in or mm in manifests
+  --cbz                   write a CBZ next to each input, and one of a zip's pages in reading order
+  --output-epub FILE      write every piece into one EPUB
+  --sprites FILE          write every piece into one sprite sheet
+  --montage FILE          write an ffmpeg concat script showing every piece in order
+  --montage-duration N    show each piece in the montage for N seconds (default 2)
+  --dedupe                write identical pieces of an input once, pointing the rest at it in the manifest
+  --dedupe-distance N     also count pieces whose perceptual hashes differ by up to N of 64 bits as the same
+  --summary               save a sheet of each input's pieces, numbered with their sizes, as summary.png
+  --quarantine DIR        copy images that can't be decoded into DIR
+  --post-cmd CMD          run CMD on each piece, with {piece} replaced by its path
+  --post-jobs N           how many post commands run at once
+  --events URL            send a JSON message about each piece written to a webhook URL,
+                          kafka://BROKERS/TOPIC or amqp://SERVER#EXCHANGE/KEY
+  --low-memory            stream PNGs rather than decoding them whole, unless they have furniture to crop or a
+                          colour profile to convert
+  --jobs N                how many inputs are processed at once
+  --dry-run               only report the pieces that would be written
+  --coords-only           only print each piece's rectangle and cut differences as JSON
+  --preview               only save a copy of each input with its cuts drawn on it
+  --export-slices         only record where each input would be cut as slices, for --slices
+  --no-lock               don't lock the output directories
+  --verbose, -v           print every decision and file saved, not just a line for each input
+  --quiet, -q             only print errors
+  --log-format F          print lines of text, or a JSON object a line for monitoring (text or json)
+  --fail-fast             stop at the first input that fails instead of carrying on
+  --help                  print this and exit
+  --version               print the version and exit
+";
+
+// Print an error about the command line arguments and exit
+fn usage_error(message: &str) -> ! {
+    eprintln!("Error: {}", message);
+    exit(1);
+}
+
+// Parse a flag's value, exiting with an error if it is missing or invalid
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> T {
+    let value = value.unwrap_or_else(|| usage_error(&format!("{} requires a value", flag)));
+    value.parse().unwrap_or_else(|_| usage_error(&format!("invalid value for {}: {}", flag, value)))
+}
+
+// Put the settings of the config file given with `--config`, or found in the working directory unless there's a
+// `--no-config`, before the command line's arguments, so the command line's win, and get the config file's settings
+// for directories
+fn with_config_file(args: Vec<String>) -> (Vec<String>, Vec<overrides::DirectorySettings>) {
+    let mut path = Some(PathBuf::from(overrides::CONFIG_FILE)).filter(|path| path.is_file());
+    let mut rest = Vec::with_capacity(args.len());
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => path = Some(parse_value(&arg, args.next())),
+            "--no-config" => path = None,
+            _ => rest.push(arg)
+        }
+    }
+    let path = match path {
+        Some(path) => path,
+        None => return (rest, Vec::new())
+    };
+
+    let file = overrides::read_config_file(&path).unwrap_or_else(|error| usage_error(&format!("couldn't read {}: {}", path.display(), error)));
+    let mut with_file = Vec::with_capacity(file.settings.len() * 2 + rest.len());
+    for (name, value) in file.settings {
+        with_file.push(format!("--{}", name));
+        with_file.extend(value);
+    }
+    with_file.append(&mut rest);
+    (with_file, file.directories)
+}
+
+// Parse the command line arguments into a config, output options and a list of input files
+fn parse_args<I: Iterator<Item = String>>(args: I) -> (GuillotineConfig, OutputOptions, Vec<String>) {
+    let (args, directories) = with_config_file(args.collect());
+    let mut args = args.into_iter();
+    let mut config = GuillotineConfig::default();
+    let mut output = OutputOptions::default();
+    let mut files = Vec::new();
+    let mut units = "px".to_string();
+    let mut dpi = None;
+    let mut fallback: Option<String> = None;
+    let mut refine: Option<String> = None;
+    let mut overrides_path: Option<PathBuf> = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fallback" => fallback = Some(parse_value(&arg, args.next())),
+            "--refine" => refine = Some(parse_value(&arg, args.next())),
+            "--overrides" => overrides_path = Some(parse_value(&arg, args.next())),
+            _ if parse_config_arg(&mut config, &arg, &mut args).unwrap_or_else(|error| usage_error(&error)) => {},
+            "--deskew" => output.post_process.deskew = true,
+            "--trim" => output.post_process.trim = Some(parse_value(&arg, args.next())),
+            "--trim-padding" => output.post_process.trim_padding = parse_value(&arg, args.next()),
+            "--resize" => output.post_process.resize = Some(parse_value(&arg, args.next())),
+            "--filter" => {
+                let value: String = parse_value(&arg, args.next());
+                output.post_process.filter = value.parse().unwrap_or_else(|_| {
+                    usage_error(&format!("{} must be nearest, triangle, catmull-rom, gaussian or lanczos: {}", arg, value))
+                });
+            },
+            "--min-sharpness" => output.post_process.min_sharpness = Some(parse_value(&arg, args.next())),
+            "--drop-blurry" => output.post_process.drop_blurry = true,
+            "--auto-contrast" => output.post_process.auto_contrast = true,
+            "--invert" => output.post_process.invert = true,
+            "--white-balance" => output.post_process.white_balance = true,
+            // Make viewable frames straight from a scanned strip of film, inverted if it's negative, with the cast of
+            // its base taken out and its contrast stretched
+            "--film" => {
+                let value: String = parse_value(&arg, args.next());
+                output.post_process.invert = match value.as_str() {
+                    "negative" => true,
+                    "positive" => false,
+                    _ => usage_error(&format!("{} must be negative or positive: {}", arg, value))
+                };
+                output.post_process.white_balance = true;
+                output.post_process.auto_contrast = true;
+            },
+            "--post" => {
+                let value: String = parse_value(&arg, args.next());
+                postprocess::apply_steps(&mut output.post_process, &value).unwrap_or_else(|error| usage_error(&format!("in {}: {}", arg, error)));
+            },
+            "--redact" => {
+                let path: PathBuf = parse_value(&arg, args.next());
+                output.post_process.redact = postprocess::read_redactions(&path)
+                    .unwrap_or_else(|error| usage_error(&format!("couldn't read {}: {}", path.display(), error)));
+            },
+            "--bleed" => output.post_process.bleed = Some(parse_value(&arg, args.next())),
+            "--bleed-fill" => {
+                let value: String = parse_value(&arg, args.next());
+                output.post_process.bleed_fill = value.parse().unwrap_or_else(|_| {
+                    usage_error(&format!("{} must be edge, background or image: {}", arg, value))
+                });
+            },
+            "--quantize" => {
+                let levels = parse_value(&arg, args.next());
+                if levels < 2 {
+                    usage_error(&format!("{} must be at least 2", arg));
+                }
+                output.post_process.quantize = Some(levels);
+            },
+            "--low-memory" => output.low_memory = true,
+            "--indexed" => output.indexed = true,
+            "--slices" => output.slices = true,
+            "--paired" => output.paired.push(parse_value(&arg, args.next())),
+            "--layers" => output.layers = true,
+            "--pdf-dpi" => {
+                output.pdf_dpi = parse_value(&arg, args.next());
+                if output.pdf_dpi == 0 {
+                    usage_error(&format!("{} must be at least 1", arg));
+                }
+            },
+            "--pdf-images" => output.pdf_images = true,
+            "--jpeg" => {
+                let quality = parse_value(&arg, args.next());
+                if quality == 0 || quality > 100 {
+                    usage_error(&format!("{} takes a quality from 1 to 100", arg));
+                }
+                output.format = cbz::Encoding::Jpeg;
+                output.jpeg_quality = quality;
+            },
+            "--format" => {
+                let value: String = parse_value(&arg, args.next());
+                output.format = match value.as_str() {
+                    "png" => cbz::Encoding::Png,
+                    "jpeg" | "jpg" => cbz::Encoding::Jpeg,
+                    "bmp" => cbz::Encoding::Bmp,
+                    "tiff" | "tif" => cbz::Encoding::Tiff,
+                    "webp" if formats::webp_supported() => cbz::Encoding::Webp,
+                    "webp" => usage_error(&format!("{} webp needs autoguillotine built with the webp feature (--features webp)", arg)),
+                    _ => usage_error(&format!("{} must be png, jpeg, bmp, tiff or webp: {}", arg, value))
+                };
+            },
+            "--same-format" => output.same_format = true,
+            // Keep pixel art's exact colours: gaps have to be a single colour, nothing is smoothed or normalized and
+            // pieces are saved paletted where they can be
+            "--pixel-art" => {
+                config.gap_deviation = 0.0;
+                config.content_tolerance = 0.0;
+                config.relative_threshold = None;
+                output.post_process.filter = postprocess::Filter::Nearest;
+                output.post_process.auto_contrast = false;
+                output.indexed = true;
+            },
+            // Suit photos scanned on a flatbed: colours are compared as they look, grain is blurred away, cuts need a
+            // strip of scanner bed around them rather than a strong edge, and pieces are straightened, trimmed of the
+            // scanner bed and saved as JPEGs
+            "--photo" => {
+                config.metric = Metric::DeltaE;
+                config.threshold = 0.5;
+                config.min_gap_horizontal = 8;
+                config.min_gap_vertical = 8;
+                config.blur = Some(2.0);
+                output.post_process.deskew = true;
+                output.post_process.trim = Some(24.0);
+                output.format = cbz::Encoding::Jpeg;
+                output.indexed = false;
+            },
+            "--furniture" => output.crop_furniture = true,
+            "--save-plan" => output.save_plan = Some(parse_value(&arg, args.next())),
+            "--apply-plan" => {
+                let path: PathBuf = parse_value(&arg, args.next());
+                output.apply_plan = Some(plan::Plan::read(&path).unwrap_or_else(|error| usage_error(&format!("couldn't read {}: {}", path.display(), error))));
+            },
+            "--piece-size" => {
+                let value: String = parse_value(&arg, args.next());
+                output.piece_size = Some(value.parse().unwrap_or_else(|_| usage_error(&format!("{} takes a size like 4x6in or 10x15cm", arg))));
+            },
+            "--nested" => output.nested = true,
+            "--quarantine" => output.quarantine = Some(parse_value(&arg, args.next())),
+            "--name" => {
+                output.name_template = parse_value(&arg, args.next());
+                if output.name_template.contains("{code}") {
+                    output.barcodes = true;
+                }
+            },
+            "--expect-pieces" => output.expect_pieces = Some(parse_value(&arg, args.next())),
+            "--expect-rows" => output.expect_rows = Some(parse_value(&arg, args.next())),
+            "--expect-cols" => output.expect_columns = Some(parse_value(&arg, args.next())),
+            "--cbz" => output.cbz = true,
+            "--output-epub" => output.epub = Some(parse_value(&arg, args.next())),
+            "--sprites" => output.sprites = Some(parse_value(&arg, args.next())),
+            "--montage" => output.montage = Some(parse_value(&arg, args.next())),
+            "--summary" => output.summary = true,
+            "--dedupe" => output.dedupe = true,
+            "--dedupe-distance" => {
+                output.dedupe = true;
+                output.dedupe_distance = Some(parse_value(&arg, args.next()));
+            },
+            "--montage-duration" => {
+                output.montage_duration = parse_value(&arg, args.next());
+                if !(output.montage_duration > 0.0) {
+                    usage_error("--montage-duration must be more than 0");
+                }
+            },
+            "--dry-run" => output.dry_run = true,
+            "--coords-only" => output.coords_only = true,
+            "--preview" => output.preview = true,
+            "--export-slices" => output.export_slices = true,
+            "--no-lock" => output.no_lock = true,
+            "--verbose" | "-v" => output.log.verbosity = logging::Verbosity::Verbose,
+            "--quiet" | "-q" => output.log.verbosity = logging::Verbosity::Quiet,
+            "--log-format" => {
+                let value: String = parse_value(&arg, args.next());
+                output.log.format = value.parse().unwrap_or_else(|_| usage_error(&format!("{} must be text or json: {}", arg, value)));
+            },
+            "--fail-fast" => output.fail_fast = true,
+            "--output-dir" => output.output_dir = Some(parse_value(&arg, args.next())),
+            "--run-dir" => output.run_dir = Some(parse_value(&arg, args.next())),
+            "--incremental" => output.incremental = true,
+            "--force" => output.force = true,
+            "--watch" => {
+                if !watch::watch_supported() {
+                    usage_error(&format!("{} needs autoguillotine built with the watch feature (--features watch)", arg));
+                }
+                output.watch = Some(parse_value(&arg, args.next()));
+            },
+            "--append" => output.append = Some(parse_value(&arg, args.next())),
+            "--ladder" => {
+                let value: String = parse_value(&arg, args.next());
+                output.ladder = value.split(',')
+                    .map(|step| step.trim().trim_end_matches('%').parse().ok().filter(|&percent| percent > 0 && percent <= 100))
+                    .collect::<Option<Vec<u32>>>()
+                    .unwrap_or_else(|| usage_error(&format!("{} takes percentages from 1 to 100, like 100%,50%,25%: {}", arg, value)));
+            },
+            "--stdout" => {
+                let value: String = parse_value(&arg, args.next());
+                output.stdout = Some(value.parse().unwrap_or_else(|_| usage_error(&format!("{} must be zip or manifest: {}", arg, value))));
+            },
+            "--help" | "-h" => {
+                print!("{}", USAGE);
+                exit(0);
+            },
+            "--version" => {
+                println!("autoguillotine {}", env!("CARGO_PKG_VERSION"));
+                exit(0);
+            },
+            "--units" => units = parse_value(&arg, args.next()),
+            "--consistent-cuts" => output.consistent_cuts = true,
+            "--barcodes" => output.barcodes = true,
+            "--too-small" => {
+                let value: String = parse_value(&arg, args.next());
+                output.too_small = value.parse().unwrap_or_else(|_| usage_error(&format!("{} must be copy, warn or error: {}", arg, value)));
+            },
+            "--no-color-management" => output.color_management = false,
+            "--dpi" => dpi = Some(parse_value(&arg, args.next())),
+            "--jobs" => {
+                output.jobs = parse_value(&arg, args.next());
+                if output.jobs == 0 {
+                    usage_error(&format!("{} must be at least 1", arg));
+                }
+            },
+            "--post-cmd" => output.post_command = Some(parse_value(&arg, args.next())),
+            "--events" => {
+                if !events::events_supported() {
+                    usage_error(&format!("{} needs autoguillotine built with the events feature (--features events)", arg));
+                }
+                let value: String = parse_value(&arg, args.next());
+                output.events = Some(value.parse().unwrap_or_else(|_| {
+                    usage_error(&format!("{} must be an http:// or https:// URL, kafka://BROKERS/TOPIC or amqp://SERVER#EXCHANGE/KEY: {}", arg, value))
+                }));
+            },
+            "--post-jobs" => {
+                output.post_jobs = parse_value(&arg, args.next());
+                if output.post_jobs == 0 {
+                    usage_error(&format!("{} must be at least 1", arg));
+                }
+            },
+            _ if arg.starts_with("--") => usage_error(&format!("unknown option: {}", arg)),
+            _ => files.push(arg)
+        }
+    }
+
+    // Stdin can only be read once, and what's written to stdout can't be mixed with the other outputs
+    if files.iter().filter(|file| *file == stream::STDIN).count() > 1 {
+        usage_error("stdin can only be given as an input once");
+    }
+    let only_stdout = !(output.cbz || output.epub.is_some() || output.sprites.is_some() || output.montage.is_some() || output.post_command.is_some()
+        || output.events.is_some() || output.dry_run || output.coords_only || output.preview || output.export_slices);
+    if output.stdout.is_some() && !only_stdout {
+        usage_error("--stdout can't be used with other outputs, or the options that only report or preview");
+    }
+    if output.append.is_some() && (output.cbz || output.epub.is_some() || output.sprites.is_some() || output.stdout.is_some()) {
+        usage_error("--append can't be used with --cbz, --output-epub, --sprites or --stdout");
+    }
+    if output.watch.is_some() && !files.is_empty() {
+        usage_error("--watch cuts the images in its directory rather than inputs given");
+    }
+    let together = output.epub.is_some() || output.sprites.is_some() || output.montage.is_some() || output.append.is_some();
+    if output.watch.is_some() && (together || output.stdout.is_some() || output.run_dir.is_some() || output.save_plan.is_some()) {
+        usage_error("--watch can't be used with --output-epub, --sprites, --montage, --append, --stdout, --run-dir or --save-plan");
+    }
+    if output.force && !output.incremental {
+        usage_error("--force only applies to --incremental");
+    }
+    if output.incremental && (together || output.stdout.is_some() || output.run_dir.is_some() || files.iter().any(|file| file == stream::STDIN)) {
+        usage_error("--incremental can't be used with --output-epub, --sprites, --montage, --append, --stdout, --run-dir or stdin");
+    }
+    if output.run_dir.is_some() && (output.output_dir.is_some() || output.append.is_some() || output.stdout.is_some()) {
+        usage_error("--run-dir can't be used with --output-dir, --append or --stdout");
+    }
+    if output.save_plan.is_some() && files.len() != 1 {
+        usage_error("--save-plan saves the cuts of a single input");
+    }
+    if output.apply_plan.is_some() && (output.save_plan.is_some() || output.piece_size.is_some()) {
+        usage_error("--apply-plan can't be used with --save-plan or --piece-size");
+    }
+    if output.post_process.image_bleed().is_some() && (output.post_process.deskew || output.post_process.trim.is_some()) {
+        usage_error("--bleed-fill image can't be used with --deskew or --trim, which would move the piece inside its margin");
+    }
+    if output.post_process.image_bleed().is_some() && config.apply_deskew {
+        usage_error("--bleed-fill image can't be used with --apply-deskew, as the pieces aren't from the image as it was");
+    }
+    if !output.ladder.is_empty() && (output.cbz || output.epub.is_some() || output.sprites.is_some() || output.stdout.is_some() || output.append.is_some()) {
+        usage_error("--ladder can't be used with --cbz, --output-epub, --sprites, --stdout or --append");
+    }
+
+    output.dpi = dpi;
+    output.units = match (units.as_str(), dpi) {
+        ("px", _) => manifest::Units::Pixels,
+        ("norm", _) => manifest::Units::Normalized,
+        ("in", Some(dpi)) => manifest::Units::Inches(dpi),
+        ("mm", Some(dpi)) => manifest::Units::Millimetres(dpi),
+        ("in", None) | ("mm", None) => usage_error(&format!("--units {} requires --dpi", units)),
+        _ => usage_error(&format!("--units must be px, norm, in or mm: {}", units))
+    };
+
+    // The fallback settings start from the primary settings, whichever order the arguments came in
+    if let Some(fallback) = fallback {
+        let mut fallback_config = config.clone();
+        apply_settings(&mut fallback_config, &fallback)
+            .unwrap_or_else(|error| usage_error(&format!("in --fallback: {}", error)));
+        config.fallback = Some(Box::new(fallback_config));
+    }
+
+    // The refining settings start from the primary settings with half the minimum size
+    if let Some(refine) = refine {
+        let min_area = config.min_area.map(|min_area| min_area / 4);
+        let mut refine_config = GuillotineConfig { min_size: config.min_size / 2, min_area, fallback: None, ..config.clone() };
+        apply_settings(&mut refine_config, &refine)
+            .unwrap_or_else(|error| usage_error(&format!("in --refine: {}", error)));
+        config.refine = Some(Box::new(refine_config));
+    }
+
+    // Check the overrides' settings up front rather than failing part way through a run
+    if let Some(path) = overrides_path {
+        config.overrides = overrides::read_overrides(&path)
+            .unwrap_or_else(|error| usage_error(&format!("couldn't read {}: {}", path.display(), error)));
+        for matching in &config.overrides {
+            apply_settings(&mut config.clone(), &matching.settings)
+                .unwrap_or_else(|error| usage_error(&format!("for {} in {}: {}", matching.pattern, path.display(), error)));
+        }
+    }
 
-    // If the image is too small, don't return anything
-    if width < min_size || height < min_size {
-        return Vec::new();
+    // Check the config file's settings for directories, then find the settings files in the directories the inputs
+    // are in, checking them up front in the same way
+    for directory in directories {
+        apply_setting_list(&mut config.clone(), &directory.settings)
+            .unwrap_or_else(|error| usage_error(&format!("for {} in the config file: {}", directory.dir.display(), error)));
+        config.directories.push(directory);
+    }
+    let mut found: Vec<PathBuf> = Vec::new();
+    for file in files.iter().filter(|file| *file != stream::STDIN) {
+        for path in overrides::find_directory_files(Path::new(file)) {
+            if found.contains(&path) {
+                continue;
+            }
+            found.push(path.clone());
+            let directory = overrides::read_directory_settings(&path)
+                .unwrap_or_else(|error| usage_error(&format!("couldn't read {}: {}", path.display(), error)));
+            apply_setting_list(&mut config.clone(), &directory.settings)
+                .unwrap_or_else(|error| usage_error(&format!("in {}: {}", path.display(), error)));
+            for profile in &directory.profiles {
+                apply_setting_list(&mut config.clone(), &profile.settings)
+                    .unwrap_or_else(|error| usage_error(&format!("in profile {} in {}: {}", profile.profile, path.display(), error)));
+            }
+            config.directories.push(directory);
+        }
     }
+    config.directories.sort_by_key(|directory| directory.dir.components().count());
 
-    // Get the maximum differences and the place to cut 
-    let (h_index, h_max) = difference_horizontal(&image);
-    let (v_index, v_max) = difference_vertical(&image);
+    (config, output, files)
+}
 
-    let horizontal = h_max > v_max;
-    let max = if horizontal { h_max } else { v_max };
-    let cut = max > threshold;
+// Every setting of a run
+#[derive(Serialize)]
+struct Settings<'a> {
+    config: &'a GuillotineConfig,
+    output: &'a OutputOptions
+}
 
-    println!("Cut: {}, Horizontal: {}, Max: {}", cut, horizontal, max);
+// Stamp a run's results with the version and settings that made them
+fn stamp(config: &GuillotineConfig, output: &OutputOptions) -> manifest::Stamp {
+    manifest::Stamp::new(&Settings { config, output })
+}
 
-    // If the image won't be cut, return it
-    if !cut {
-        return vec![image];
+// Once everything else is done, say how many images were skipped or didn't make the expected pieces, and fail if
+// there were any
+fn report_failures(processed: &[Processed]) {
+    let failed: usize = processed.iter().map(|processed| processed.failed).sum();
+    let mismatched: usize = processed.iter().map(|processed| processed.mismatched).sum();
+    if failed > 0 {
+        eprintln!("Warning: {} images failed and were skipped:", failed);
+        for error in processed.iter().flat_map(|processed| &processed.errors) {
+            eprintln!("  {}", error);
+        }
     }
+    if mismatched > 0 {
+        eprintln!("Warning: {} images didn't make the expected pieces", mismatched);
+    }
+    if failed > 0 || mismatched > 0 {
+        exit(1);
+    }
+}
 
-    // Create the two sub images
-    let (sub_a, sub_b) = if horizontal {(
-        image.sub_image(0, 0, width, h_index).to_image(),
-        image.sub_image(0, h_index, width, height - h_index).to_image()
-    )} else {(
-        image.sub_image(0, 0, v_index, height).to_image(),
-        image.sub_image(v_index, 0, width - v_index, height).to_image()
-    )};
+// Take the ground truth file out of the arguments of `analyze`, returning it and the arguments left to parse as usual
+fn parse_analyze_args<I: Iterator<Item = String>>(mut args: I) -> (PathBuf, Vec<String>) {
+    let mut ground_truth = None;
+    let mut rest = Vec::new();
 
-    // Guillotine the two sub images in parallel
-    let (mut g_a, mut g_b) = rayon::join(
-        || guillotine(sub_a, threshold, min_size),
-        || guillotine(sub_b, threshold, min_size)
-    );
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ground-truth" => ground_truth = Some(parse_value(&arg, args.next())),
+            _ => rest.push(arg)
+        }
+    }
 
-    // Move the guillotined images into images
-    let mut images = Vec::new();
-    images.append(&mut g_a);
-    images.append(&mut g_b);
+    let ground_truth = ground_truth.unwrap_or_else(|| usage_error("analyze takes --ground-truth and the labelled images"));
+    (ground_truth, rest)
+}
+
+// Parse the arguments of `pack`, returning the options and the manifests or directories of pieces to pack
+fn parse_pack_args<I: Iterator<Item = String>>(mut args: I) -> (pack::PackOptions, Vec<String>) {
+    let mut options = pack::PackOptions::default();
+    let mut inputs = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => {
+                let size: String = parse_value(&arg, args.next());
+                let parsed = size.split_once('x').and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)));
+                match parsed {
+                    Some((width, height)) if width > 0 && height > 0 => {
+                        options.width = width;
+                        options.height = height;
+                    },
+                    _ => usage_error(&format!("{} takes a size like 1240x1754", arg))
+                }
+            },
+            "--margin" => options.margin = parse_value(&arg, args.next()),
+            "--spacing" => options.spacing = parse_value(&arg, args.next()),
+            "--output" => options.output = parse_value(&arg, args.next()),
+            "--help" | "-h" => {
+                print!("{}", USAGE);
+                exit(0);
+            },
+            _ if arg.starts_with("--") => usage_error(&format!("unknown option: {}", arg)),
+            _ => inputs.push(arg)
+        }
+    }
 
-    // And return them
-    images
+    if inputs.is_empty() {
+        usage_error("pack takes manifests or directories of pieces");
+    }
+    (options, inputs)
 }
 
 fn main() {
-    for arg in args().skip(1) {
-        let path = Path::new(&arg);
-        // Load the image
-        let image = image::open(path).unwrap().to_rgb();
-        // Autoguillotine the image
-        let images = guillotine(image, 30.0, 100);
-        // Get the filename without the extension as the dir
-        let dir = path.parent().unwrap().join(path.file_stem().unwrap());
-        // Create the dir
-        create_dir_all(&dir).unwrap();
-
-        // Save the images
-        for (i, image) in images.iter().enumerate() {
-            let path = dir.join(format!("{}.png", i));
-            println!("Saving {}...", path.display());
-            image.save(path).unwrap();
+    let mut args = args().skip(1).peekable();
+
+    // `check` only reads the inputs' headers and reports on them
+    if args.peek().map(|arg| arg == "check").unwrap_or(false) {
+        args.next();
+        let (config, _, files) = parse_args(args);
+        exit(if check::check(&files, &config) { 0 } else { 1 });
+    }
+
+    // `analyze` compares the cuts with hand-labelled pieces
+    if args.peek().map(|arg| arg == "analyze").unwrap_or(false) {
+        args.next();
+        let (ground_truth, args) = parse_analyze_args(args);
+        let (config, _, files) = parse_args(args.into_iter());
+        exit(if analyze::analyze(&files, &ground_truth, &config) { 0 } else { 1 });
+    }
+
+    // `diff` compares the manifests of two runs
+    if args.peek().map(|arg| arg == "diff").unwrap_or(false) {
+        args.next();
+        let paths: Vec<String> = args.collect();
+        if paths.len() != 2 {
+            usage_error("diff takes an old and a new manifest or guillotined zip");
         }
+        exit(if diff::diff(Path::new(&paths[0]), Path::new(&paths[1])) { 0 } else { 1 });
+    }
+
+    // `verify` checks written pieces against the checksums in their manifests
+    if args.peek().map(|arg| arg == "verify").unwrap_or(false) {
+        args.next();
+        let manifests: Vec<String> = args.collect();
+        if manifests.is_empty() {
+            usage_error("verify takes one or more manifests");
+        }
+        exit(if verify::verify(&manifests) { 0 } else { 1 });
+    }
+
+    // `pack` puts pieces back onto sheets
+    if args.peek().map(|arg| arg == "pack").unwrap_or(false) {
+        args.next();
+        let (options, inputs) = parse_pack_args(args);
+        exit(if pack::pack(&inputs, &options) { 0 } else { 1 });
+    }
+
+    let (config, mut output, files) = parse_args(args);
+    if output.crop_furniture {
+        output.furniture = furniture::find_furniture(&files);
+    }
+
+    // Only print the pieces' rectangles, so the output can be read by other tools
+    if output.coords_only {
+        let processed = process_files(&files, &output, |arg| coordinates_file(arg, &config, &output));
+        for processed in &processed {
+            for line in &processed.log {
+                println!("{}", line);
+            }
+        }
+        report_failures(&processed);
+        return;
+    }
+
+    // Write the pieces or manifests to stdout, keeping everything else printed on stderr out of their way
+    if let Some(format) = output.stdout {
+        let mut processed = process_files(&files, &output, |arg| stream_file(arg, &config, &output));
+        let mut streamed = Vec::new();
+        for (file, processed) in files.iter().zip(&mut processed) {
+            output.log.print_log(file, &processed.log, logging::is_problem, true);
+            output.log.print_summary(file, processed.pieces, processed.failed > 0, true);
+            streamed.append(&mut processed.streamed);
+        }
+        let stdout = std::io::stdout();
+        if let Err(error) = stream::write(stdout.lock(), format, &streamed).map_err(Error::writing(Path::new("stdout"))) {
+            eprintln!("Error: {}", error);
+            exit(1);
+        }
+        report_failures(&processed);
+        return;
+    }
+
+    let run = stamp(&config, &output);
+    output.log.print_run(&run);
+
+    if output.dry_run {
+        let processed = process_files(&files, &output, |arg| dry_run_file(arg, &config, &output));
+        for processed in &processed {
+            for line in &processed.log {
+                println!("{}", line);
+            }
+        }
+        let total_pieces: usize = processed.iter().map(|processed| processed.pieces).sum();
+        let total_bytes: u64 = processed.iter().map(|processed| processed.bytes).sum();
+        println!("Total: {} pieces, ~{}", total_pieces, format_bytes(total_bytes));
+        report_failures(&processed);
+        return;
+    }
+
+    // A run directory is made fresh for the run and written into as the output directory
+    let run_dir = output.run_dir.as_ref().map(|root| {
+        rundir::RunDir::create(root).unwrap_or_else(|error| usage_error(&format!("couldn't create a run directory in {}: {}", root.display(), error)))
+    });
+    if let Some(ref run_dir) = run_dir {
+        output.log.print(&format!("Run directory: {}", run_dir.dir.display()));
+        output.output_dir = Some(run_dir.dir.clone());
+    }
+
+    // The output directory has to exist before it can be locked or written into
+    if let Some(ref dir) = output.output_dir {
+        create_dir_all(dir).unwrap_or_else(|error| usage_error(&format!("couldn't create {}: {}", dir.display(), error)));
+    }
+
+    // Cut each image as it arrives, printing what came of it as a run would, until the watch fails
+    if let Some(ref dir) = output.watch {
+        output.log.print(&format!("Watching {}...", dir.display()));
+        let watched = watch::watch(dir, |path| {
+            let arg = path.to_string_lossy().into_owned();
+            if up_to_date(&arg, &output) {
+                return;
+            }
+            let mut processed = process_file(&arg, &config, &output);
+            send_events(&mut processed, &output);
+            output.log.print_log(&arg, &processed.log, logging::is_problem, false);
+            output.log.print_summary(&arg, processed.pieces, processed.failed > 0, false);
+            if let Some(ref command) = output.post_command {
+                hooks::run_post_command(command, &processed.written, output.post_jobs);
+            }
+        });
+        if let Err(error) = watched {
+            eprintln!("Error: couldn't watch {}: {}", dir.display(), error);
+            exit(1);
+        }
+        return;
+    }
+
+    // Hold the locks until the end of the run
+    let _locks = if output.no_lock {
+        Vec::new()
+    } else {
+        let locations: Vec<PathBuf> = match output.append {
+            Some(ref dir) => vec![dir.clone()],
+            None => files.iter().map(|file| output_location(Path::new(file), &output)).collect()
+        };
+        let outputs = locations.iter().map(PathBuf::as_path).chain(output.epub.as_deref()).chain(output.sprites.as_deref())
+            .chain(output.montage.as_deref());
+        lock::lock_outputs(outputs).unwrap_or_else(|error| {
+            eprintln!("Error: {}", error);
+            exit(1);
+        })
+    };
+
+    if output.preview || output.export_slices {
+        let processed = process_files(&files, &output, |arg| {
+            if output.preview { preview_file(arg, &config, &output) } else { export_slices_file(arg, &config, &output) }
+        });
+        for processed in &processed {
+            for line in &processed.log {
+                println!("{}", line);
+            }
+        }
+        report_failures(&processed);
+        return;
+    }
+
+    // Leave out the inputs that are up to date with what they were last cut into
+    let state_path = output.output_dir.as_deref().unwrap_or(Path::new(".")).join(incremental::STATE_FILE);
+    let mut state = if output.incremental { Some(incremental::State::read(&state_path)) } else { None };
+    let files: Vec<String> = match state {
+        Some(ref state) if !output.force => files.into_iter()
+            .filter(|file| {
+                let skip = state.up_to_date(file, &run.fingerprint);
+                if skip {
+                    output.log.print(&format!("{} is up to date", file));
+                }
+                !skip
+            })
+            .collect(),
+        _ => files
+    };
+
+    let mut epub_pages = Vec::new();
+    let mut sprites = Vec::new();
+    let mut written = Vec::new();
+    let mut montage = Vec::new();
+    let mut collected = Vec::new();
+    let mut processed = process_files(&files, &output, |arg| {
+        if output.append.is_some() {
+            collect_file(arg, &config, &output)
+        } else {
+            // Send each input's messages as soon as it's done, rather than once the whole run is
+            let mut processed = process_file(arg, &config, &output);
+            send_events(&mut processed, &output);
+            processed
+        }
+    });
+
+    for (file, processed) in files.iter().zip(&mut processed) {
+        // Unless asked for everything, only print what went wrong and how many pieces each input made
+        output.log.print_log(file, &processed.log, logging::is_problem, false);
+        output.log.print_summary(file, processed.pieces, processed.failed > 0, false);
+        if let Some(ref mut state) = state {
+            if processed.failed == 0 {
+                let mut outputs = processed.written.clone();
+                if archive::is_zip(Path::new(file)) {
+                    outputs.push(archive::output_path(&output_location(Path::new(file), &output), archive::is_comic(Path::new(file), &output)));
+                }
+                state.record(file, &run.fingerprint, outputs);
+            }
+        }
+        epub_pages.append(&mut processed.pages);
+        sprites.append(&mut processed.sprites);
+        written.append(&mut processed.written);
+        montage.append(&mut processed.montage);
+        collected.append(&mut processed.collected);
+    }
+
+    if let Some(ref state) = state {
+        if let Err(error) = state.save(&state_path) {
+            eprintln!("Warning: couldn't save {}: {}", state_path.display(), error);
+        }
+    }
+
+    // Pieces added to a collection are numbered in the order the inputs were given, so they're only saved once
+    // every input has been cut
+    if let Some(ref dir) = output.append {
+        let mut appended = Processed::default();
+        let saved = create_dir_all(dir).map_err(Error::writing(dir))
+            .and_then(|_| append_collected(dir, collected, &config, &output, &mut appended));
+        if let Err(error) = saved {
+            appended.fail(&dir.display().to_string(), error);
+        }
+        send_events(&mut appended, &output);
+        output.log.print_log(&dir.display().to_string(), &appended.log, |line| !line.starts_with("Saving"), false);
+        written.append(&mut appended.written);
+        montage.append(&mut appended.montage);
+        processed.push(appended);
+    }
+
+    if let Some(ref command) = output.post_command {
+        hooks::run_post_command(command, &written, output.post_jobs);
+    }
+
+    // The outputs made from every input's pieces are written last, and the run fails if any of them can't be
+    let mut written_together = Vec::new();
+    if let Some(epub_path) = output.epub {
+        let title = epub_path.file_stem().unwrap().to_string_lossy().into_owned();
+        output.log.print(&format!("Saving {}...", epub_path.display()));
+        written_together.push(epub::write_epub(&epub_path, &title, &epub_pages).map_err(Error::writing(&epub_path)));
+    }
+
+    if let Some(sprites_path) = output.sprites {
+        output.log.print(&format!("Saving {}...", sprites_path.display()));
+        written_together.push(sprites::write_sprites(&sprites_path, &sprites).map_err(Error::writing(&sprites_path)));
+    }
+
+    if let Some(ref montage_path) = output.montage {
+        output.log.print(&format!("Saving {}...", montage_path.display()));
+        written_together.push(montage::write_montage(montage_path, &montage, output.montage_duration).map_err(Error::writing(montage_path)));
+    }
+
+    if let Some(ref run_dir) = run_dir {
+        let inputs: Vec<rundir::InputReport> = files.iter().zip(&processed)
+            .map(|(file, processed)| {
+                rundir::InputReport { input: file, pieces: processed.pieces, errors: &processed.errors, mismatched: processed.mismatched > 0 }
+            })
+            .collect();
+        let log: Vec<String> = processed.iter().flat_map(|processed| processed.log.iter().cloned()).collect();
+        written_together.push(run_dir.finish(&run, &inputs, &log).map_err(Error::writing(&run_dir.dir)));
+    }
+
+    let errors: Vec<Error> = written_together.into_iter().filter_map(Result::err).collect();
+    for error in &errors {
+        eprintln!("Error: {}", error);
+    }
+    report_failures(&processed);
+    if !errors.is_empty() {
+        exit(1);
     }
 }
\ No newline at end of file
@@ -0,0 +1,198 @@
+// Describe where each piece of an image came from
+
+use serde::{Serialize, Serializer};
+use serde_json;
+
+use blurhash;
+use icc;
+use sha256::sha256;
+use stats::{self, Stats};
+use Piece;
+
+// The units the rectangles in a manifest are given in
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Units {
+    Pixels,
+    // Fractions of the source image's width and height
+    Normalized,
+    // Physical sizes at the given number of dots per inch
+    Inches(f64),
+    Millimetres(f64)
+}
+
+impl Units {
+    fn name(&self) -> &'static str {
+        match *self {
+            Units::Pixels => "px",
+            Units::Normalized => "norm",
+            Units::Inches(_) => "in",
+            Units::Millimetres(_) => "mm"
+        }
+    }
+
+    // Convert a horizontal or vertical pixel measurement, given the source image's size along that axis
+    fn convert(&self, pixels: u32, size: u32) -> f64 {
+        match *self {
+            Units::Pixels => pixels as f64,
+            Units::Normalized => pixels as f64 / size as f64,
+            Units::Inches(dpi) => pixels as f64 / dpi,
+            Units::Millimetres(dpi) => pixels as f64 / dpi * 25.4
+        }
+    }
+}
+
+// Write whole numbers without a fractional part, so pixel rectangles stay integers
+fn serialize_number<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        serializer.serialize_i64(*value as i64)
+    } else {
+        serializer.serialize_f64(*value)
+    }
+}
+
+// The version of the tool and every setting it ran with, so a run can be reproduced and audited later
+#[derive(Clone, Serialize)]
+pub struct Stamp {
+    pub version: &'static str,
+    // A hash of the settings, to tell at a glance whether two runs used the same ones
+    pub fingerprint: String,
+    pub settings: serde_json::Value
+}
+
+impl Stamp {
+    pub fn new<T: Serialize>(settings: &T) -> Stamp {
+        let json = serde_json::to_string(settings).unwrap();
+
+        // FNV-1a, which unlike the standard library's hasher is the same on every build
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in json.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+
+        Stamp {
+            version: env!("CARGO_PKG_VERSION"),
+            fingerprint: format!("{:016x}", hash),
+            settings: serde_json::from_str(&json).unwrap()
+        }
+    }
+}
+
+// A rectangle in the source image
+#[derive(Serialize)]
+pub struct Rectangle {
+    #[serde(serialize_with = "serialize_number")]
+    pub x: f64,
+    #[serde(serialize_with = "serialize_number")]
+    pub y: f64,
+    #[serde(serialize_with = "serialize_number")]
+    pub width: f64,
+    #[serde(serialize_with = "serialize_number")]
+    pub height: f64
+}
+
+// A piece's file and its rectangle in the source image
+#[derive(Serialize)]
+pub struct ManifestPiece {
+    pub file: String,
+    // The SHA-256 of the file, to find pieces that have been damaged since
+    pub sha256: String,
+    // Which side of each cut the piece fell on, like `0.1.0`, so pieces that only differ in the last place were cut
+    // apart from each other
+    pub id: String,
+    #[serde(serialize_with = "serialize_number")]
+    pub x: f64,
+    #[serde(serialize_with = "serialize_number")]
+    pub y: f64,
+    #[serde(serialize_with = "serialize_number")]
+    pub width: f64,
+    #[serde(serialize_with = "serialize_number")]
+    pub height: f64,
+    // The difference that made the cut along each of the left, top, right and bottom edges, or null where an edge
+    // wasn't cut
+    pub scores: [Option<f64>; 4],
+    pub stats: Stats,
+    // A placeholder for the piece, to show while it loads
+    pub blurhash: String,
+    // The piece this one was found inside by refining, if it was
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<Rectangle>,
+    // Where the piece was cut, if it was grown from there to round its size
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nominal: Option<Rectangle>,
+    // The name of the colour profile embedded in the piece, which is its source's, if it has one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    // The barcode the piece is anchored by, if codes were read and one was found on or near it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    // Whether the piece is the same as an earlier one, so its file is that piece's rather than one of its own
+    #[serde(skip_serializing_if = "is_false")]
+    pub duplicate: bool
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+// The pieces an image was cut into
+#[derive(Serialize)]
+pub struct Manifest {
+    pub source: String,
+    // The size of the source image in pixels
+    pub source_width: u32,
+    pub source_height: u32,
+    #[serde(serialize_with = "serialize_units")]
+    pub units: Units,
+    pub pieces: Vec<ManifestPiece>,
+    // How the pieces were made
+    pub run: Stamp
+}
+
+fn serialize_units<S: Serializer>(units: &Units, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(units.name())
+}
+
+impl Manifest {
+    pub fn new(source: String, source_width: u32, source_height: u32, units: Units, run: Stamp) -> Manifest {
+        Manifest { source, source_width, source_height, units, pieces: Vec::new(), run }
+    }
+
+    // Add a piece, describing it from its image, its rectangle in pixels and the data written to its file
+    pub fn push(&mut self, file: String, piece: &Piece, data: &[u8]) {
+        let units = self.units;
+        self.pieces.push(ManifestPiece {
+            file,
+            sha256: sha256(data),
+            id: piece.id(),
+            x: units.convert(piece.x, self.source_width),
+            y: units.convert(piece.y, self.source_height),
+            width: units.convert(piece.width, self.source_width),
+            height: units.convert(piece.height, self.source_height),
+            scores: piece.scores,
+            stats: stats::piece_stats(&piece.image),
+            blurhash: blurhash::blurhash(&piece.image),
+            parent: piece.parent.map(|rectangle| self.rectangle(rectangle)),
+            nominal: piece.nominal.map(|rectangle| self.rectangle(rectangle)),
+            profile: icc::embedded_profile(data).and_then(|profile| icc::profile_name(&profile)),
+            code: None,
+            duplicate: false
+        });
+    }
+
+    // Convert a rectangle in the source image to the manifest's units
+    fn rectangle(&self, [x, y, width, height]: [u32; 4]) -> Rectangle {
+        let units = self.units;
+        Rectangle {
+            x: units.convert(x, self.source_width),
+            y: units.convert(y, self.source_height),
+            width: units.convert(width, self.source_width),
+            height: units.convert(height, self.source_height)
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
@@ -0,0 +1,58 @@
+// Take the median of each pixel's neighbourhood, which wipes out specks of film grain and the ringing around JPEG
+// blocks without softening the edges between panels the way a blur does
+
+use image::{Rgba, RgbaImage};
+
+// Get the value with half the counted values below it from a histogram of them
+fn histogram_median(histogram: &[u32; 256], count: u32) -> u8 {
+    let mut below = 0;
+    for (value, &times) in histogram.iter().enumerate() {
+        below += times;
+        if below * 2 > count {
+            return value as u8;
+        }
+    }
+    255
+}
+
+// Replace each channel of each pixel with its median over the square of pixels up to the radius around it, cut off
+// at the image's edges. Each row slides a histogram of the square along it, so the cost grows with the radius rather
+// than its square.
+pub fn median_filter(image: &RgbaImage, radius: u32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let mut filtered = RgbaImage::new(width, height);
+
+    for y in 0 .. height {
+        let (top, bottom) = (y.saturating_sub(radius), (y + radius).min(height - 1));
+        let mut histograms = [[0u32; 256]; 4];
+        let mut count = 0;
+        let column = |histograms: &mut [[u32; 256]; 4], x: u32, adding: bool| {
+            for row in top ..= bottom {
+                let pixel = image.get_pixel(x, row);
+                for channel in 0 .. 4 {
+                    let bin = &mut histograms[channel][pixel[channel] as usize];
+                    if adding { *bin += 1 } else { *bin -= 1 }
+                }
+            }
+            bottom - top + 1
+        };
+
+        for x in 0 ..= radius.min(width - 1) {
+            count += column(&mut histograms, x, true);
+        }
+        for x in 0 .. width {
+            if x > radius {
+                count -= column(&mut histograms, x - radius - 1, false);
+            }
+            if x > 0 && x + radius < width {
+                count += column(&mut histograms, x + radius, true);
+            }
+            let mut data = [0; 4];
+            for (channel, value) in data.iter_mut().enumerate() {
+                *value = histogram_median(&histograms[channel], count);
+            }
+            filtered.put_pixel(x, y, Rgba { data });
+        }
+    }
+    filtered
+}
@@ -0,0 +1,34 @@
+// Write every piece of a run as an ffmpeg concat script that shows each for a while in order, so storyboard panels can
+// be turned straight into an animatic with `ffmpeg -f concat -i montage.ffconcat`
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use atomic;
+
+// Write the pieces into a concat script at the path, each shown for the duration in seconds. Pieces are listed
+// relative to the script where they can be, which is how ffmpeg resolves them.
+pub fn write_montage(path: &Path, pieces: &[PathBuf], duration: f64) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => fs::canonicalize(dir)?,
+        _ => fs::canonicalize(".")?
+    };
+    // Quote each path between single quotes, ending the quotes around any quote in it
+    let listed = |piece: &Path| -> io::Result<String> {
+        let piece = fs::canonicalize(piece)?;
+        let relative = piece.strip_prefix(&dir).unwrap_or(&piece).to_string_lossy().replace('\'', "'\\''");
+        Ok(format!("file '{}'\n", relative))
+    };
+
+    let mut script = String::from("ffconcat version 1.0\n");
+    for piece in pieces {
+        script += &listed(piece)?;
+        script += &format!("duration {}\n", duration);
+    }
+    // The last piece's duration only counts if another file follows it, so list it again
+    if let Some(last) = pieces.last() {
+        script += &listed(last)?;
+    }
+    atomic::write(path, script.as_bytes())
+}
@@ -0,0 +1,163 @@
+// Name pieces from a template, optionally using the text of their largest heading
+
+use std::collections::HashSet;
+
+use image::RgbaImage;
+
+#[cfg(feature = "ocr")]
+use std::io::Write;
+#[cfg(feature = "ocr")]
+use std::process::{Command, Stdio};
+
+#[cfg(feature = "ocr")]
+use image::GenericImage;
+
+#[cfg(feature = "ocr")]
+use {estimate_background, luma};
+
+// How far a pixel's luma may stray from the background's before it counts as ink
+#[cfg(feature = "ocr")]
+const INK_TOLERANCE: i32 = 64;
+
+// Whether headings can be read, which needs the `ocr` feature and the `tesseract` command
+pub fn headings_supported() -> bool {
+    cfg!(feature = "ocr")
+}
+
+// Find the tallest band of rows with ink in them that is still short enough to be a line of text, taken as the
+// piece's heading
+#[cfg(feature = "ocr")]
+fn heading_band(image: &RgbaImage) -> Option<(u32, u32)> {
+    let background = luma(&estimate_background(image)) as i32;
+    let inked: Vec<bool> = (0 .. image.height())
+        .map(|y| (0 .. image.width()).any(|x| (luma(image.get_pixel(x, y)) as i32 - background).abs() > INK_TOLERANCE))
+        .collect();
+
+    let mut bands = Vec::new();
+    let mut start = None;
+    for (y, &ink) in inked.iter().enumerate() {
+        match (ink, start) {
+            (true, None) => start = Some(y as u32),
+            (false, Some(band_start)) => {
+                bands.push((band_start, y as u32));
+                start = None;
+            },
+            _ => {}
+        }
+    }
+    if let Some(band_start) = start {
+        bands.push((band_start, image.height()));
+    }
+
+    bands.into_iter()
+        .filter(|&(start, end)| end - start <= image.height() / 4)
+        .max_by_key(|&(start, end)| (end - start, u32::MAX - start))
+}
+
+// Read the heading of a piece with tesseract, if it has one
+#[cfg(feature = "ocr")]
+fn read_heading(image: &RgbaImage) -> Option<String> {
+    let (start, end) = heading_band(image)?;
+    let mut band = image.clone();
+    let band = image::DynamicImage::ImageRgba8(band.sub_image(0, start, image.width(), end - start).to_image());
+    let mut png = Vec::new();
+    band.save(&mut png, image::ImageFormat::PNG).ok()?;
+
+    let mut child = Command::new("tesseract").args(["stdin", "stdout", "--psm", "7"])
+        .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::null())
+        .spawn().ok()?;
+    child.stdin.take()?.write_all(&png).ok()?;
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(not(feature = "ocr"))]
+fn read_heading(_image: &RgbaImage) -> Option<String> {
+    None
+}
+
+// Turn text into lowercase words joined by dashes, fit for a file name
+pub fn slugify(text: &str) -> String {
+    text.split(|character: char| !character.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<String>>()
+        .join("-")
+}
+
+// The extensions a template may end with, which are left off since pieces get the extension of their format
+const EXTENSIONS: [&str; 7] = [".png", ".jpg", ".jpeg", ".bmp", ".tif", ".tiff", ".webp"];
+
+// Fill in a numbered field of a template, written as `{field}`, or as `{field:03}` to pad it with zeros to a width
+fn fill_number(template: &str, field: &str, value: usize) -> String {
+    let mut filled = template.replace(&format!("{{{}}}", field), &value.to_string());
+    let prefix = format!("{{{}:", field);
+    let mut searched = 0;
+    while let Some(start) = filled[searched ..].find(&prefix).map(|start| searched + start) {
+        let rest = &filled[start + prefix.len() ..];
+        let (end, width) = match rest.find('}').and_then(|end| rest[.. end].parse::<usize>().ok().map(|width| (end, width))) {
+            Some(found) => found,
+            // Leave a field that isn't a width as it is
+            None => {
+                searched = start + prefix.len();
+                continue;
+            }
+        };
+        filled = format!("{}{:0width$}{}", &filled[.. start], value, &rest[end + 1 ..], width = width);
+        searched = start;
+    }
+    filled
+}
+
+// Names the pieces of an image, making sure no two are the same
+pub struct Namer<'a> {
+    template: &'a str,
+    stem: String,
+    used: HashSet<String>
+}
+
+impl<'a> Namer<'a> {
+    // Name the pieces of the input with the given stem
+    pub fn new(template: &'a str, stem: &str) -> Namer<'a> {
+        let lower = template.to_lowercase();
+        let template = EXTENSIONS.iter()
+            .find(|extension| lower.ends_with(*extension))
+            .map_or(template, |extension| &template[.. template.len() - extension.len()]);
+        Namer { template, stem: stem.to_string(), used: HashSet::new() }
+    }
+
+    // Name a piece, without its extension, by filling in `{index}`, `{row}`, `{col}`, `{stem}` and `{heading}` in the
+    // template, given its place in reading order and its row and column
+    pub fn name(&mut self, index: usize, cell: (usize, usize), image: &RgbaImage) -> String {
+        self.name_coded(index, cell, image, None)
+    }
+
+    // Name a piece as above, also filling in `{code}` with the barcode it's anchored by, if it has one
+    pub fn name_coded(&mut self, index: usize, (row, column): (usize, usize), image: &RgbaImage, code: Option<&str>) -> String {
+        let mut name = fill_number(self.template, "index", index);
+        name = fill_number(&name, "row", row);
+        name = fill_number(&name, "col", column);
+        name = name.replace("{stem}", &self.stem);
+        if name.contains("{heading}") {
+            let heading = read_heading(image).map(|heading| slugify(&heading)).filter(|heading| !heading.is_empty());
+            name = name.replace("{heading}", &heading.unwrap_or_else(|| "untitled".to_string()));
+        }
+        if name.contains("{code}") {
+            let code = code.map(slugify).filter(|code| !code.is_empty());
+            name = name.replace("{code}", &code.unwrap_or_else(|| "uncoded".to_string()));
+        }
+
+        // Number repeated names, so one piece can't overwrite another
+        let mut unique = name.clone();
+        let mut count = 2;
+        while self.used.contains(&unique) {
+            unique = format!("{}-{}", name, count);
+            count += 1;
+        }
+        self.used.insert(unique.clone());
+        unique
+    }
+}
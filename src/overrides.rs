@@ -0,0 +1,367 @@
+// Read a sidecar file of settings to use for particular inputs instead of the command line's, the settings files
+// that apply to every input in a directory and the config file of defaults for a run. Only the command line reads
+// the files, so that's left out of builds for the browser.
+
+#[cfg(feature = "cli")]
+use std::fs::{self, File};
+#[cfg(feature = "cli")]
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+// The file of settings for the inputs in the directory it's in and the directories inside that
+#[cfg(feature = "cli")]
+pub const DIRECTORY_FILE: &str = ".autoguillotine.toml";
+
+// The file of defaults for a run, found in the working directory unless another is given
+#[cfg(feature = "cli")]
+pub const CONFIG_FILE: &str = "autoguillotine.toml";
+
+// Settings to apply to the inputs whose names match a pattern
+#[derive(Clone, Serialize)]
+pub struct Override {
+    pub pattern: String,
+    // Settings in the same `name=value,name=value` form as `--fallback`
+    pub settings: String
+}
+
+// Whether a name matches a pattern, where `*` matches any run of characters and `?` matches any one
+fn glob_matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0 ..= name.len()).any(|skip| glob_matches(&pattern[1 ..], &name[skip ..])),
+        Some(&character) => match name.first() {
+            Some(&first) if character == '?' || character == first => glob_matches(&pattern[1 ..], &name[1 ..]),
+            _ => false
+        }
+    }
+}
+
+// Whether a pattern matches an input, by its whole name or just its file name
+fn pattern_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let file_name = Path::new(name).file_name().map(|file_name| file_name.to_string_lossy().into_owned());
+    [Some(name.to_string()), file_name].iter().flatten()
+        .any(|name| glob_matches(&pattern, &name.chars().collect::<Vec<char>>()))
+}
+
+impl Override {
+    // Whether the override applies to an input
+    pub fn matches(&self, name: &str) -> bool {
+        pattern_matches(&self.pattern, name)
+    }
+}
+
+// Read an overrides file, where each line is a pattern followed by comma-separated settings, for example
+// `page-0*.png,threshold=20,min-gap=2`, and blank lines and lines starting with `#` are skipped
+#[cfg(feature = "cli")]
+pub fn read_overrides(path: &Path) -> ::std::io::Result<Vec<Override>> {
+    let mut overrides = Vec::new();
+
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.splitn(2, ',');
+        overrides.push(Override {
+            pattern: fields.next().unwrap().trim().to_string(),
+            settings: fields.next().unwrap_or("").to_string()
+        });
+    }
+
+    Ok(overrides)
+}
+
+// The settings for the inputs in a directory and the directories inside it, as setting names and their values, where
+// a setting without one is a switch
+#[derive(Clone, Serialize)]
+pub struct DirectorySettings {
+    pub dir: PathBuf,
+    pub settings: Vec<(String, Option<String>)>,
+    // The named profiles to use for the inputs whose names match patterns, in the order they were given
+    pub profiles: Vec<PatternProfile>
+}
+
+// A named profile of settings for the inputs whose names match a pattern, such as `*.webp` for webtoons
+#[derive(Clone, Serialize)]
+pub struct PatternProfile {
+    pub pattern: String,
+    pub profile: String,
+    pub settings: Vec<(String, Option<String>)>
+}
+
+impl DirectorySettings {
+    // Get the profile for an input, the first whose pattern matches its name
+    pub fn profile_for(&self, name: &str) -> Option<&PatternProfile> {
+        self.profiles.iter().find(|profile| pattern_matches(&profile.pattern, name))
+    }
+}
+
+// Find the settings files in the directories an input is in, from the outermost in, so the inner ones' settings win
+#[cfg(feature = "cli")]
+pub fn find_directory_files(input: &Path) -> Vec<PathBuf> {
+    let input = match input.canonicalize() {
+        Ok(input) => input,
+        Err(_) => return Vec::new()
+    };
+    let mut files: Vec<PathBuf> = input.ancestors().skip(1)
+        .map(|dir| dir.join(DIRECTORY_FILE))
+        .filter(|file| file.is_file())
+        .collect();
+    files.reverse();
+    files
+}
+
+// Get the value of a setting written in TOML, which can be a string, a number, a boolean or a list of those, where a
+// list becomes its items separated by commas as on the command line
+#[cfg(feature = "cli")]
+fn toml_value(value: &str) -> Result<Option<String>, String> {
+    let value = value.trim();
+    if let Some(items) = value.strip_prefix('[').and_then(|value| value.strip_suffix(']')) {
+        let items = items.split(',').map(str::trim).filter(|item| !item.is_empty())
+            .map(|item| toml_value(item).and_then(|item| item.ok_or_else(|| format!("a list can't hold {}", value))))
+            .collect::<Result<Vec<String>, String>>()?;
+        return Ok(Some(items.join(",")));
+    }
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return Ok(Some(value[1 .. value.len() - 1].to_string()));
+        }
+    }
+    match value {
+        "true" => Ok(None),
+        "" | "false" => Err(format!("a setting can only be switched on, not set to {}", if value.is_empty() { "nothing" } else { value })),
+        _ => Ok(Some(value.to_string()))
+    }
+}
+
+// Which table of a settings file the lines are in
+#[cfg(feature = "cli")]
+enum Table {
+    Top,
+    // A profile, by its index in the named ones
+    Profile(usize),
+    Patterns,
+    // A directory's settings, by its index in the ones given
+    Directory(usize)
+}
+
+// What a settings file holds: the settings at the top, the named profiles picked for inputs by pattern and the
+// settings for particular directories, by their paths relative to the file
+#[cfg(feature = "cli")]
+struct SettingsFile {
+    settings: Vec<(String, Option<String>)>,
+    profiles: Vec<PatternProfile>,
+    directories: Vec<(String, Vec<(String, Option<String>)>)>
+}
+
+// Read a settings file, which holds the same settings as the command line without their dashes, one to a line like
+// `threshold = 20`, `strategy = "gutter"`, `flatten-shading = true` or `weights = [1, 1, 1]`, with comments starting
+// with `#`. Named profiles of settings go in tables like `[profiles.webtoon]`, and a `[patterns]` table picks the
+// profile for the inputs whose names match each pattern, like `"*.webp" = "webtoon"`. Tables like
+// `[directories."scans/photos"]` hold settings for the inputs in a directory.
+#[cfg(feature = "cli")]
+fn read_settings_file(path: &Path) -> Result<SettingsFile, String> {
+    let text = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    let mut settings = Vec::new();
+    let mut named: Vec<(String, Vec<(String, Option<String>)>)> = Vec::new();
+    let mut patterns: Vec<(usize, String, String)> = Vec::new();
+    let mut directories: Vec<(String, Vec<(String, Option<String>)>)> = Vec::new();
+    let mut table = Table::Top;
+
+    for (number, line) in text.lines().enumerate() {
+        // A `#` inside a quoted value isn't a comment
+        let mut quote = None;
+        let end = line.char_indices()
+            .find(|&(_, character)| {
+                match quote {
+                    Some(open) if character == open => quote = None,
+                    None if character == '"' || character == '\'' => quote = Some(character),
+                    _ => {}
+                }
+                quote.is_none() && character == '#'
+            })
+            .map_or(line.len(), |(index, _)| index);
+        let line = line[.. end].trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let at_line = |error: String| format!("line {}: {}", number + 1, error);
+        if let Some(header) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            let header = header.trim();
+            table = if header == "patterns" {
+                Table::Patterns
+            } else if let Some(profile) = header.strip_prefix("profiles.") {
+                let profile = profile.trim().trim_matches('"').to_string();
+                if named.iter().any(|&(ref name, _)| *name == profile) {
+                    return Err(at_line(format!("profile {} is given twice", profile)));
+                }
+                named.push((profile, Vec::new()));
+                Table::Profile(named.len() - 1)
+            } else if let Some(dir) = header.strip_prefix("directories.") {
+                directories.push((dir.trim().trim_matches('"').to_string(), Vec::new()));
+                Table::Directory(directories.len() - 1)
+            } else {
+                return Err(at_line(format!("expected [profiles.NAME], [patterns] or [directories.PATH]: {}", line)));
+            };
+            continue;
+        }
+
+        let mut parts = line.splitn(2, '=');
+        let name = parts.next().unwrap().trim().trim_matches('"').to_string();
+        let value = parts.next().ok_or_else(|| at_line(format!("expected a setting like name = value: {}", line)))?;
+        let value = toml_value(value).map_err(at_line)?;
+        match table {
+            Table::Top => settings.push((name, value)),
+            Table::Profile(index) => named[index].1.push((name, value)),
+            Table::Directory(index) => directories[index].1.push((name, value)),
+            Table::Patterns => match value {
+                Some(profile) => patterns.push((number + 1, name, profile)),
+                None => return Err(at_line(format!("expected the name of a profile for {}", name)))
+            }
+        }
+    }
+
+    let profiles = patterns.into_iter()
+        .map(|(number, pattern, profile)| {
+            match named.iter().find(|&&(ref name, _)| *name == profile) {
+                Some(&(_, ref settings)) => Ok(PatternProfile { pattern, profile, settings: settings.clone() }),
+                None => Err(format!("line {}: no profile called {}", number, profile))
+            }
+        })
+        .collect::<Result<Vec<PatternProfile>, String>>()?;
+
+    Ok(SettingsFile { settings, profiles, directories })
+}
+
+// Read a directory's settings file, which can't have tables for other directories
+#[cfg(feature = "cli")]
+pub fn read_directory_settings(path: &Path) -> Result<DirectorySettings, String> {
+    let file = read_settings_file(path)?;
+    if !file.directories.is_empty() {
+        return Err(format!("[directories] tables only go in {}, not {}", CONFIG_FILE, DIRECTORY_FILE));
+    }
+    Ok(DirectorySettings { dir: path.parent().unwrap().to_path_buf(), settings: file.settings, profiles: file.profiles })
+}
+
+// The defaults a config file gives: settings to put before the command line's, and settings for directories
+#[cfg(feature = "cli")]
+pub struct ConfigFile {
+    pub settings: Vec<(String, Option<String>)>,
+    pub directories: Vec<DirectorySettings>
+}
+
+// Read a config file, finding the directories it has settings for from where it is. Profiles and patterns go in the
+// directories' own settings files instead, where it's clear which inputs they're for.
+#[cfg(feature = "cli")]
+pub fn read_config_file(path: &Path) -> Result<ConfigFile, String> {
+    let file = read_settings_file(path)?;
+    if !file.profiles.is_empty() {
+        return Err(format!("[patterns] tables go in a {} in the inputs' directories", DIRECTORY_FILE));
+    }
+    let base = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+    let directories = file.directories.into_iter()
+        .map(|(dir, settings)| {
+            let dir = base.join(&dir).canonicalize().map_err(|error| format!("directory {}: {}", dir, error))?;
+            Ok(DirectorySettings { dir, settings, profiles: Vec::new() })
+        })
+        .collect::<Result<Vec<DirectorySettings>, String>>()?;
+    Ok(ConfigFile { settings: file.settings, directories })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pattern_matches, Override};
+
+    #[test]
+    fn patterns_match_whole_names_or_file_names() {
+        assert!(pattern_matches("page-0*.png", "scans/page-01.png"));
+        assert!(pattern_matches("scans/*", "scans/page-01.png"));
+        assert!(pattern_matches("page-??.png", "page-10.png"));
+        assert!(!pattern_matches("page-0*.png", "page-10.png"));
+        assert!(!pattern_matches("page-?.png", "page-10.png"));
+        assert!(Override { pattern: "*.webp".to_string(), settings: String::new() }.matches("strip.webp"));
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn toml_values_become_command_line_values() {
+        use super::toml_value;
+
+        assert_eq!(toml_value(" 20 "), Ok(Some("20".to_string())));
+        assert_eq!(toml_value("\"gutter\""), Ok(Some("gutter".to_string())));
+        assert_eq!(toml_value("'a, b'"), Ok(Some("a, b".to_string())));
+        assert_eq!(toml_value("[1, 1, 2]"), Ok(Some("1,1,2".to_string())));
+        assert_eq!(toml_value("true"), Ok(None));
+        assert!(toml_value("false").is_err());
+        assert!(toml_value("[true]").is_err());
+    }
+
+    // Write a file into a directory of the test's own, which is removed after
+    #[cfg(feature = "cli")]
+    fn with_file<T, F: FnOnce(&::std::path::Path) -> T>(test: &str, name: &str, text: &str, read: F) -> T {
+        let dir = ::std::env::temp_dir().join(format!("autoguillotine-test-{}-{}", ::std::process::id(), test));
+        ::std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        ::std::fs::write(&path, text).unwrap();
+        let result = read(&path);
+        ::std::fs::remove_dir_all(&dir).unwrap();
+        result
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn settings_file_is_read_into_settings_profiles_and_directories() {
+        use super::read_settings_file;
+
+        let text = "# Defaults\n\
+                    threshold = 20\n\
+                    strategy = \"gutter\" # after a value\n\
+                    weights = [1, 1, 2]\n\
+                    flatten-shading = true\n\
+                    \n\
+                    [profiles.webtoon]\n\
+                    min-size = 50\n\
+                    \n\
+                    [patterns]\n\
+                    \"*.webp\" = \"webtoon\"\n\
+                    \n\
+                    [directories.\"scans/photos\"]\n\
+                    jpeg = 80\n";
+        let file = with_file("settings", "autoguillotine.toml", text, read_settings_file).unwrap();
+        let setting = |name: &str, value: Option<&str>| (name.to_string(), value.map(str::to_string));
+        assert_eq!(file.settings, vec![
+            setting("threshold", Some("20")),
+            setting("strategy", Some("gutter")),
+            setting("weights", Some("1,1,2")),
+            setting("flatten-shading", None)
+        ]);
+        assert_eq!(file.profiles.len(), 1);
+        assert_eq!((file.profiles[0].pattern.as_str(), file.profiles[0].profile.as_str()), ("*.webp", "webtoon"));
+        assert_eq!(file.profiles[0].settings, vec![setting("min-size", Some("50"))]);
+        assert_eq!(file.directories, vec![("scans/photos".to_string(), vec![setting("jpeg", Some("80"))])]);
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn settings_file_naming_a_missing_profile_is_an_error() {
+        use super::read_settings_file;
+
+        let read = with_file("missing", "autoguillotine.toml", "[patterns]\n\"*.png\" = \"missing\"\n", read_settings_file);
+        assert_eq!(read.err(), Some("line 2: no profile called missing".to_string()));
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn overrides_file_is_read_into_patterns_and_settings() {
+        use super::read_overrides;
+
+        let text = "# Pages with thin gutters\n\npage-0*.png,threshold=20,min-gap=2\ncover.png\n";
+        let overrides = with_file("overrides", "overrides.txt", text, read_overrides).unwrap();
+        let read: Vec<(&str, &str)> = overrides.iter().map(|matching| (matching.pattern.as_str(), matching.settings.as_str())).collect();
+        assert_eq!(read, vec![("page-0*.png", "threshold=20,min-gap=2"), ("cover.png", "")]);
+    }
+}
@@ -0,0 +1,183 @@
+// Pack pieces back onto fixed-size sheets, for printing them
+
+use std::fs::{create_dir_all, read_dir, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use image::{GenericImage, Rgba, RgbaImage};
+use serde_json;
+
+use atomic;
+use {encode_page, output_image};
+
+// The size of the sheets and how the pieces are spaced on them
+pub struct PackOptions {
+    pub width: u32,
+    pub height: u32,
+    // The space left blank around the edge of each sheet
+    pub margin: u32,
+    // The space left between pieces
+    pub spacing: u32,
+    // Where to save the sheets
+    pub output: PathBuf
+}
+
+impl Default for PackOptions {
+    fn default() -> PackOptions {
+        // A4 at 150 DPI
+        PackOptions { width: 1240, height: 1754, margin: 30, spacing: 10, output: PathBuf::from("packed") }
+    }
+}
+
+// The parts of a manifest that say which files the pieces are in
+#[derive(Deserialize)]
+struct Files {
+    pieces: Vec<ListedPiece>
+}
+
+#[derive(Deserialize)]
+struct ListedPiece {
+    file: String
+}
+
+// Where a piece was put
+#[derive(Serialize)]
+struct Placement {
+    file: String,
+    sheet: usize,
+    x: u32,
+    y: u32
+}
+
+// Whether a file starts like an image
+fn looks_like_image(path: &Path) -> bool {
+    let mut magic = [0; 16];
+    match File::open(path).and_then(|mut file| file.read(&mut magic)) {
+        Ok(read) => image::guess_format(&magic[.. read]).is_ok(),
+        Err(_) => false
+    }
+}
+
+// Find the pieces to pack, either the files listed in a manifest or every image in a directory, in name order
+fn piece_paths(path: &Path) -> Result<Vec<PathBuf>, String> {
+    if path.is_dir() {
+        let mut paths: Vec<PathBuf> = read_dir(path).map_err(|error| format!("{}: {}", path.display(), error))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && looks_like_image(path))
+            .collect();
+        paths.sort();
+        return Ok(paths);
+    }
+
+    let mut data = Vec::new();
+    File::open(path).and_then(|mut file| file.read_to_end(&mut data)).map_err(|error| format!("{}: {}", path.display(), error))?;
+    let files: Files = serde_json::from_slice(&data).map_err(|error| format!("{}: {}", path.display(), error))?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    Ok(files.pieces.into_iter().map(|piece| dir.join(piece.file)).collect())
+}
+
+// Place rectangles on as few sheets as a simple shelf packing manages: the tallest go first, left to right, starting
+// a new row when one is full and a new sheet when the rows are.
+// Returns the sheet and position of each rectangle, in the order given, or None for those too big for a sheet.
+pub fn shelf_pack(sizes: &[(u32, u32)], width: u32, height: u32, spacing: u32) -> Vec<Option<(usize, u32, u32)>> {
+    // Tallest first, keeping the given order between rectangles of the same height
+    let mut order: Vec<usize> = (0 .. sizes.len()).collect();
+    order.sort_by_key(|&i| ::std::cmp::Reverse(sizes[i].1));
+
+    let mut placements = vec![None; sizes.len()];
+    // The current sheet, where the next rectangle goes on the current row, and how tall the row is
+    let (mut sheet, mut x, mut y, mut row_height) = (0, 0, 0, 0);
+
+    for i in order {
+        let (piece_width, piece_height) = sizes[i];
+        if piece_width > width || piece_height > height {
+            continue;
+        }
+
+        if x > 0 && x + piece_width > width {
+            x = 0;
+            y += row_height + spacing;
+            row_height = 0;
+        }
+        if y + piece_height > height {
+            sheet += 1;
+            x = 0;
+            y = 0;
+            row_height = 0;
+        }
+
+        placements[i] = Some((sheet, x, y));
+        x += piece_width + spacing;
+        row_height = row_height.max(piece_height);
+    }
+
+    placements
+}
+
+// Pack the pieces from each input onto sheets.
+// Returns whether every piece fit.
+pub fn pack(inputs: &[String], options: &PackOptions) -> bool {
+    let mut ok = true;
+    let mut pieces = Vec::new();
+    for input in inputs {
+        let paths = match piece_paths(Path::new(input)) {
+            Ok(paths) => paths,
+            Err(error) => {
+                eprintln!("Warning: couldn't read {}", error);
+                ok = false;
+                continue;
+            }
+        };
+        for path in paths {
+            match image::open(&path) {
+                Ok(image) => pieces.push((path, image.to_rgba())),
+                Err(error) => {
+                    eprintln!("Warning: couldn't decode {}: {}", path.display(), error);
+                    ok = false;
+                }
+            }
+        }
+    }
+
+    let sizes: Vec<(u32, u32)> = pieces.iter().map(|(_, image)| image.dimensions()).collect();
+    let usable_width = options.width.saturating_sub(options.margin * 2);
+    let usable_height = options.height.saturating_sub(options.margin * 2);
+    let positions = shelf_pack(&sizes, usable_width, usable_height, options.spacing);
+
+    let sheet_count = positions.iter().flatten().map(|&(sheet, _, _)| sheet + 1).max().unwrap_or(1);
+    let mut sheets = vec![RgbaImage::from_pixel(options.width, options.height, Rgba { data: [255, 255, 255, 255] }); sheet_count];
+    let mut placements = Vec::new();
+
+    for ((path, image), position) in pieces.iter().zip(positions) {
+        match position {
+            Some((sheet, x, y)) => {
+                sheets[sheet].copy_from(image, options.margin + x, options.margin + y);
+                placements.push(Placement {
+                    file: path.display().to_string(),
+                    sheet,
+                    x: options.margin + x,
+                    y: options.margin + y
+                });
+            },
+            None => {
+                eprintln!("Warning: {} is {}x{}, which doesn't fit on a {}x{} sheet inside its margins",
+                    path.display(), image.width(), image.height(), options.width, options.height);
+                ok = false;
+            }
+        }
+    }
+    // List the placements sheet by sheet, in the order they were placed
+    placements.sort_by_key(|placement| (placement.sheet, placement.y, placement.x));
+
+    create_dir_all(&options.output).unwrap();
+    for (i, sheet) in sheets.iter().enumerate() {
+        let path = options.output.join(format!("{}.png", i));
+        println!("Saving {}...", path.display());
+        atomic::write(&path, &encode_page(&output_image(sheet, false)).data).unwrap();
+    }
+    let path = options.output.join("pack.json");
+    println!("Saving {}...", path.display());
+    atomic::write(&path, serde_json::to_string_pretty(&placements).unwrap().as_bytes()).unwrap();
+
+    ok
+}
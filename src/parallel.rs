@@ -0,0 +1,42 @@
+// Cut the parts of an image in parallel with rayon, or one after another where there are no threads to run them on,
+// as on the web, through the same calls either way
+
+#[cfg(feature = "parallel")]
+pub use rayon::join;
+#[cfg(feature = "parallel")]
+pub use rayon::prelude::*;
+
+#[cfg(not(feature = "parallel"))]
+use std::slice;
+
+#[cfg(not(feature = "parallel"))]
+pub fn join<A: FnOnce() -> RA, B: FnOnce() -> RB, RA, RB>(a: A, b: B) -> (RA, RB) {
+    (a(), b())
+}
+
+#[cfg(not(feature = "parallel"))]
+pub trait IntoParallelIterator: IntoIterator + Sized {
+    fn into_par_iter(self) -> Self::IntoIter {
+        self.into_iter()
+    }
+}
+
+#[cfg(not(feature = "parallel"))]
+impl<T: IntoIterator> IntoParallelIterator for T {}
+
+#[cfg(not(feature = "parallel"))]
+pub trait ParallelSlice<T> {
+    fn par_iter(&self) -> slice::Iter<T>;
+    fn par_chunks(&self, size: usize) -> slice::Chunks<T>;
+}
+
+#[cfg(not(feature = "parallel"))]
+impl<T> ParallelSlice<T> for [T] {
+    fn par_iter(&self) -> slice::Iter<T> {
+        self.iter()
+    }
+
+    fn par_chunks(&self, size: usize) -> slice::Chunks<T> {
+        self.chunks(size)
+    }
+}
@@ -0,0 +1,105 @@
+// Read the pages of PDFs, which scans are often kept in, one at a time with poppler's pdfinfo, pdftoppm and pdfimages
+
+use std::path::Path;
+
+#[cfg(feature = "pdf")]
+use std::fs;
+#[cfg(feature = "pdf")]
+use std::process::{self, Command, Stdio};
+#[cfg(feature = "pdf")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "pdf")]
+use image;
+use image::RgbaImage;
+
+// Whether PDFs can be read, which needs the `pdf` feature and poppler's commands
+pub fn pdf_supported() -> bool {
+    cfg!(feature = "pdf")
+}
+
+// Whether a path looks like a PDF
+pub fn is_pdf(path: &Path) -> bool {
+    path.extension().map(|extension| extension.eq_ignore_ascii_case("pdf")).unwrap_or(false)
+}
+
+// Run a command, giving what it wrote to stdout, or why it failed
+#[cfg(feature = "pdf")]
+fn run(command: &mut Command) -> Result<Vec<u8>, String> {
+    let name = format!("{:?}", command.get_program());
+    let output = command.stdin(Stdio::null()).output().map_err(|error| format!("failed to run {}: {}", name, error))?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(format!("{} exited with {}: {}", name, output.status, String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+// Count a PDF's pages, from what pdfinfo says about it
+#[cfg(feature = "pdf")]
+pub fn page_count(path: &Path) -> Result<usize, String> {
+    let info = run(Command::new("pdfinfo").arg(path))?;
+    String::from_utf8_lossy(&info).lines()
+        .find_map(|line| line.strip_prefix("Pages:").and_then(|pages| pages.trim().parse().ok()))
+        .ok_or_else(|| "pdfinfo didn't say how many pages it has".to_string())
+}
+
+#[cfg(not(feature = "pdf"))]
+pub fn page_count(_path: &Path) -> Result<usize, String> {
+    Err("reading PDFs needs the pdf feature".to_string())
+}
+
+// Render a page, numbered from 1, at a number of dots per inch. pdftoppm writes a single page to stdout when it isn't
+// given where to write it.
+#[cfg(feature = "pdf")]
+fn render_page(path: &Path, page: usize, dpi: u32) -> Result<RgbaImage, String> {
+    let page = page.to_string();
+    let data = run(Command::new("pdftoppm").arg("-png").arg("-singlefile").arg("-r").arg(dpi.to_string())
+        .arg("-f").arg(&page).arg("-l").arg(&page).arg(path))?;
+    image::load_from_memory(&data).map(|image| image.to_rgba()).map_err(|error| error.to_string())
+}
+
+// Extract the biggest image drawn on a page, numbered from 1, which for a scan is the scan itself at the resolution it
+// was scanned at, or nothing if there are none. pdfimages writes them into a directory of their own, which is removed
+// after, and writes each image's soft mask after it, so the first of the biggest is taken.
+#[cfg(feature = "pdf")]
+fn extract_page(path: &Path, page: usize) -> Result<Option<RgbaImage>, String> {
+    static EXTRACTED: AtomicUsize = AtomicUsize::new(0);
+    let dir = ::std::env::temp_dir().join(format!("autoguillotine-pdf-{}-{}", process::id(), EXTRACTED.fetch_add(1, Ordering::SeqCst)));
+    fs::create_dir_all(&dir).map_err(|error| error.to_string())?;
+    let page = page.to_string();
+    let extracted = run(Command::new("pdfimages").arg("-png").arg("-f").arg(&page).arg("-l").arg(&page).arg(path).arg(dir.join("image")))
+        .and_then(|_| {
+            let mut files: Vec<_> = fs::read_dir(&dir).map_err(|error| error.to_string())?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect();
+            files.sort();
+            let mut biggest: Option<RgbaImage> = None;
+            for file in files {
+                let image = image::open(&file).map_err(|error| error.to_string())?.to_rgba();
+                let area = |image: &RgbaImage| image.width() as u64 * image.height() as u64;
+                if biggest.as_ref().map_or(true, |biggest| area(&image) > area(biggest)) {
+                    biggest = Some(image);
+                }
+            }
+            Ok(biggest)
+        });
+    let _ = fs::remove_dir_all(&dir);
+    extracted
+}
+
+// Read a page, numbered from 1, as its biggest image if `images` and it has any, or else rendered at `dpi`
+#[cfg(feature = "pdf")]
+pub fn read_page(path: &Path, page: usize, dpi: u32, images: bool) -> Result<RgbaImage, String> {
+    if images {
+        if let Some(image) = extract_page(path, page)? {
+            return Ok(image);
+        }
+    }
+    render_page(path, page, dpi)
+}
+
+#[cfg(not(feature = "pdf"))]
+pub fn read_page(_path: &Path, _page: usize, _dpi: u32, _images: bool) -> Result<RgbaImage, String> {
+    Err("reading PDFs needs the pdf feature".to_string())
+}
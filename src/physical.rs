@@ -0,0 +1,143 @@
+// Cut scans into pieces of a fixed physical size, using the resolution in their metadata
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+// How much of the start of a file to look through for its resolution
+const HEADER_BYTES: u64 = 65536;
+
+// The units a physical size can be given in
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Unit {
+    Inches,
+    Centimetres
+}
+
+// The size of each piece, like 4x6in or 10x15cm
+#[derive(Clone, Copy, Serialize)]
+pub struct PieceSize {
+    pub width: f64,
+    pub height: f64,
+    pub unit: Unit
+}
+
+impl FromStr for PieceSize {
+    type Err = ();
+
+    fn from_str(string: &str) -> Result<PieceSize, ()> {
+        let (size, unit) = if let Some(size) = string.strip_suffix("in") {
+            (size, Unit::Inches)
+        } else if let Some(size) = string.strip_suffix("cm") {
+            (size, Unit::Centimetres)
+        } else {
+            return Err(());
+        };
+        let mut sides = size.splitn(2, 'x').map(|side| side.parse::<f64>());
+        match (sides.next(), sides.next()) {
+            (Some(Ok(width)), Some(Ok(height))) if width > 0.0 && height > 0.0 => Ok(PieceSize { width, height, unit }),
+            _ => Err(())
+        }
+    }
+}
+
+impl PieceSize {
+    // Get the size in pixels at the given number of dots per inch
+    pub fn pixels(&self, dpi: f64) -> (u32, u32) {
+        let inches = match self.unit {
+            Unit::Inches => 1.0,
+            Unit::Centimetres => 1.0 / 2.54
+        };
+        ((self.width * inches * dpi).round() as u32, (self.height * inches * dpi).round() as u32)
+    }
+}
+
+fn read_u16(data: &[u8]) -> u32 {
+    (data[0] as u32) << 8 | data[1] as u32
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    read_u16(data) << 16 | read_u16(&data[2 ..])
+}
+
+// Find the resolution in a PNG's pHYs chunk, which comes before the image data
+fn png_dpi(data: &[u8]) -> Option<f64> {
+    let mut offset = 8;
+    while offset + 8 <= data.len() {
+        let length = read_u32(&data[offset ..]) as usize;
+        let kind = &data[offset + 4 .. offset + 8];
+        let chunk = data.get(offset + 8 .. offset + 8 + length)?;
+        match kind {
+            // The resolution is in pixels per metre when the unit is 1, and only gives the aspect ratio otherwise
+            b"pHYs" if length == 9 && chunk[8] == 1 => return Some(read_u32(chunk) as f64 * 0.0254),
+            b"IDAT" | b"IEND" => return None,
+            _ => offset += 12 + length
+        }
+    }
+    None
+}
+
+// Find the resolution in a JPEG's JFIF segment
+fn jpeg_dpi(data: &[u8]) -> Option<f64> {
+    let mut offset = 2;
+    while offset + 4 <= data.len() && data[offset] == 0xff {
+        let marker = data[offset + 1];
+        let length = read_u16(&data[offset + 2 ..]) as usize;
+        let segment = data.get(offset + 4 .. offset + 2 + length)?;
+        if marker == 0xe0 && segment.starts_with(b"JFIF\0") && segment.len() >= 12 {
+            let density = read_u16(&segment[8 ..]) as f64;
+            // The density is per inch when the unit is 1 and per centimetre when it's 2, and only gives the aspect
+            // ratio otherwise
+            return match segment[7] {
+                1 => Some(density),
+                2 => Some(density * 2.54),
+                _ => None
+            };
+        }
+        // The image data starts at the start of scan
+        if marker == 0xda {
+            return None;
+        }
+        offset += 2 + length;
+    }
+    None
+}
+
+// Read the horizontal resolution of a PNG or JPEG from its metadata, if it has any
+pub fn read_dpi(path: &Path) -> Option<f64> {
+    let mut data = Vec::new();
+    File::open(path).ok()?.take(HEADER_BYTES).read_to_end(&mut data).ok()?;
+
+    let dpi = if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        png_dpi(&data)
+    } else if data.starts_with(&[0xff, 0xd8]) {
+        jpeg_dpi(&data)
+    } else {
+        None
+    };
+    dpi.filter(|&dpi| dpi > 0.0)
+}
+
+// Lay out pieces of the given size in a grid from the top left of the image, turning them sideways if more whole
+// pieces fit that way. Only whole pieces are kept, so any strips left along the right and bottom edges are dropped.
+pub fn grid(width: u32, height: u32, piece_width: u32, piece_height: u32) -> Vec<(u32, u32, u32, u32)> {
+    if piece_width == 0 || piece_height == 0 {
+        return Vec::new();
+    }
+    let count = |piece_width, piece_height| (width / piece_width) * (height / piece_height);
+    let (piece_width, piece_height) = if count(piece_height, piece_width) > count(piece_width, piece_height) {
+        (piece_height, piece_width)
+    } else {
+        (piece_width, piece_height)
+    };
+
+    let mut rectangles = Vec::new();
+    for row in 0 .. height / piece_height {
+        for column in 0 .. width / piece_width {
+            rectangles.push((column * piece_width, row * piece_height, piece_width, piece_height));
+        }
+    }
+    rectangles
+}
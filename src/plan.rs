@@ -0,0 +1,62 @@
+// Save the rectangles one image was cut into as a plan, and cut other images with the same layout into the same
+// rectangles without measuring them
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde_json;
+
+use atomic;
+
+// A rectangle of the image a plan was made from
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct PlanPiece {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32
+}
+
+// The size of the image a plan was made from and the rectangles it was cut into, in reading order
+#[derive(Serialize, Deserialize)]
+pub struct Plan {
+    pub width: u32,
+    pub height: u32,
+    pub pieces: Vec<PlanPiece>
+}
+
+impl Plan {
+    // Make a plan from the x, y, width and height of each piece an image was cut into
+    pub fn new(width: u32, height: u32, rectangles: &[(u32, u32, u32, u32)]) -> Plan {
+        let pieces = rectangles.iter().map(|&(x, y, width, height)| PlanPiece { x, y, width, height }).collect();
+        Plan { width, height, pieces }
+    }
+
+    // Get the plan's rectangles as x, y, width and height in an image of the given size, scaled to fit if it isn't the
+    // size the plan was made from, as scans of the same layout often differ by a few pixels. Rectangles that don't
+    // reach into the image are left out.
+    pub fn rectangles(&self, width: u32, height: u32) -> Vec<(u32, u32, u32, u32)> {
+        let scale_x = width as f64 / self.width.max(1) as f64;
+        let scale_y = height as f64 / self.height.max(1) as f64;
+        let scaled = |value: u32, scale: f64, size: u32| ((value as f64 * scale).round() as u32).min(size);
+        self.pieces.iter()
+            .map(|piece| {
+                let (left, top) = (scaled(piece.x, scale_x, width), scaled(piece.y, scale_y, height));
+                let right = scaled(piece.x.saturating_add(piece.width), scale_x, width);
+                let bottom = scaled(piece.y.saturating_add(piece.height), scale_y, height);
+                (left, top, right.saturating_sub(left), bottom.saturating_sub(top))
+            })
+            .filter(|&(_, _, width, height)| width > 0 && height > 0)
+            .collect()
+    }
+
+    pub fn read(path: &Path) -> Result<Plan, String> {
+        let data = fs::read(path).map_err(|error| error.to_string())?;
+        serde_json::from_slice(&data).map_err(|error| error.to_string())
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        atomic::write(path, serde_json::to_string_pretty(self).unwrap().as_bytes())
+    }
+}
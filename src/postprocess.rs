@@ -0,0 +1,411 @@
+// Tidy up pieces after they've been cut, independently of how they were found
+
+use std::fs;
+use std::path::Path;
+
+use image::{self, GenericImage, RgbaImage};
+use image::imageops::FilterType;
+use rayon::prelude::*;
+use serde_json;
+
+use deskew::{rotate, skew_angle};
+use stats::laplacian_variance;
+use {estimate_background, Piece};
+
+// How to sample pixels when scaling, where nearest keeps pixel art's exact colours and Lanczos suits photos
+#[derive(Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Filter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    #[default]
+    Lanczos
+}
+
+impl Filter {
+    fn filter_type(self) -> FilterType {
+        match self {
+            Filter::Nearest => FilterType::Nearest,
+            Filter::Triangle => FilterType::Triangle,
+            Filter::CatmullRom => FilterType::CatmullRom,
+            Filter::Gaussian => FilterType::Gaussian,
+            Filter::Lanczos => FilterType::Lanczos3
+        }
+    }
+}
+
+impl ::std::str::FromStr for Filter {
+    type Err = ();
+
+    fn from_str(string: &str) -> Result<Filter, ()> {
+        match string {
+            "nearest" => Ok(Filter::Nearest),
+            "triangle" => Ok(Filter::Triangle),
+            "catmull-rom" => Ok(Filter::CatmullRom),
+            "gaussian" => Ok(Filter::Gaussian),
+            "lanczos" => Ok(Filter::Lanczos),
+            _ => Err(())
+        }
+    }
+}
+
+// What fills the margin added around a piece for bleed
+#[derive(Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BleedFill {
+    // The nearest pixel on the piece's edge, stretched outwards
+    #[default]
+    Edge,
+    // The piece's background colour
+    Background,
+    // The pixels around the piece in the image it was cut from, up to the image's edges, which is taken when it's cut
+    // rather than added here
+    Image
+}
+
+impl ::std::str::FromStr for BleedFill {
+    type Err = ();
+
+    fn from_str(string: &str) -> Result<BleedFill, ()> {
+        match string {
+            "edge" => Ok(BleedFill::Edge),
+            "background" => Ok(BleedFill::Background),
+            "image" => Ok(BleedFill::Image),
+            _ => Err(())
+        }
+    }
+}
+
+// How far a channel may stray from the background for a `trim` step without a tolerance
+const TRIM_TOLERANCE: f64 = 24.0;
+
+// Switch on the steps of a pipeline like `trim|pad:4`, each named like the option it stands for with its value after
+// a colon, where `pad` is a bleed filled with the background. The steps still run in their usual order.
+pub fn apply_steps(options: &mut PostProcess, steps: &str) -> Result<(), String> {
+    for step in steps.split('|').map(str::trim).filter(|step| !step.is_empty()) {
+        let mut parts = step.splitn(2, ':');
+        let name = parts.next().unwrap().trim();
+        let value = parts.next().map(str::trim);
+        let invalid = || format!("invalid value for {}: {}", name, value.unwrap_or(""));
+        let number = || value.ok_or_else(|| format!("{} requires a value", name))?.parse::<f64>().map_err(|_| invalid());
+        let whole = || number().and_then(|number| if number >= 0.0 && number.fract() == 0.0 { Ok(number as u32) } else { Err(invalid()) });
+        match name {
+            "deskew" => options.deskew = true,
+            "trim" => options.trim = Some(if value.is_some() { number()? } else { TRIM_TOLERANCE }),
+            "trim-padding" => options.trim_padding = whole()?,
+            "resize" => options.resize = Some(whole()?),
+            "filter" => options.filter = value.unwrap_or("").parse().map_err(|_| invalid())?,
+            "invert" => options.invert = true,
+            "white-balance" => options.white_balance = true,
+            "auto-contrast" => options.auto_contrast = true,
+            "quantize" => options.quantize = Some(whole()?.clamp(2, 255) as u8),
+            "bleed" | "pad" => {
+                options.bleed = Some(whole()?);
+                options.bleed_fill = if name == "pad" { BleedFill::Background } else { BleedFill::Edge };
+            },
+            "min-sharpness" => options.min_sharpness = Some(number()?),
+            "drop-blurry" => options.drop_blurry = true,
+            _ => return Err(format!("unknown step: {}", name))
+        }
+    }
+    Ok(())
+}
+
+// A region of the source image to fill in wherever it falls in a piece, so what's in it never makes it into a piece,
+// in the source image's pixels
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Redaction {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    // The colour to fill it with as red, green and blue, black if it's left out
+    #[serde(default)]
+    pub color: [u8; 3]
+}
+
+// Read the regions to redact from a JSON list of them, like `[{"x": 10, "y": 20, "width": 300, "height": 40}]`
+pub fn read_redactions(path: &Path) -> Result<Vec<Redaction>, String> {
+    let data = fs::read(path).map_err(|error| error.to_string())?;
+    serde_json::from_slice(&data).map_err(|error| error.to_string())
+}
+
+// The steps to run on each piece, in the order they're listed here
+#[derive(Clone, Default, Serialize)]
+pub struct PostProcess {
+    // Rotate pieces so their content is square to the edges
+    pub deskew: bool,
+    // Trim borders of the piece's background colour, allowing channels to stray this far from it
+    pub trim: Option<f64>,
+    // How much of the border to keep around the content when trimming
+    pub trim_padding: u32,
+    // Shrink pieces so neither side is longer than this
+    pub resize: Option<u32>,
+    // The filter to resize with
+    pub filter: Filter,
+    // Turn film negatives into positives by inverting each colour channel
+    pub invert: bool,
+    // Scale each colour channel so the piece averages out to grey, taking out a film base's or light's cast
+    pub white_balance: bool,
+    // Stretch each colour channel to cover the full range
+    pub auto_contrast: bool,
+    // Reduce each colour channel to this many levels
+    pub quantize: Option<u8>,
+    // Add a margin this wide around each piece, for printing with bleed
+    pub bleed: Option<u32>,
+    // What fills the margin
+    pub bleed_fill: BleedFill,
+    // Flag pieces whose sharpness, the variance of their Laplacian, is below this
+    pub min_sharpness: Option<f64>,
+    // Whether to drop blurry pieces rather than just flag them
+    pub drop_blurry: bool,
+    // Regions of the source image to fill in, before any of the other steps
+    pub redact: Vec<Redaction>
+}
+
+impl PostProcess {
+    pub fn is_empty(&self) -> bool {
+        !self.deskew && self.trim.is_none() && self.resize.is_none() && !self.invert && !self.white_balance && !self.auto_contrast && self.quantize.is_none() &&
+            self.bleed.is_none() && self.min_sharpness.is_none() && self.redact.is_empty()
+    }
+
+    // The margin to take around each piece from the image it's cut from, if the bleed is filled from the image
+    pub fn image_bleed(&self) -> Option<u32> {
+        self.bleed.filter(|_| self.bleed_fill == BleedFill::Image)
+    }
+}
+
+// Straighten a piece if its content is skewed
+fn deskew(mut piece: Piece) -> Piece {
+    let angle = skew_angle(&piece.image);
+    if angle != 0.0 {
+        piece.image = rotate(&piece.image, angle);
+    }
+    piece
+}
+
+// Cut a piece down to the smallest rectangle holding everything that isn't its background, keeping up to the padding
+// of the border around it
+fn trim(piece: Piece, tolerance: f64, padding: u32) -> Piece {
+    let background = estimate_background(&piece.image);
+    let is_content = |pixel: &image::Rgba<u8>| (0 .. 4).any(|channel| (pixel[channel] as f64 - background[channel] as f64).abs() > tolerance);
+
+    let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in piece.image.enumerate_pixels() {
+        if is_content(pixel) {
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x + 1);
+            bottom = bottom.max(y + 1);
+        }
+    }
+
+    // Leave pieces that are all background alone
+    if right == 0 {
+        return piece;
+    }
+    let (left, top) = (left.saturating_sub(padding), top.saturating_sub(padding));
+    let right = right.saturating_add(padding).min(piece.image.width());
+    let bottom = bottom.saturating_add(padding).min(piece.image.height());
+
+    let mut image = piece.image;
+    Piece {
+        image: image.sub_image(left, top, right - left, bottom - top).to_image(),
+        x: piece.x + left,
+        y: piece.y + top,
+        width: right - left,
+        height: bottom - top,
+        subpixel: piece.subpixel,
+        scores: piece.scores,
+        path: piece.path,
+        parent: piece.parent,
+        nominal: piece.nominal
+    }
+}
+
+// Shrink an image to fit within a square, keeping its aspect ratio
+fn resize(image: RgbaImage, max_side: u32, filter: Filter) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    if width <= max_side && height <= max_side {
+        return image;
+    }
+
+    scale(&image, max_side as f64 / width.max(height) as f64, filter)
+}
+
+// Scale an image by a factor, keeping at least a pixel each way
+pub fn scale(image: &RgbaImage, scale: f64, filter: Filter) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let new_width = ((width as f64 * scale).round() as u32).max(1);
+    let new_height = ((height as f64 * scale).round() as u32).max(1);
+    image::imageops::resize(image, new_width, new_height, filter.filter_type())
+}
+
+// Invert each colour channel, leaving the alpha as it is
+fn invert(mut image: RgbaImage) -> RgbaImage {
+    for pixel in image.pixels_mut() {
+        for channel in 0 .. 3 {
+            pixel[channel] = 255 - pixel[channel];
+        }
+    }
+    image
+}
+
+// Scale each colour channel so its average over the opaque pixels is the average of all three, as a scene averages out
+// to grey, which takes out the cast of a film's base or a scanner's light
+fn white_balance(mut image: RgbaImage) -> RgbaImage {
+    let mut sums = [0u64; 3];
+    let mut count = 0u64;
+    for pixel in image.pixels().filter(|pixel| pixel[3] > 0) {
+        for (channel, sum) in sums.iter_mut().enumerate() {
+            *sum += pixel[channel] as u64;
+        }
+        count += 1;
+    }
+    if count == 0 || sums.contains(&0) {
+        return image;
+    }
+
+    let grey = (sums[0] + sums[1] + sums[2]) as f64 / 3.0;
+    let gains = [grey / sums[0] as f64, grey / sums[1] as f64, grey / sums[2] as f64];
+    for pixel in image.pixels_mut() {
+        for (channel, gain) in gains.iter().enumerate() {
+            pixel[channel] = (pixel[channel] as f64 * gain).round().min(255.0) as u8;
+        }
+    }
+    image
+}
+
+// Stretch each colour channel so its darkest value becomes 0 and its brightest 255
+fn auto_contrast(mut image: RgbaImage) -> RgbaImage {
+    let mut ranges = [(255u8, 0u8); 3];
+    for pixel in image.pixels() {
+        for (channel, range) in ranges.iter_mut().enumerate() {
+            *range = (range.0.min(pixel[channel]), range.1.max(pixel[channel]));
+        }
+    }
+
+    for pixel in image.pixels_mut() {
+        for (channel, &(low, high)) in ranges.iter().enumerate() {
+            if high > low {
+                pixel[channel] = ((pixel[channel] - low) as u32 * 255 / (high - low) as u32) as u8;
+            }
+        }
+    }
+    image
+}
+
+// Round each colour channel to the nearest of a number of evenly spaced levels
+fn quantize(mut image: RgbaImage, levels: u8) -> RgbaImage {
+    let steps = (levels.max(2) - 1) as f64;
+    for pixel in image.pixels_mut() {
+        for channel in 0 .. 3 {
+            pixel[channel] = ((pixel[channel] as f64 * steps / 255.0).round() * 255.0 / steps).round() as u8;
+        }
+    }
+    image
+}
+
+// Add a margin around an image, filled by stretching its edges outwards or with its background colour
+fn bleed(image: RgbaImage, margin: u32, fill: BleedFill) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let background = estimate_background(&image);
+    let clamp = |value: u32, size: u32| value.saturating_sub(margin).min(size - 1);
+
+    RgbaImage::from_fn(width + margin * 2, height + margin * 2, |x, y| {
+        let inside = x >= margin && y >= margin && x - margin < width && y - margin < height;
+        match fill {
+            _ if inside => *image.get_pixel(x - margin, y - margin),
+            BleedFill::Edge | BleedFill::Image => *image.get_pixel(clamp(x, width), clamp(y, height)),
+            BleedFill::Background => background
+        }
+    })
+}
+
+// Fill in the parts of the regions that fall in a piece, scaling them onto its image if it's no longer the size it was
+// cut at
+fn redact(mut piece: Piece, redactions: &[Redaction]) -> Piece {
+    let (width, height) = piece.image.dimensions();
+    let scale_x = width as f64 / piece.width.max(1) as f64;
+    let scale_y = height as f64 / piece.height.max(1) as f64;
+    let to_image = |value: u32, origin: u32, scale: f64, size: u32| (((value - origin) as f64 * scale).round() as u32).min(size);
+
+    for redaction in redactions {
+        let (left, top) = (redaction.x.max(piece.x), redaction.y.max(piece.y));
+        let right = redaction.x.saturating_add(redaction.width).min(piece.x + piece.width);
+        let bottom = redaction.y.saturating_add(redaction.height).min(piece.y + piece.height);
+        if left >= right || top >= bottom {
+            continue;
+        }
+
+        let [red, green, blue] = redaction.color;
+        let fill = image::Rgba {data: [red, green, blue, 255]};
+        for y in to_image(top, piece.y, scale_y, height) .. to_image(bottom, piece.y, scale_y, height) {
+            for x in to_image(left, piece.x, scale_x, width) .. to_image(right, piece.x, scale_x, width) {
+                piece.image.put_pixel(x, y, fill);
+            }
+        }
+    }
+    piece
+}
+
+// Run the steps on a single piece
+fn process(piece: Piece, options: &PostProcess) -> Piece {
+    let piece = if options.redact.is_empty() { piece } else { redact(piece, &options.redact) };
+    let piece = if options.deskew { deskew(piece) } else { piece };
+    let mut piece = match options.trim {
+        Some(tolerance) => trim(piece, tolerance, options.trim_padding),
+        None => piece
+    };
+    if let Some(max_side) = options.resize {
+        piece.image = resize(piece.image, max_side, options.filter);
+    }
+    if options.invert {
+        piece.image = invert(piece.image);
+    }
+    if options.white_balance {
+        piece.image = white_balance(piece.image);
+    }
+    if options.auto_contrast {
+        piece.image = auto_contrast(piece.image);
+    }
+    if let Some(levels) = options.quantize {
+        piece.image = quantize(piece.image, levels);
+    }
+    if let (Some(margin), None) = (options.bleed, options.image_bleed()) {
+        piece.image = bleed(piece.image, margin, options.bleed_fill);
+    }
+    piece
+}
+
+// Run the steps on every piece of an image in parallel, keeping them in order, then flag or drop the blurry ones
+pub fn post_process(name: &str, pieces: Vec<Piece>, options: &PostProcess, log: &mut Vec<String>) -> Vec<Piece> {
+    if options.is_empty() {
+        return pieces;
+    }
+
+    let processed: Vec<(Piece, Option<f64>)> = pieces.into_par_iter()
+        .map(|piece| {
+            let piece = process(piece, options);
+            let sharpness = options.min_sharpness.map(|_| laplacian_variance(&piece.image));
+            (piece, sharpness)
+        })
+        .collect();
+
+    let mut kept = Vec::with_capacity(processed.len());
+    for (index, (piece, sharpness)) in processed.into_iter().enumerate() {
+        match (sharpness, options.min_sharpness) {
+            (Some(sharpness), Some(min_sharpness)) if sharpness < min_sharpness => {
+                let action = if options.drop_blurry { "dropped" } else { "kept" };
+                log.push(format!("Blurry: {} piece {} has a sharpness of {:.1}, {}", name, index, sharpness, action));
+                if !options.drop_blurry {
+                    kept.push(piece);
+                }
+            },
+            _ => kept.push(piece)
+        }
+    }
+    kept
+}
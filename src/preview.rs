@@ -0,0 +1,103 @@
+// Draw where an image would be cut on top of it, so settings can be tuned by eye
+
+use image::{Rgba, RgbaImage};
+
+use Piece;
+
+// Horizontal cuts are drawn in red and vertical cuts in blue
+const HORIZONTAL_COLOR: Rgba<u8> = Rgba { data: [230, 30, 30, 255] };
+const VERTICAL_COLOR: Rgba<u8> = Rgba { data: [30, 80, 230, 255] };
+
+// The rows of a 3 by 5 glyph for each character a score or a size is written with, with the leftmost pixel in the highest bit
+fn glyph(character: char) -> Option<[u8; 5]> {
+    Some(match character {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        'x' => [0b000, 0b101, 0b010, 0b101, 0b000],
+        ' ' => [0b000; 5],
+        _ => return None
+    })
+}
+
+// Fill a rectangle, clipped to the image
+fn fill(image: &mut RgbaImage, x: u32, y: u32, width: u32, height: u32, color: Rgba<u8>) {
+    for y in y .. (y + height).min(image.height()) {
+        for x in x .. (x + width).min(image.width()) {
+            image.put_pixel(x, y, color);
+        }
+    }
+}
+
+// Write text in blocks of `scale` pixels on a white backing, with its top left corner at `x` and `y`
+pub fn write(image: &mut RgbaImage, text: &str, x: u32, y: u32, scale: u32, color: Rgba<u8>) {
+    let glyphs: Vec<[u8; 5]> = text.chars().filter_map(glyph).collect();
+    fill(image, x, y, (glyphs.len() as u32 * 4 + 1) * scale, 7 * scale, Rgba { data: [255; 4] });
+
+    for (index, rows) in glyphs.iter().enumerate() {
+        let left = x + (index as u32 * 4 + 1) * scale;
+        for (row, bits) in rows.iter().enumerate() {
+            for column in 0 .. 3 {
+                if bits & (0b100 >> column) != 0 {
+                    fill(image, left + column * scale, y + (row as u32 + 1) * scale, scale, scale, color);
+                }
+            }
+        }
+    }
+}
+
+// Get a copy of an image with a line along every edge of its pieces that was cut, labelled with the difference that
+// made the cut
+pub fn draw_cuts(image: &RgbaImage, pieces: &[Piece]) -> RgbaImage {
+    let mut preview = image.clone();
+    // Keep the lines and labels visible on large scans
+    let thickness = (image.width().min(image.height()) / 500).max(1);
+    let scale = thickness * 2;
+
+    // Each cut is the edge of the pieces on both sides of it, so gather the cuts by where they fall and how strong
+    // they were, and label each once at its start
+    let mut cuts: Vec<(bool, u32, String, u32, u32)> = Vec::new();
+    for piece in pieces {
+        let edges = [
+            (false, piece.x, piece.scores[0]),
+            (true, piece.y, piece.scores[1]),
+            (false, piece.x + piece.width, piece.scores[2]),
+            (true, piece.y + piece.height, piece.scores[3])
+        ];
+        for &(horizontal, at, score) in &edges {
+            if let Some(score) = score {
+                let (start, end) = if horizontal { (piece.x, piece.x + piece.width) } else { (piece.y, piece.y + piece.height) };
+                cuts.push((horizontal, at, format!("{:.1}", score), start, end));
+            }
+        }
+    }
+    cuts.sort();
+
+    // Draw every line before any label, so no line crosses a label
+    for &(horizontal, at, _, start, end) in &cuts {
+        let at = at.saturating_sub(thickness / 2);
+        if horizontal {
+            fill(&mut preview, start, at, end - start, thickness, HORIZONTAL_COLOR);
+        } else {
+            fill(&mut preview, at, start, thickness, end - start, VERTICAL_COLOR);
+        }
+    }
+    cuts.dedup_by(|a, b| (a.0, a.1, &a.2) == (b.0, b.1, &b.2));
+    for (horizontal, at, text, start, _) in cuts {
+        if horizontal {
+            write(&mut preview, &text, start + thickness, at + thickness, scale, HORIZONTAL_COLOR);
+        } else {
+            write(&mut preview, &text, at + thickness, start + thickness, scale, VERTICAL_COLOR);
+        }
+    }
+
+    preview
+}
@@ -0,0 +1,68 @@
+// Split scanned receipt rolls into their printed blocks by where the ink is down the roll
+
+use image::{Rgba, RgbaImage};
+
+use {content_pixels, Line};
+
+// Find the runs of rows with ink in them, leaving out runs a single row tall, which are speckle rather than print
+fn ink_runs(inked: &[bool]) -> Vec<(u32, u32)> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    for (y, &ink) in inked.iter().enumerate() {
+        match (ink, start) {
+            (true, None) => start = Some(y as u32),
+            (false, Some(run_start)) => {
+                runs.push((run_start, y as u32));
+                start = None;
+            },
+            _ => {}
+        }
+    }
+    if let Some(run_start) = start {
+        runs.push((run_start, inked.len() as u32));
+    }
+    runs.retain(|&(start, end)| end - start > 1);
+    runs
+}
+
+// Find the rectangle of each block of printing, where lines of print closer together than the gap belong to the same
+// block. Rows need ink in more than one pixel in 100, and columns in more than one pixel, to count as printed, so the
+// speckle thermal paper picks up doesn't join blocks together. Each block is trimmed to its ink and padded by a
+// quarter of the gap.
+pub fn receipt_blocks(image: &RgbaImage, background: Rgba<u8>, weights: &[f64; 4], tolerance: f64, gap: u32) -> Vec<(u32, u32, u32, u32)> {
+    let (width, height) = image.dimensions();
+    let noise = width / 100;
+    let inked: Vec<bool> = (0 .. height)
+        .map(|y| {
+            let row: Line = (0 .. width).map(|x| *image.get_pixel(x, y)).collect();
+            content_pixels(&row, background, weights, tolerance) > noise
+        })
+        .collect();
+
+    // Group the lines of print into blocks
+    let mut blocks: Vec<(u32, u32)> = Vec::new();
+    for (start, end) in ink_runs(&inked) {
+        match blocks.last_mut() {
+            Some(block) if start - block.1 < gap => block.1 = end,
+            _ => blocks.push((start, end))
+        }
+    }
+
+    let padding = gap / 4;
+    blocks.into_iter()
+        .map(|(top, bottom)| {
+            let inked_columns: Vec<bool> = (0 .. width)
+                .map(|x| {
+                    let column: Line = (top .. bottom).map(|y| *image.get_pixel(x, y)).collect();
+                    content_pixels(&column, background, weights, tolerance) > 1
+                })
+                .collect();
+            let left = inked_columns.iter().position(|&ink| ink).unwrap_or(0) as u32;
+            let right = inked_columns.iter().rposition(|&ink| ink).map(|x| x as u32 + 1).unwrap_or(width);
+
+            let (left, top) = (left.saturating_sub(padding), top.saturating_sub(padding));
+            let (right, bottom) = ((right + padding).min(width), (bottom + padding).min(height));
+            (left, top, right - left, bottom - top)
+        })
+        .collect()
+}
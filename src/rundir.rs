@@ -0,0 +1,78 @@
+// Keep everything a run makes in a directory of its own, named after when it started, along with the settings it ran
+// with, a report of what came of each input and the log of every decision made, so each session can be looked back on
+// and run again
+
+use std::fs::{create_dir, create_dir_all};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde_json;
+
+use atomic;
+use epub::timestamp;
+use manifest::Stamp;
+
+// The files written into a run's directory beside the inputs' outputs
+pub const CONFIG_FILE: &str = "config.json";
+pub const REPORT_FILE: &str = "report.json";
+pub const LOG_FILE: &str = "log.txt";
+
+// What came of an input
+#[derive(Serialize)]
+pub struct InputReport<'a> {
+    pub input: &'a str,
+    pub pieces: usize,
+    pub errors: &'a [String],
+    pub mismatched: bool
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    started: &'a str,
+    finished: String,
+    pieces: usize,
+    failed: usize,
+    inputs: &'a [InputReport<'a>]
+}
+
+// A run's directory, and when the run started
+pub struct RunDir {
+    pub dir: PathBuf,
+    pub started: String
+}
+
+impl RunDir {
+    // Make a new directory for a run in a root directory, named after the time in UTC, with a number after it if
+    // another run already started that second
+    pub fn create(root: &Path) -> io::Result<RunDir> {
+        create_dir_all(root)?;
+        let started = timestamp();
+        // Colons aren't allowed in file names everywhere
+        let name = started.replace(':', "-");
+        let mut attempt = 1;
+        loop {
+            let dir = if attempt == 1 { root.join(&name) } else { root.join(format!("{}-{}", name, attempt)) };
+            match create_dir(&dir) {
+                Ok(()) => return Ok(RunDir { dir, started }),
+                Err(ref error) if error.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+                Err(error) => return Err(error)
+            }
+        }
+    }
+
+    // Write the settings the run used, the report of what came of each input and the whole log
+    pub fn finish(&self, stamp: &Stamp, inputs: &[InputReport], log: &[String]) -> io::Result<()> {
+        atomic::write(&self.dir.join(CONFIG_FILE), serde_json::to_string_pretty(stamp).unwrap().as_bytes())?;
+        let report = Report {
+            started: &self.started,
+            finished: timestamp(),
+            pieces: inputs.iter().map(|input| input.pieces).sum(),
+            failed: inputs.iter().filter(|input| !input.errors.is_empty()).count(),
+            inputs
+        };
+        atomic::write(&self.dir.join(REPORT_FILE), serde_json::to_string_pretty(&report).unwrap().as_bytes())?;
+        let mut text = log.join("\n");
+        text.push('\n');
+        atomic::write(&self.dir.join(LOG_FILE), text.as_bytes())
+    }
+}
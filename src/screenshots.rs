@@ -0,0 +1,80 @@
+// Find where stitched phone screenshots join by looking for their status and navigation bars repeating
+
+use image::RgbaImage;
+
+// Get the average difference between two rows of an image
+fn row_difference(image: &RgbaImage, a: u32, b: u32) -> f64 {
+    let mut value = 0.0;
+
+    for x in 0 .. image.width() {
+        let (pa, pb) = (image.get_pixel(x, a), image.get_pixel(x, b));
+        for channel in 0 .. 4 {
+            value += (pa[channel] as f64 - pb[channel] as f64).abs();
+        }
+    }
+
+    value / image.width() as f64 / 4.0
+}
+
+// Whether the band of rows starting at `start` matches the band starting at `band`
+fn band_matches(image: &RgbaImage, band: u32, start: u32, rows: u32, tolerance: f64) -> bool {
+    (0 .. rows).all(|row| row_difference(image, band + row, start + row) <= tolerance)
+}
+
+// Whether a band has enough detail to be recognised, rather than being a plain background that matches everywhere
+fn band_has_detail(image: &RgbaImage, band: u32, rows: u32, tolerance: f64) -> bool {
+    (band .. band + rows).any(|y| {
+        let first = image.get_pixel(0, y);
+        (1 .. image.width()).any(|x| {
+            let pixel = image.get_pixel(x, y);
+            (0 .. 4).any(|channel| (pixel[channel] as f64 - first[channel] as f64).abs() > tolerance)
+        })
+    })
+}
+
+// Find every row where the band at the top (a status bar) repeats, or just after where the band at the bottom
+// (a navigation bar) repeats
+pub fn chrome_cuts(image: &RgbaImage, rows: u32, tolerance: f64) -> Vec<u32> {
+    let height = image.height();
+    let mut cuts = Vec::new();
+
+    if rows == 0 || height < rows * 2 {
+        return cuts;
+    }
+
+    // Look for the status bar starting somewhere below the top
+    if band_has_detail(image, 0, rows, tolerance) {
+        let mut y = rows;
+        while y + rows <= height {
+            if band_matches(image, 0, y, rows, tolerance) {
+                cuts.push(y);
+                y += rows;
+            } else {
+                y += 1;
+            }
+        }
+    }
+
+    // Look for the navigation bar ending somewhere above the bottom
+    let bottom = height - rows;
+    if band_has_detail(image, bottom, rows, tolerance) {
+        let mut y = bottom - rows;
+        loop {
+            if band_matches(image, bottom, y, rows, tolerance) {
+                cuts.push(y + rows);
+                if y < rows {
+                    break;
+                }
+                y -= rows;
+            } else if y == 0 {
+                break;
+            } else {
+                y -= 1;
+            }
+        }
+    }
+
+    cuts.sort();
+    cuts.dedup();
+    cuts
+}
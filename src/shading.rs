@@ -0,0 +1,64 @@
+// Even out the light falling off towards the spine of a scanned book, so the gradient doesn't look like an edge
+
+use image::{Rgba, RgbaImage};
+
+use luma;
+
+// The share of a line's pixels that are darker than its paper
+const PAPER_PERCENTILE: f64 = 0.9;
+
+// The fraction of the image's size to smooth the paper's brightness over, so the content doesn't count as shading
+const SMOOTHING: u32 = 20;
+
+// The most a line is brightened, so a line that is all dark content isn't blown out
+const MAX_GAIN: f64 = 3.0;
+
+// Get the brightness of the paper along each line, as a bright percentile of the luma of its pixels
+fn paper_brightness<F: Fn(u32, u32) -> Rgba<u8>>(lines: u32, length: u32, pixel: F) -> Vec<f64> {
+    let rank = (length as f64 * PAPER_PERCENTILE) as u32;
+    (0 .. lines)
+        .map(|line| {
+            let mut histogram = [0u32; 256];
+            for along in 0 .. length {
+                histogram[luma(&pixel(line, along)) as usize] += 1;
+            }
+            let mut count = 0;
+            histogram.iter().position(|&pixels| {
+                count += pixels;
+                count > rank
+            }).unwrap_or(255) as f64
+        })
+        .collect()
+}
+
+// Average each value with those within the window on either side of it
+fn smooth(values: &[f64], window: usize) -> Vec<f64> {
+    (0 .. values.len())
+        .map(|index| {
+            let nearby = &values[index.saturating_sub(window) .. (index + window + 1).min(values.len())];
+            nearby.iter().sum::<f64>() / nearby.len() as f64
+        })
+        .collect()
+}
+
+// Get how much to brighten each line so its paper is as bright as the brightest
+fn gains(brightness: &[f64], window: usize) -> Vec<f64> {
+    let smoothed = smooth(brightness, window);
+    let brightest = smoothed.iter().cloned().fold(0.0, f64::max);
+    smoothed.iter().map(|&value| (brightest / value.max(1.0)).min(MAX_GAIN)).collect()
+}
+
+// Brighten an image's columns and then its rows so its paper is evenly lit, leaving the alpha as it is
+pub fn flatten_shading(image: &RgbaImage) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let brighten = |pixel: &Rgba<u8>, gain: f64| {
+        let channel = |value: u8| (value as f64 * gain).round().min(255.0) as u8;
+        Rgba { data: [channel(pixel[0]), channel(pixel[1]), channel(pixel[2]), pixel[3]] }
+    };
+
+    let column_gains = gains(&paper_brightness(width, height, |x, y| *image.get_pixel(x, y)), (width / SMOOTHING) as usize);
+    let columns = RgbaImage::from_fn(width, height, |x, y| brighten(image.get_pixel(x, y), column_gains[x as usize]));
+
+    let row_gains = gains(&paper_brightness(height, width, |y, x| *columns.get_pixel(x, y)), (height / SMOOTHING) as usize);
+    RgbaImage::from_fn(width, height, |x, y| brighten(columns.get_pixel(x, y), row_gains[y as usize]))
+}
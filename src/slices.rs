@@ -0,0 +1,160 @@
+// Read the slices some asset tools store in PNGs, to cut exactly where they say rather than detecting where to cut,
+// and store the slices found by detecting in PNGs the same way
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use image::{self, GenericImage};
+use serde_json;
+
+use {Guillotined, Piece};
+
+// The text chunk keyword slices are stored under, as a JSON list of rectangles
+pub const SLICES_KEYWORD: &str = "autoguillotine:slices";
+
+// The chunk Android stores a compiled 9-patch's stretchable regions in
+const NINE_PATCH_CHUNK: &[u8; 4] = b"npTc";
+
+// The length of a compiled 9-patch's header, before its divisions
+const NINE_PATCH_HEADER: usize = 32;
+
+// A slice of an image, in pixels
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Slice {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32
+}
+
+// Get the type and data of each chunk of a PNG, stopping at the first that's cut short
+pub fn chunks(data: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut chunks = Vec::new();
+    let mut position = 8;
+    while position + 12 <= data.len() {
+        let length = u32::from_be_bytes([data[position], data[position + 1], data[position + 2], data[position + 3]]) as usize;
+        let kind = &data[position + 4 .. position + 8];
+        match data.get(position + 8 .. position + 8 + length) {
+            Some(chunk) => chunks.push((kind, chunk)),
+            None => break
+        }
+        position += 12 + length;
+    }
+    chunks
+}
+
+// Read the slices from a text chunk
+fn text_slices(chunk: &[u8]) -> Option<Vec<Slice>> {
+    let separator = chunk.iter().position(|&byte| byte == 0)?;
+    if &chunk[.. separator] != SLICES_KEYWORD.as_bytes() {
+        return None;
+    }
+    serde_json::from_slice(&chunk[separator + 1 ..]).ok()
+}
+
+// Turn a compiled 9-patch's stretchable regions into the grid of slices between their edges
+fn nine_patch_slices(chunk: &[u8], width: u32, height: u32) -> Option<Vec<Slice>> {
+    let (x_count, y_count) = (*chunk.get(1)? as usize, *chunk.get(2)? as usize);
+    let int = |index: usize| {
+        let start = NINE_PATCH_HEADER + index * 4;
+        chunk.get(start .. start + 4).map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+    let edges = |start: usize, count: usize, size: u32| -> Option<Vec<u32>> {
+        let mut edges = vec![0];
+        for index in start .. start + count {
+            edges.push(int(index)?.min(size));
+        }
+        edges.push(size);
+        edges.sort();
+        edges.dedup();
+        Some(edges)
+    };
+    let columns = edges(0, x_count, width)?;
+    let rows = edges(x_count, y_count, height)?;
+
+    let mut slices = Vec::new();
+    for row in rows.windows(2) {
+        for column in columns.windows(2) {
+            slices.push(Slice { x: column[0], y: row[0], width: column[1] - column[0], height: row[1] - row[0] });
+        }
+    }
+    Some(slices)
+}
+
+// Read the slices stored in a PNG, preferring our own over a 9-patch's, if it has any that fit inside it
+pub fn read_slices(path: &Path) -> Option<Vec<Slice>> {
+    let mut data = Vec::new();
+    File::open(path).and_then(|mut file| file.read_to_end(&mut data)).ok()?;
+    if image::guess_format(&data).ok()? != image::ImageFormat::PNG {
+        return None;
+    }
+
+    let chunks = chunks(&data);
+    let (_, header) = *chunks.iter().find(|&&(kind, _)| kind == b"IHDR")?;
+    let header = header.get(.. 8)?;
+    let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+    let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+
+    let slices = chunks.iter().filter(|&&(kind, _)| kind == b"tEXt").filter_map(|&(_, chunk)| text_slices(chunk)).next()
+        .or_else(|| chunks.iter().filter(|&&(kind, _)| kind == NINE_PATCH_CHUNK).filter_map(|&(_, chunk)| nine_patch_slices(chunk, width, height)).next())?;
+    let fits = |slice: &Slice| slice.width > 0 && slice.height > 0 && slice.x + slice.width <= width && slice.y + slice.height <= height;
+    if slices.is_empty() || !slices.iter().all(fits) {
+        return None;
+    }
+    Some(slices)
+}
+
+// Get the CRC of a chunk's type and data, as PNGs store after each chunk
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0 .. 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+// Get a copy of a PNG with slices, as JSON, in a text chunk after its header, or nothing if it isn't a PNG
+pub fn with_slices(data: &[u8], json: &str) -> Option<Vec<u8>> {
+    let mut text = SLICES_KEYWORD.as_bytes().to_vec();
+    text.push(0);
+    text.extend_from_slice(json.as_bytes());
+    with_chunk(data, b"tEXt", &text)
+}
+
+// Get a copy of a PNG with a chunk added straight after its header, where chunks that have to come before the image
+// data can go, or nothing if it isn't a PNG
+pub fn with_chunk(data: &[u8], kind: &[u8; 4], contents: &[u8]) -> Option<Vec<u8>> {
+    if image::guess_format(data).ok()? != image::ImageFormat::PNG {
+        return None;
+    }
+    let (_, header) = *chunks(data).first()?;
+    let after_header = 8 + 12 + header.len();
+
+    let mut chunk = kind.to_vec();
+    chunk.extend_from_slice(contents);
+
+    let mut png = data[.. after_header].to_vec();
+    png.extend_from_slice(&(chunk.len() as u32 - 4).to_be_bytes());
+    png.extend_from_slice(&chunk);
+    png.extend_from_slice(&crc32(&chunk).to_be_bytes());
+    png.extend_from_slice(&data[after_header ..]);
+    Some(png)
+}
+
+// Cut an image into its slices
+pub fn cut_slices(image: image::DynamicImage, slices: &[Slice]) -> (Guillotined, bool) {
+    let has_alpha = matches!(image.color(), image::ColorType::RGBA(_) | image::ColorType::GrayA(_));
+    let mut image = image.to_rgba();
+    let pieces = slices.iter()
+        .enumerate()
+        .map(|(index, slice)| {
+            let piece = image.sub_image(slice.x, slice.y, slice.width, slice.height).to_image();
+            Piece { path: vec![index as u32], ..Piece::new(piece, slice.x, slice.y) }
+        })
+        .collect();
+    (Guillotined { pieces, log: vec![format!("Slices from metadata: {}", slices.len())] }, has_alpha)
+}
@@ -0,0 +1,69 @@
+// Pack every piece of a run into one sprite sheet, with a stylesheet and an index of where each piece is
+
+use std::io;
+use std::path::Path;
+
+use image::{GenericImage, Rgba, RgbaImage};
+use serde_json;
+
+use atomic;
+use indexed::encode_indexed;
+use pack::shelf_pack;
+use {encode_page, output_image};
+
+// The space left between sprites, so scaling the sheet doesn't bleed neighbours into each other
+const SPACING: u32 = 2;
+
+// Where a sprite is on the sheet
+#[derive(Serialize)]
+struct Sprite<'a> {
+    name: &'a str,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32
+}
+
+// Turn a piece's name into something that can be used as a CSS class
+fn class_name(name: &str) -> String {
+    name.chars().map(|character| if character.is_ascii_alphanumeric() || character == '-' { character } else { '_' }).collect()
+}
+
+// Write the named pieces into a single PNG sprite sheet, next to a stylesheet with a class for each and a JSON index
+// of their rectangles
+pub fn write_sprites(path: &Path, sprites: &[(String, RgbaImage)]) -> io::Result<()> {
+    let sizes: Vec<(u32, u32)> = sprites.iter().map(|(_, image)| image.dimensions()).collect();
+
+    // Aim for a roughly square sheet, but no narrower than the widest sprite
+    let area: u64 = sizes.iter().map(|&(width, height)| (width + SPACING) as u64 * (height + SPACING) as u64).sum();
+    let widest = sizes.iter().map(|&(width, _)| width).max().unwrap_or(1);
+    let width = widest.max((area as f64).sqrt().ceil() as u32);
+    let positions: Vec<(u32, u32)> = shelf_pack(&sizes, width, u32::MAX, SPACING).into_iter()
+        .map(|position| position.map(|(_, x, y)| (x, y)).unwrap())
+        .collect();
+
+    let height = positions.iter().zip(&sizes).map(|(&(_, y), &(_, height))| y + height).max().unwrap_or(1);
+    let sheet_width = positions.iter().zip(&sizes).map(|(&(x, _), &(width, _))| x + width).max().unwrap_or(1);
+    let mut sheet = RgbaImage::from_pixel(sheet_width, height, Rgba { data: [0, 0, 0, 0] });
+    for ((_, image), &(x, y)) in sprites.iter().zip(&positions) {
+        sheet.copy_from(image, x, y);
+    }
+
+    // Sprite sheets are often flat artwork, so save them paletted when they can be
+    let data = match encode_indexed(&sheet) {
+        Some(data) => data,
+        None => encode_page(&output_image(&sheet, true)).data
+    };
+    atomic::write(path, &data)?;
+
+    let file_name = path.file_name().unwrap().to_string_lossy();
+    let mut css = format!(".sprite {{ background-image: url(\"{}\"); background-repeat: no-repeat; }}\n", file_name);
+    let mut index = Vec::new();
+    for ((name, image), &(x, y)) in sprites.iter().zip(&positions) {
+        css.push_str(&format!(".sprite-{} {{ width: {}px; height: {}px; background-position: -{}px -{}px; }}\n",
+            class_name(name), image.width(), image.height(), x, y));
+        index.push(Sprite { name, x, y, width: image.width(), height: image.height() });
+    }
+    atomic::write(&path.with_extension("css"), css.as_bytes())?;
+    atomic::write(&path.with_extension("json"), serde_json::to_string_pretty(&index).unwrap().as_bytes())
+}
@@ -0,0 +1,57 @@
+// Statistics about a piece, so it can be filtered without being decoded again
+
+use std::collections::HashSet;
+
+use image::RgbaImage;
+
+use {luma, luma_entropy};
+
+#[derive(Serialize)]
+pub struct Stats {
+    // The average of each of the red, green, blue and alpha channels
+    pub mean_color: [f64; 4],
+    pub unique_colors: usize,
+    // The Shannon entropy of the luma histogram, in bits
+    pub entropy: f64,
+    // The variance of the Laplacian of the luma, which is higher the sharper the piece is
+    pub sharpness: f64
+}
+
+// Get the variance of the Laplacian of an image's luma
+pub fn laplacian_variance(image: &RgbaImage) -> f64 {
+    let (width, height) = image.dimensions();
+    if width < 3 || height < 3 {
+        return 0.0;
+    }
+
+    let luma_at = |x: u32, y: u32| luma(image.get_pixel(x, y)) as f64;
+    let mut values = Vec::with_capacity(((width - 2) * (height - 2)) as usize);
+    for y in 1 .. height - 1 {
+        for x in 1 .. width - 1 {
+            values.push(luma_at(x - 1, y) + luma_at(x + 1, y) + luma_at(x, y - 1) + luma_at(x, y + 1) - 4.0 * luma_at(x, y));
+        }
+    }
+
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / values.len() as f64
+}
+
+pub fn piece_stats(image: &RgbaImage) -> Stats {
+    let pixels = image.width() as f64 * image.height() as f64;
+    let mut sums = [0.0; 4];
+    let mut colors = HashSet::new();
+
+    for pixel in image.pixels() {
+        for channel in 0 .. 4 {
+            sums[channel] += pixel[channel] as f64;
+        }
+        colors.insert(pixel.data);
+    }
+
+    Stats {
+        mean_color: [sums[0] / pixels, sums[1] / pixels, sums[2] / pixels, sums[3] / pixels],
+        unique_colors: colors.len(),
+        entropy: luma_entropy(image),
+        sharpness: laplacian_variance(image)
+    }
+}
@@ -0,0 +1,69 @@
+// Separate stickers on a transparent background by the shapes of their opaque regions rather than straight cuts
+
+use image::{Rgba, RgbaImage};
+
+// A connected region of opaque pixels, and its bounding box
+struct Region {
+    pixels: Vec<(u32, u32)>,
+    left: u32,
+    top: u32,
+    right: u32,
+    bottom: u32
+}
+
+// Find the regions of pixels more opaque than the threshold that touch each other, including diagonally, in the
+// order their top-left-most pixels come in
+fn regions(image: &RgbaImage, alpha: u8) -> Vec<Region> {
+    let (width, height) = image.dimensions();
+    let mut seen = vec![false; width as usize * height as usize];
+    let at = |x: u32, y: u32| y as usize * width as usize + x as usize;
+    let mut regions = Vec::new();
+
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if seen[at(x, y)] || pixel[3] <= alpha {
+            continue;
+        }
+
+        let mut region = Region { pixels: Vec::new(), left: x, top: y, right: x, bottom: y };
+        let mut stack = vec![(x, y)];
+        seen[at(x, y)] = true;
+
+        while let Some((x, y)) = stack.pop() {
+            region.pixels.push((x, y));
+            region.left = region.left.min(x);
+            region.top = region.top.min(y);
+            region.right = region.right.max(x);
+            region.bottom = region.bottom.max(y);
+
+            for ny in y.saturating_sub(1) ..= (y + 1).min(height - 1) {
+                for nx in x.saturating_sub(1) ..= (x + 1).min(width - 1) {
+                    let index = at(nx, ny);
+                    if !seen[index] && image.get_pixel(nx, ny)[3] > alpha {
+                        seen[index] = true;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+        }
+        regions.push(region);
+    }
+
+    regions
+}
+
+// Get each region at least `min_size` wide and high as an image cropped to it, where anything in its bounding box
+// that isn't part of it is transparent, along with where it was
+pub fn split_stickers(image: &RgbaImage, alpha: u8, min_size: u32) -> Vec<(RgbaImage, u32, u32)> {
+    regions(image, alpha).into_iter()
+        .filter(|region| region.right - region.left + 1 >= min_size && region.bottom - region.top + 1 >= min_size)
+        .map(|region| {
+            let mut sticker = RgbaImage::from_pixel(
+                region.right - region.left + 1, region.bottom - region.top + 1, Rgba {data: [0; 4]}
+            );
+            for &(x, y) in &region.pixels {
+                sticker.put_pixel(x - region.left, y - region.top, *image.get_pixel(x, y));
+            }
+            (sticker, region.left, region.top)
+        })
+        .collect()
+}
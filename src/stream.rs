@@ -0,0 +1,83 @@
+// Read an image from stdin and write the pieces or manifests to stdout, so autoguillotine can sit in a shell pipeline
+
+use std::fs;
+use std::io::{self, Cursor, Read, Write};
+use std::path::Path;
+
+use image;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+use zip::result::ZipResult;
+
+// The input name that means stdin
+pub const STDIN: &str = "-";
+
+// What the outputs of inputs read from stdin are named after
+pub const STDIN_NAME: &str = "stdin";
+
+// What to write to stdout instead of saving files
+#[derive(Clone, Copy)]
+pub enum Stdout {
+    // A zip with a directory of pieces and a manifest for each image, as they would be saved on disk
+    Zip,
+    // Only each image's manifest, one after another
+    Manifest
+}
+
+impl ::std::str::FromStr for Stdout {
+    type Err = ();
+
+    fn from_str(string: &str) -> Result<Stdout, ()> {
+        match string {
+            "zip" => Ok(Stdout::Zip),
+            "manifest" => Ok(Stdout::Manifest),
+            _ => Err(())
+        }
+    }
+}
+
+// Read a whole input, from stdin if it's `-`
+pub fn read_input(arg: &str) -> io::Result<Vec<u8>> {
+    if arg == STDIN {
+        let mut data = Vec::new();
+        io::stdin().read_to_end(&mut data)?;
+        Ok(data)
+    } else {
+        fs::read(Path::new(arg))
+    }
+}
+
+// Decode an input, from stdin if it's `-`
+pub fn open_input(arg: &str) -> image::ImageResult<image::DynamicImage> {
+    if arg == STDIN {
+        image::load_from_memory(&read_input(arg)?)
+    } else {
+        image::open(arg)
+    }
+}
+
+// Write the files made from the inputs, named by where they'd be in a zip, as a zip or only the manifests among them
+pub fn write<W: Write>(mut writer: W, format: Stdout, files: &[(String, Vec<u8>)]) -> io::Result<()> {
+    match format {
+        Stdout::Zip => write_zip(&mut writer, files).map_err(|error| io::Error::other(error))?,
+        Stdout::Manifest => for (_, data) in files.iter().filter(|&&(ref name, _)| name.ends_with("/manifest.json")) {
+            writer.write_all(data)?;
+            writer.write_all(b"\n")?;
+        }
+    }
+    writer.flush()
+}
+
+// Write files into a zip, built in memory since stdout can't be seeked back into
+fn write_zip<W: Write>(writer: &mut W, files: &[(String, Vec<u8>)]) -> ZipResult<()> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    // The pieces are already compressed, so there's no point deflating them again
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    for (name, data) in files {
+        let options = if name.ends_with(".json") { SimpleFileOptions::default() } else { stored };
+        zip.start_file(name.as_str(), options)?;
+        zip.write_all(data)?;
+    }
+    writer.write_all(zip.finish()?.get_ref())?;
+    Ok(())
+}
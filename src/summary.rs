@@ -0,0 +1,48 @@
+// Lay out every piece of an input on one sheet in reading order, each shrunk and labelled with its number and size,
+// so a folder of pieces can be checked for bad cuts at a glance
+
+use image::{self, GenericImage, Rgba, RgbaImage};
+
+use preview;
+use Piece;
+
+// The file the sheet is saved as, in the directory of pieces
+pub const SUMMARY_FILE: &str = "summary.png";
+
+// The longest side of a piece on the sheet, the space around each and how big the labels' blocks are
+const THUMBNAIL: u32 = 240;
+const MARGIN: u32 = 12;
+const LABEL_SCALE: u32 = 2;
+
+const BACKGROUND: Rgba<u8> = Rgba { data: [200, 200, 200, 255] };
+const LABEL_COLOR: Rgba<u8> = Rgba { data: [0, 0, 0, 255] };
+
+// Draw the sheet for pieces already in reading order, in a grid about as many pieces wide as it is tall, with each
+// piece's label above it
+pub fn contact_sheet(pieces: &[Piece]) -> RgbaImage {
+    let columns = ((pieces.len() as f64).sqrt().ceil() as u32).max(1);
+    let rows = ((pieces.len() as u32 + columns - 1) / columns).max(1);
+    let label_height = 7 * LABEL_SCALE;
+    let (cell_width, cell_height) = (THUMBNAIL + MARGIN, label_height + THUMBNAIL + MARGIN);
+    let mut sheet = RgbaImage::from_pixel(MARGIN + columns * cell_width, MARGIN + rows * cell_height, BACKGROUND);
+
+    for (index, piece) in pieces.iter().enumerate() {
+        let left = MARGIN + index as u32 % columns * cell_width;
+        let top = MARGIN + index as u32 / columns * cell_height;
+
+        let (width, height) = piece.image.dimensions();
+        let scale = (THUMBNAIL as f64 / width.max(height) as f64).min(1.0);
+        let thumbnail = if scale < 1.0 {
+            let small_width = ((width as f64 * scale).round() as u32).max(1);
+            let small_height = ((height as f64 * scale).round() as u32).max(1);
+            image::imageops::resize(&piece.image, small_width, small_height, image::imageops::FilterType::Triangle)
+        } else {
+            piece.image.clone()
+        };
+        sheet.copy_from(&thumbnail, left + (THUMBNAIL - thumbnail.width()) / 2, top + label_height);
+
+        // Label the piece with the size it was saved at
+        preview::write(&mut sheet, &format!("{} {}x{}", index, width, height), left, top, LABEL_SCALE, LABEL_COLOR);
+    }
+    sheet
+}
@@ -0,0 +1,144 @@
+// Generate pages with panels in known places, for benchmarking and for checking settings find what they should
+
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use {guillotine, GuillotineConfig};
+
+// How to lay out and spoil a synthetic page
+#[derive(Clone)]
+pub struct PageSpec {
+    // The number of rows and columns of panels
+    pub rows: u32,
+    pub columns: u32,
+    // The size of each panel
+    pub panel_width: u32,
+    pub panel_height: u32,
+    // The width of the gutters between the panels and around the edge of the page
+    pub gutter: u32,
+    pub gutter_color: [u8; 3],
+    // How far each channel of each pixel is pushed at random, up or down
+    pub noise: u8,
+    // How far the whole page is turned clockwise, in degrees
+    pub rotation: f64,
+    // Seeds the panels' colours and the noise, so the same spec always makes the same page
+    pub seed: u64
+}
+
+impl Default for PageSpec {
+    fn default() -> PageSpec {
+        PageSpec {
+            rows: 3,
+            columns: 2,
+            panel_width: 300,
+            panel_height: 200,
+            gutter: 20,
+            gutter_color: [255; 3],
+            noise: 0,
+            rotation: 0.0,
+            seed: 0
+        }
+    }
+}
+
+// A synthetic page and the x, y, width and height of each of its panels in reading order, as they were before any
+// rotation
+pub struct Page {
+    pub image: RgbaImage,
+    pub panels: Vec<(u32, u32, u32, u32)>
+}
+
+// A xorshift generator, which is plenty for making test pages and needs no dependencies
+struct Random(u64);
+
+impl Random {
+    fn new(seed: u64) -> Random {
+        // Xorshift gets stuck at zero, so mix the seed into a constant
+        Random(seed ^ 0x9e37_79b9_7f4a_7c15)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    // A value from 0 up to but not including the limit
+    fn below(&mut self, limit: u32) -> u32 {
+        (self.next() % limit as u64) as u32
+    }
+}
+
+// Draw a page of panels from a spec
+pub fn page(spec: &PageSpec) -> Page {
+    let mut random = Random::new(spec.seed);
+    let width = spec.columns * spec.panel_width + (spec.columns + 1) * spec.gutter;
+    let height = spec.rows * spec.panel_height + (spec.rows + 1) * spec.gutter;
+    let [red, green, blue] = spec.gutter_color;
+    let mut image = RgbaImage::from_pixel(width, height, Rgba { data: [red, green, blue, 255] });
+
+    // Fill each panel with a dark colour of its own, with a lighter band across it so it isn't a flat block
+    let mut panels = Vec::new();
+    for row in 0 .. spec.rows {
+        for column in 0 .. spec.columns {
+            let x = spec.gutter + column * (spec.panel_width + spec.gutter);
+            let y = spec.gutter + row * (spec.panel_height + spec.gutter);
+            let color = [random.below(128) as u8, random.below(128) as u8, random.below(128) as u8];
+            let band = spec.panel_height / 3 .. spec.panel_height * 2 / 3;
+            for panel_y in 0 .. spec.panel_height {
+                let lift = if band.contains(&panel_y) { 64 } else { 0 };
+                for panel_x in 0 .. spec.panel_width {
+                    image.put_pixel(x + panel_x, y + panel_y, Rgba { data: [color[0] + lift, color[1] + lift, color[2] + lift, 255] });
+                }
+            }
+            panels.push((x, y, spec.panel_width, spec.panel_height));
+        }
+    }
+
+    if spec.noise > 0 {
+        let spread = spec.noise as u32 * 2 + 1;
+        for pixel in image.pixels_mut() {
+            for channel in 0 .. 3 {
+                let value = pixel[channel] as i32 + random.below(spread) as i32 - spec.noise as i32;
+                pixel[channel] = value.max(0).min(255) as u8;
+            }
+        }
+    }
+
+    if spec.rotation != 0.0 {
+        image = rotate(&image, spec.rotation, Rgba { data: [red, green, blue, 255] });
+    }
+
+    Page { image, panels }
+}
+
+// Turn an image about its centre, taking the nearest pixel and filling the corners with the background
+fn rotate(image: &RgbaImage, degrees: f64, background: Rgba<u8>) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (centre_x, centre_y) = (width as f64 / 2.0, height as f64 / 2.0);
+
+    RgbaImage::from_fn(width, height, |x, y| {
+        let (dx, dy) = (x as f64 + 0.5 - centre_x, y as f64 + 0.5 - centre_y);
+        let source_x = (dx * cos + dy * sin + centre_x).floor();
+        let source_y = (dy * cos - dx * sin + centre_y).floor();
+        if source_x < 0.0 || source_y < 0.0 || source_x >= width as f64 || source_y >= height as f64 {
+            background
+        } else {
+            *image.get_pixel(source_x as u32, source_y as u32)
+        }
+    })
+}
+
+// Whether guillotining a page finds each of its panels and nothing else, with every edge of every piece within the
+// tolerance of the panel's, in any order
+pub fn finds_panels(config: &GuillotineConfig, page: &Page, tolerance: u32) -> bool {
+    let pieces = guillotine(DynamicImage::ImageRgba8(page.image.clone()), config).pieces;
+    let near = |a: u32, b: u32| (a as i64 - b as i64).unsigned_abs() as u32 <= tolerance;
+
+    pieces.len() == page.panels.len() && page.panels.iter().all(|&(x, y, width, height)| {
+        pieces.iter().any(|piece| {
+            near(piece.x, x) && near(piece.y, y) && near(piece.x + piece.width, x + width) && near(piece.y + piece.height, y + height)
+        })
+    })
+}
@@ -0,0 +1,109 @@
+// Check that the pieces a manifest lists are still there and unchanged since they were written
+
+use std::fs;
+use std::path::Path;
+
+use serde_json;
+
+use sha256::sha256;
+
+// The parts of a manifest that say what each piece should contain
+#[derive(Deserialize)]
+struct Listed {
+    pieces: Vec<ListedPiece>
+}
+
+#[derive(Deserialize)]
+struct ListedPiece {
+    file: String,
+    sha256: Option<String>
+}
+
+// Check every piece of each manifest against its checksum, printing the ones that are missing or changed and
+// returning whether every piece is fine
+pub fn verify(manifests: &[String]) -> bool {
+    let mut fine = true;
+    for manifest in manifests {
+        let path = Path::new(manifest);
+        let listed: Listed = match fs::read(path).map_err(|error| error.to_string())
+            .and_then(|data| serde_json::from_slice(&data).map_err(|error| error.to_string()))
+        {
+            Ok(listed) => listed,
+            Err(error) => {
+                println!("{}: error: {}", manifest, error);
+                fine = false;
+                continue;
+            }
+        };
+
+        // Pieces are listed relative to the manifest's directory
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let (mut checked, mut unchecked) = (0, 0);
+        for piece in &listed.pieces {
+            let piece_path = dir.join(&piece.file);
+            let expected = match piece.sha256 {
+                Some(ref expected) => expected,
+                None => {
+                    unchecked += 1;
+                    continue;
+                }
+            };
+            match fs::read(&piece_path) {
+                Ok(ref data) if sha256(data) == *expected => checked += 1,
+                Ok(_) => {
+                    println!("{}: changed", piece_path.display());
+                    fine = false;
+                },
+                Err(error) => {
+                    println!("{}: missing: {}", piece_path.display(), error);
+                    fine = false;
+                }
+            }
+        }
+
+        if unchecked > 0 {
+            println!("{}: {} pieces fine, {} without a checksum", manifest, checked, unchecked);
+        } else {
+            println!("{}: {} pieces fine", manifest, checked);
+        }
+    }
+    fine
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use image::{Rgba, RgbaImage};
+
+    use manifest::{Manifest, Stamp, Units};
+    use super::verify;
+    use Piece;
+
+    #[test]
+    fn manifest_verifies_until_a_piece_changes_or_goes() {
+        let dir = env::temp_dir().join(format!("autoguillotine-test-{}-verify", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut manifest = Manifest::new("page.png".to_string(), 20, 10, Units::Pixels, Stamp::new(&"settings"));
+        for (index, x) in [0, 10].iter().enumerate() {
+            let piece = Piece::new(RgbaImage::from_pixel(10, 10, Rgba { data: [index as u8 * 100, 0, 0, 255] }), *x, 0);
+            let file = format!("page-{}.bin", index);
+            let data = vec![index as u8; 100];
+            fs::write(dir.join(&file), &data).unwrap();
+            manifest.push(file, &piece, &data);
+        }
+        let path = dir.join("page.json");
+        fs::write(&path, manifest.to_json()).unwrap();
+        let manifests = [path.to_string_lossy().into_owned()];
+
+        assert!(verify(&manifests));
+        fs::write(dir.join("page-0.bin"), vec![1u8; 100]).unwrap();
+        assert!(!verify(&manifests));
+        fs::write(dir.join("page-0.bin"), vec![0u8; 100]).unwrap();
+        fs::remove_file(dir.join("page-1.bin")).unwrap();
+        assert!(!verify(&manifests));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
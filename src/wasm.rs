@@ -0,0 +1,72 @@
+// Cut images in the browser, from the bytes of an image file or the RGBA pixels of an ImageData, without touching the
+// filesystem. Build with `--no-default-features --features wasm` for wasm32-unknown-unknown, which has no threads.
+
+use image::{self, RgbaImage};
+use wasm_bindgen::prelude::*;
+
+use {apply_settings, guillotine, GuillotineConfig};
+
+// What an image was cut into: each piece's rectangle, and each piece as a PNG if they were asked for
+#[wasm_bindgen]
+pub struct Cut {
+    rects: Vec<u32>,
+    pieces: Vec<Vec<u8>>
+}
+
+#[wasm_bindgen]
+impl Cut {
+    // The rectangles of the pieces as their x, y, width and height one after another, in the order they were cut
+    pub fn rects(&self) -> Vec<u32> {
+        self.rects.clone()
+    }
+
+    pub fn count(&self) -> usize {
+        self.rects.len() / 4
+    }
+
+    // A piece as a PNG, or nothing if the pieces weren't asked for
+    pub fn piece(&self, index: usize) -> Option<Vec<u8>> {
+        self.pieces.get(index).cloned()
+    }
+}
+
+fn error<E: ToString>(error: E) -> JsValue {
+    JsValue::from_str(&error.to_string())
+}
+
+// Guillotine an image with the settings, in the form `threshold=0.5,min-size=50`, encoding its pieces as PNGs if
+// they're wanted, keeping their transparency if it had any
+fn cut(image: image::DynamicImage, settings: &str, pieces: bool) -> Result<Cut, JsValue> {
+    let mut config = GuillotineConfig::default();
+    apply_settings(&mut config, settings).map_err(error)?;
+    let has_alpha = matches!(image.color(), image::ColorType::RGBA(_) | image::ColorType::GrayA(_));
+    let guillotined = guillotine(image, &config);
+
+    let rects = guillotined.pieces.iter().flat_map(|piece| vec![piece.x, piece.y, piece.width, piece.height]).collect();
+    let mut encoded = Vec::new();
+    if pieces {
+        for piece in guillotined.pieces {
+            let image = image::DynamicImage::ImageRgba8(piece.image);
+            let image = if has_alpha { image } else { image::DynamicImage::ImageRgb8(image.to_rgb()) };
+            let mut data = Vec::new();
+            image.save(&mut data, image::ImageFormat::PNG).map_err(error)?;
+            encoded.push(data);
+        }
+    }
+    Ok(Cut { rects, pieces: encoded })
+}
+
+// Cut an image file in any format the image crate reads
+#[wasm_bindgen(js_name = cutImage)]
+pub fn cut_image(data: &[u8], settings: &str, pieces: bool) -> Result<Cut, JsValue> {
+    cut(image::load_from_memory(data).map_err(error)?, settings, pieces)
+}
+
+// Cut the RGBA pixels of an ImageData, which are opaque unless any of them aren't, so their alpha isn't compared
+#[wasm_bindgen(js_name = cutImageData)]
+pub fn cut_image_data(pixels: &[u8], width: u32, height: u32, settings: &str, pieces: bool) -> Result<Cut, JsValue> {
+    let image = RgbaImage::from_raw(width, height, pixels.to_vec()).ok_or_else(|| error("there must be width * height * 4 bytes of pixels"))?;
+    let image = image::DynamicImage::ImageRgba8(image);
+    let opaque = pixels.chunks(4).all(|pixel| pixel[3] == 255);
+    cut(if opaque { image::DynamicImage::ImageRgb8(image.to_rgb()) } else { image }, settings, pieces)
+}
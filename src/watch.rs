@@ -0,0 +1,89 @@
+// Watch a hot folder a scanner drops files into, and hand each image on to be cut once it has finished arriving
+
+use std::path::Path;
+
+#[cfg(feature = "watch")]
+use std::collections::BTreeMap;
+#[cfg(feature = "watch")]
+use std::fs;
+#[cfg(feature = "watch")]
+use std::path::PathBuf;
+#[cfg(feature = "watch")]
+use std::sync::mpsc;
+#[cfg(feature = "watch")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "watch")]
+use notify::{self, RecursiveMode, Watcher};
+
+// How long a file has to go without changing before it's taken to have finished arriving, as scanners write pages a
+// strip at a time
+#[cfg(feature = "watch")]
+const SETTLE: Duration = Duration::from_secs(2);
+
+// The extensions of the files that are cut, which leaves out the sidecar files scanners write beside them
+const EXTENSIONS: [&str; 12] = ["png", "jpg", "jpeg", "gif", "bmp", "tif", "tiff", "webp", "psd", "pdf", "zip", "cbz"];
+
+// Whether directories can be watched, which needs the `watch` feature
+pub fn watch_supported() -> bool {
+    cfg!(feature = "watch")
+}
+
+// Whether a file in the watched directory is one to cut, going by its extension and skipping hidden files, which
+// includes the ones other programs write and rename into place
+pub fn is_watched(path: &Path) -> bool {
+    let hidden = path.file_name().map_or(true, |name| name.to_string_lossy().starts_with('.'));
+    let extension = path.extension().map(|extension| extension.to_string_lossy().to_lowercase());
+    !hidden && extension.map_or(false, |extension| EXTENSIONS.contains(&extension.as_str())) && path.is_file()
+}
+
+// Hand on every file to cut in a directory, and then each one that's added or changed once it settles, until the
+// watch fails. Only the directory itself is watched, so pieces written into directories inside it aren't cut again.
+#[cfg(feature = "watch")]
+pub fn watch<F: FnMut(&Path)>(dir: &Path, mut handle: F) -> Result<(), String> {
+    let (sender, receiver) = mpsc::channel::<notify::Result<notify::Event>>();
+    let mut watcher = notify::recommended_watcher(sender).map_err(|error| error.to_string())?;
+    watcher.watch(dir, RecursiveMode::NonRecursive).map_err(|error| error.to_string())?;
+
+    let mut existing: Vec<PathBuf> = fs::read_dir(dir).map_err(|error| error.to_string())?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| is_watched(path))
+        .collect();
+    existing.sort();
+    for path in existing {
+        handle(&path);
+    }
+
+    // The files that have changed, and when they last did
+    let mut pending: BTreeMap<PathBuf, Instant> = BTreeMap::new();
+    loop {
+        match receiver.recv_timeout(SETTLE) {
+            Ok(Ok(event)) => {
+                if event.kind.is_create() || event.kind.is_modify() {
+                    for path in event.paths {
+                        pending.insert(path, Instant::now());
+                    }
+                }
+            },
+            Ok(Err(error)) => return Err(error.to_string()),
+            Err(mpsc::RecvTimeoutError::Timeout) => {},
+            Err(mpsc::RecvTimeoutError::Disconnected) => return Err("the watch stopped".to_string())
+        }
+
+        let settled: Vec<PathBuf> = pending.iter()
+            .filter(|&(_, changed)| changed.elapsed() >= SETTLE)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in settled {
+            pending.remove(&path);
+            if is_watched(&path) {
+                handle(&path);
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "watch"))]
+pub fn watch<F: FnMut(&Path)>(_dir: &Path, _handle: F) -> Result<(), String> {
+    Err("watching needs the watch feature".to_string())
+}